@@ -0,0 +1,111 @@
+// region:    --- Modules
+
+mod error;
+
+pub use self::error::{Error, Result};
+
+use lib_utils::b64::b64u_encode;
+use rand::RngCore;
+use std::fmt::Display;
+use std::str::FromStr;
+
+// endregion: --- Modules
+
+const PREFIX_BYTES: usize = 8;
+const SECRET_BYTES: usize = 32;
+
+// region:    --- ApiKey Type
+
+/// String format: `prefix.secret`
+///
+/// - `prefix` is stored in clear and used to look up the key.
+/// - `secret` is only shown once at creation, the db only stores its hash.
+#[derive(Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct ApiKey {
+    pub prefix: String,
+    pub secret: String,
+}
+
+impl ApiKey {
+    /// Generate a new random api key.
+    pub fn generate() -> Self {
+        Self {
+            prefix: random_b64u(PREFIX_BYTES),
+            secret: random_b64u(SECRET_BYTES),
+        }
+    }
+}
+
+impl FromStr for ApiKey {
+    type Err = Error;
+
+    fn from_str(key_str: &str) -> std::result::Result<Self, Self::Err> {
+        let (prefix, secret) = key_str.split_once('.').ok_or(Error::InvalidFormat)?;
+
+        if prefix.is_empty() || secret.is_empty() || secret.contains('.') {
+            return Err(Error::InvalidFormat);
+        }
+
+        Ok(Self {
+            prefix: prefix.to_string(),
+            secret: secret.to_string(),
+        })
+    }
+}
+
+impl Display for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.prefix, self.secret)
+    }
+}
+
+// endregion: --- ApiKey Type
+
+fn random_b64u(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    b64u_encode(bytes)
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_api_key_display_from_str_ok() -> Result<()> {
+        // -- Fixtures
+        let fx_api_key = ApiKey::generate();
+
+        // -- Exec
+        let api_key: ApiKey = fx_api_key.to_string().parse()?;
+
+        // -- Check
+        assert_eq!(api_key, fx_api_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_key_from_str_err_invalid_format() -> Result<()> {
+        // -- Fixtures
+        let fx_key_strs = ["no-separator", ".only-secret", "only-prefix.", "a.b.c"];
+
+        for fx_key_str in fx_key_strs {
+            // -- Exec
+            let res = fx_key_str.parse::<ApiKey>();
+
+            // -- Check
+            assert!(
+                matches!(res, Err(Error::InvalidFormat)),
+                "Should have matched `Err(Error::InvalidFormat)` for `{fx_key_str}` but was `{res:?}`"
+            );
+        }
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
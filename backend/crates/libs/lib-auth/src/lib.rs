@@ -1,5 +1,8 @@
+pub mod api_key;
 mod config;
+mod mac;
 pub mod pwd;
 pub mod token;
+pub mod webhook;
 
 use config::auth_config;
@@ -0,0 +1,71 @@
+//! HMAC-SHA-512 shared by the password hash, the web token and the webhook signatures.
+
+use hmac::digest::InvalidLength;
+use hmac::{Hmac, Mac};
+use lib_utils::b64::{b64u_decode, b64u_encode};
+use sha2::Sha512;
+
+/// HMAC-SHA-512 of the parts (in order, without separator), b64u encoded.
+pub(crate) fn hmac_sha512_b64u(key: &[u8], parts: &[&[u8]]) -> Result<String, InvalidLength> {
+    let hmac_sha512 = new_hmac_sha512(key, parts)?;
+
+    Ok(b64u_encode(hmac_sha512.finalize().into_bytes()))
+}
+
+/// Check a b64u signature produced by `hmac_sha512_b64u`.
+///
+/// The comparison is done in constant time, so it does not leak how much of the signature matched.
+pub(crate) fn hmac_sha512_verify_b64u(
+    key: &[u8],
+    parts: &[&[u8]],
+    sign_b64u: &str,
+) -> Result<bool, InvalidLength> {
+    let hmac_sha512 = new_hmac_sha512(key, parts)?;
+
+    let Ok(sign) = b64u_decode(sign_b64u) else {
+        return Ok(false);
+    };
+
+    Ok(hmac_sha512.verify_slice(&sign).is_ok())
+}
+
+fn new_hmac_sha512(key: &[u8], parts: &[&[u8]]) -> Result<Hmac<Sha512>, InvalidLength> {
+    // -- Create a HMAC-SHA-512 from key.
+    let mut hmac_sha512 = Hmac::<Sha512>::new_from_slice(key)?;
+
+    // -- Add content.
+    for part in parts {
+        hmac_sha512.update(part);
+    }
+
+    Ok(hmac_sha512)
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_hmac_sha512_verify_b64u() -> Result<()> {
+        // -- Fixtures
+        let fx_key = b"some-key";
+        let fx_parts: &[&[u8]] = &[b"hello", b".", b"world"];
+
+        // -- Exec
+        let sign = hmac_sha512_b64u(fx_key, fx_parts)?;
+
+        // -- Check
+        // parts are concatenated
+        assert_eq!(sign, hmac_sha512_b64u(fx_key, &[b"hello.world"])?);
+        assert!(hmac_sha512_verify_b64u(fx_key, fx_parts, &sign)?);
+        assert!(!hmac_sha512_verify_b64u(b"other-key", fx_parts, &sign)?);
+        assert!(!hmac_sha512_verify_b64u(fx_key, &[b"hello"], &sign)?);
+        assert!(!hmac_sha512_verify_b64u(fx_key, fx_parts, &sign[1..])?);
+        assert!(!hmac_sha512_verify_b64u(fx_key, fx_parts, "not b64u!")?);
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -5,10 +5,9 @@ mod error;
 pub use self::error::{Error, Result};
 
 use crate::config::auth_config;
-use hmac::{Hmac, Mac};
+use crate::mac::{hmac_sha512_b64u, hmac_sha512_verify_b64u};
 use lib_utils::b64::{b64u_decode_to_string, b64u_encode};
 use lib_utils::time::{now_utc, now_utc_plus_sec_str, parse_utc};
-use sha2::Sha512;
 use std::fmt::Display;
 use std::str::FromStr;
 use uuid::Uuid;
@@ -58,6 +57,15 @@ impl Display for Token {
     }
 }
 
+impl Token {
+    /// 模拟登录 token 的 ident 为 `imp:<impersonation_id>`
+    pub fn impersonation_id(&self) -> Option<i64> {
+        self.ident
+            .strip_prefix(IMPERSONATION_IDENT_PREFIX)
+            .and_then(|id| id.parse().ok())
+    }
+}
+
 // endregion: --- Token Type
 
 // region:    --- Web Token Gen and Validation
@@ -76,6 +84,24 @@ pub fn validate_web_token(origin_token: &Token, salt: Uuid) -> Result<()> {
 
 // endregion: --- Web Token Gen and Validation
 
+// region:    --- Impersonation Token Gen
+
+// 普通 web token 的 ident 是用户名，加上前缀后不会和用户名混淆
+pub const IMPERSONATION_IDENT_PREFIX: &str = "imp:";
+
+/// 生成有时限的模拟登录 token，使用 validate_web_token 校验
+pub fn generate_impersonation_token(
+    impersonation_id: i64,
+    duration_sec: f64,
+    salt: Uuid,
+) -> Result<Token> {
+    let config = &auth_config();
+    let ident = format!("{IMPERSONATION_IDENT_PREFIX}{impersonation_id}");
+    _generate_token(&ident, duration_sec, salt, &config.TOKEN_KEY)
+}
+
+// endregion: --- Impersonation Token Gen
+
 // region:    --- (private) Token Gen and Validation
 
 fn _generate_token(ident: &str, duration_sec: f64, salt: Uuid, key: &[u8]) -> Result<Token> {
@@ -95,9 +121,15 @@ fn _generate_token(ident: &str, duration_sec: f64, salt: Uuid, key: &[u8]) -> Re
 
 fn _validate_token_sign_and_exp(origin_token: &Token, salt: Uuid, key: &[u8]) -> Result<()> {
     // -- Validate signature.
-    let new_sign_b64u = _token_sign_into_b64u(&origin_token.ident, &origin_token.exp, salt, key)?;
-
-    if new_sign_b64u != origin_token.sign_b64u {
+    let content = _token_sign_content(&origin_token.ident, &origin_token.exp);
+    let matching = hmac_sha512_verify_b64u(
+        key,
+        &[content.as_bytes(), salt.as_bytes()],
+        &origin_token.sign_b64u,
+    )
+    .map_err(|_| Error::HmacFailNewFromSlice)?;
+
+    if !matching {
         return Err(Error::SignatureNotMatching);
     }
 
@@ -115,22 +147,15 @@ fn _validate_token_sign_and_exp(origin_token: &Token, salt: Uuid, key: &[u8]) ->
 /// Create token signature from token parts
 /// and salt.
 fn _token_sign_into_b64u(ident: &str, exp: &str, salt: Uuid, key: &[u8]) -> Result<String> {
-    let content = format!("{}.{}", b64u_encode(ident), b64u_encode(exp));
-
-    // -- Create a HMAC-SHA-512 from key.
-    let mut hmac_sha512 =
-        Hmac::<Sha512>::new_from_slice(key).map_err(|_| Error::HmacFailNewFromSlice)?;
-
-    // -- Add content.
-    hmac_sha512.update(content.as_bytes());
-    hmac_sha512.update(salt.as_bytes());
+    let content = _token_sign_content(ident, exp);
 
-    // -- Finalize and b64u encode.
-    let hmac_result = hmac_sha512.finalize();
-    let result_bytes = hmac_result.into_bytes();
-    let result = b64u_encode(result_bytes);
+    hmac_sha512_b64u(key, &[content.as_bytes(), salt.as_bytes()])
+        .map_err(|_| Error::HmacFailNewFromSlice)
+}
 
-    Ok(result)
+/// Signed content, the salt is appended after it.
+fn _token_sign_content(ident: &str, exp: &str) -> String {
+    format!("{}.{}", b64u_encode(ident), b64u_encode(exp))
 }
 
 // endregion: --- (private) Token Gen and Validation
@@ -220,5 +245,23 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_impersonation_token_ok() -> Result<()> {
+        // -- Setup & Fixtures
+        let fx_salt = Uuid::parse_str("f05e8961-d6ad-4086-9e78-a6de065e5453").unwrap();
+        let fx_token = generate_impersonation_token(1001, 60.0, fx_salt)?;
+
+        // -- Exec
+        let token: Token = fx_token.to_string().parse()?;
+        let user_token = _generate_token("imp", 60.0, fx_salt, &auth_config().TOKEN_KEY)?;
+
+        // -- Check
+        validate_web_token(&token, fx_salt)?;
+        assert_eq!(token.impersonation_id(), Some(1001));
+        assert_eq!(user_token.impersonation_id(), None);
+
+        Ok(())
+    }
 }
 // endregion: --- Tests
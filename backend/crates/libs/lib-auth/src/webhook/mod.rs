@@ -0,0 +1,128 @@
+// region:    --- Modules
+
+mod error;
+
+pub use self::error::{Error, Result};
+
+use crate::mac::{hmac_sha512_b64u, hmac_sha512_verify_b64u};
+use lib_utils::b64::b64u_encode;
+use rand::RngCore;
+
+// endregion: --- Modules
+
+const SECRET_PREFIX: &str = "whsec_";
+const SECRET_BYTES: usize = 32;
+
+/// Header carrying the signature, format: `t=<unix timestamp>,v1=<signature>`
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+/// Generate a new random webhook secret.
+///
+/// Unlike api keys, the secret is stored in clear since it is needed to sign each delivery.
+pub fn generate_secret() -> String {
+    let mut bytes = vec![0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    format!("{SECRET_PREFIX}{}", b64u_encode(bytes))
+}
+
+/// Sign a webhook body, returns the `SIGNATURE_HEADER` value.
+///
+/// The timestamp is part of the signed content so receivers can reject replayed deliveries.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> Result<String> {
+    let timestamp_str = timestamp.to_string();
+    let signature = hmac_sha512_b64u(secret.as_bytes(), &signed_parts(&timestamp_str, body))
+        .map_err(|_| Error::HmacFailNewFromSlice)?;
+
+    Ok(format!("t={timestamp},v1={signature}"))
+}
+
+/// Verify a `SIGNATURE_HEADER` value against the body, returns the signed timestamp.
+pub fn verify(secret: &str, header: &str, body: &[u8]) -> Result<i64> {
+    let (timestamp, signature) = parse_header(header).ok_or(Error::SignatureInvalidFormat)?;
+
+    let timestamp_str = timestamp.to_string();
+    let matching = hmac_sha512_verify_b64u(
+        secret.as_bytes(),
+        &signed_parts(&timestamp_str, body),
+        signature,
+    )
+    .map_err(|_| Error::HmacFailNewFromSlice)?;
+    if !matching {
+        return Err(Error::SignatureNotMatching);
+    }
+
+    Ok(timestamp)
+}
+
+// region:    --- (private) Signature
+
+/// Signed content: `<timestamp>.<body>`
+fn signed_parts<'a>(timestamp: &'a str, body: &'a [u8]) -> [&'a [u8]; 3] {
+    [timestamp.as_bytes(), b".", body]
+}
+
+fn parse_header(header: &str) -> Option<(i64, &str)> {
+    let (timestamp, signature) = header.split_once(',')?;
+    let timestamp = timestamp.strip_prefix("t=")?.parse().ok()?;
+    let signature = signature.strip_prefix("v1=")?;
+
+    Some((timestamp, signature))
+}
+
+// endregion: --- (private) Signature
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_webhook_sign_verify_ok() -> Result<()> {
+        // -- Fixtures
+        let fx_secret = generate_secret();
+        let fx_body = br#"{"type":"PasswordChanged","data":{"user_id":1000}}"#;
+
+        // -- Exec
+        let header = sign(&fx_secret, 1_700_000_000, fx_body)?;
+        let timestamp = verify(&fx_secret, &header, fx_body)?;
+
+        // -- Check
+        assert!(fx_secret.starts_with(SECRET_PREFIX));
+        assert!(header.starts_with("t=1700000000,v1="));
+        assert_eq!(timestamp, 1_700_000_000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_webhook_verify_err() -> Result<()> {
+        // -- Fixtures
+        let fx_secret = generate_secret();
+        let fx_body = b"{}";
+        let header = sign(&fx_secret, 1_700_000_000, fx_body)?;
+
+        // -- Exec
+        let other_secret_res = verify(&generate_secret(), &header, fx_body);
+        let other_body_res = verify(&fx_secret, &header, b"{ }");
+        let other_timestamp_res = verify(
+            &fx_secret,
+            &header.replace("t=1700000000", "t=1700000001"),
+            fx_body,
+        );
+        let format_res = verify(&fx_secret, "v1=abc", fx_body);
+
+        // -- Check
+        assert!(matches!(other_secret_res, Err(Error::SignatureNotMatching)));
+        assert!(matches!(other_body_res, Err(Error::SignatureNotMatching)));
+        assert!(matches!(
+            other_timestamp_res,
+            Err(Error::SignatureNotMatching)
+        ));
+        assert!(matches!(format_res, Err(Error::SignatureInvalidFormat)));
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -0,0 +1,637 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Expr, Fields, LitStr, Path, Result};
+
+#[derive(Default)]
+struct BmcAttrs {
+    table: Option<String>,
+    owner_column: Option<String>,
+    owner_fill: Option<String>,
+    tenant_column: Option<String>,
+    soft_delete: bool,
+    versioned: bool,
+    timestamps: bool,
+    history: bool,
+    user_data: Option<Expr>,
+    deleted_event: Option<Path>,
+    filter: Option<Path>,
+    for_create: Option<Path>,
+    for_update: Option<Path>,
+}
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let attrs = parse_attrs(&input)?;
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Bmc can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Bmc requires named fields",
+        ));
+    };
+    let columns: Vec<String> = fields
+        .named
+        .iter()
+        .filter_map(|f| f.ident.as_ref().map(|i| i.to_string()))
+        .collect();
+
+    let vis = &input.vis;
+    let entity = &input.ident;
+    let bmc = format_ident!("{entity}Bmc");
+    let iden = format_ident!("{entity}Iden");
+    let table = attrs
+        .table
+        .clone()
+        .unwrap_or_else(|| to_snake_case(&entity.to_string()));
+
+    let db_bmc = expand_db_bmc(&bmc, &table, &attrs);
+    let iden_enum = expand_iden(vis, &iden, &table, &columns);
+    let methods = expand_methods(entity, &attrs);
+
+    Ok(quote! {
+        #vis struct #bmc {}
+
+        #db_bmc
+
+        #iden_enum
+
+        impl #bmc {
+            #methods
+        }
+    })
+}
+
+fn parse_attrs(input: &DeriveInput) -> Result<BmcAttrs> {
+    let mut attrs = BmcAttrs::default();
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("bmc")) {
+        attr.parse_nested_meta(|meta| {
+            let path = &meta.path;
+
+            if path.is_ident("table") {
+                attrs.table = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if path.is_ident("owner_column") {
+                attrs.owner_column = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if path.is_ident("owner_fill") {
+                attrs.owner_fill = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if path.is_ident("tenant_column") {
+                attrs.tenant_column = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if path.is_ident("soft_delete") {
+                attrs.soft_delete = true;
+            } else if path.is_ident("versioned") {
+                attrs.versioned = true;
+            } else if path.is_ident("timestamps") {
+                attrs.timestamps = true;
+            } else if path.is_ident("history") {
+                attrs.history = true;
+            } else if path.is_ident("user_data") {
+                attrs.user_data = Some(meta.value()?.parse()?);
+            } else if path.is_ident("deleted_event") {
+                attrs.deleted_event = Some(meta.value()?.parse()?);
+            } else if path.is_ident("filter") {
+                attrs.filter = Some(meta.value()?.parse()?);
+            } else if path.is_ident("for_create") {
+                attrs.for_create = Some(meta.value()?.parse()?);
+            } else if path.is_ident("for_update") {
+                attrs.for_update = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("unknown bmc attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    if attrs.versioned && attrs.for_update.is_none() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "bmc(versioned) requires bmc(for_update = ..)",
+        ));
+    }
+
+    Ok(attrs)
+}
+
+fn expand_db_bmc(bmc: &Ident, table: &str, attrs: &BmcAttrs) -> TokenStream {
+    let option_str = |value: &Option<String>| match value {
+        Some(value) => quote! { Some(#value) },
+        None => quote! { None },
+    };
+    let owner_column = option_str(&attrs.owner_column);
+    let owner_fill = option_str(&attrs.owner_fill);
+    let tenant_column = option_str(&attrs.tenant_column);
+    let soft_delete = attrs.soft_delete;
+    let versioned = attrs.versioned;
+    let timestamps = attrs.timestamps;
+    let history = attrs.history;
+    let user_data = match &attrs.user_data {
+        Some(user_data) => quote! { #user_data },
+        None => quote! { &[] },
+    };
+    let deleted_event = match &attrs.deleted_event {
+        Some(deleted_event) => quote! { Some(#deleted_event) },
+        None => quote! { None },
+    };
+
+    quote! {
+        impl crate::model::base::DbBmc for #bmc {
+            const TABLE: &'static str = #table;
+            const OWNER_COLUMN: Option<&'static str> = #owner_column;
+            const OWNER_FILL_COLUMN: Option<&'static str> = #owner_fill;
+            const TENANT_COLUMN: Option<&'static str> = #tenant_column;
+            const SOFT_DELETE: bool = #soft_delete;
+            const VERSIONED: bool = #versioned;
+            const TIMESTAMPS: bool = #timestamps;
+            const HISTORY: bool = #history;
+            const USER_DATA: &'static [crate::model::UserData] = #user_data;
+            const DELETED_EVENT: Option<
+                fn(&crate::ctx::Ctx, i64) -> crate::model::outbox::DomainEvent,
+            > = #deleted_event;
+        }
+    }
+}
+
+fn expand_iden(
+    vis: &syn::Visibility,
+    iden: &Ident,
+    table: &str,
+    columns: &[String],
+) -> TokenStream {
+    let variants: Vec<Ident> = columns
+        .iter()
+        .map(|c| format_ident!("{}", to_pascal_case(c)))
+        .collect();
+
+    quote! {
+        #[derive(Debug, Clone, Copy)]
+        #vis enum #iden {
+            Table,
+            #(#variants,)*
+        }
+
+        impl sea_query::Iden for #iden {
+            fn unquoted(&self, s: &mut dyn std::fmt::Write) {
+                let name = match self {
+                    Self::Table => #table,
+                    #(Self::#variants => #columns,)*
+                };
+                s.write_str(name).unwrap();
+            }
+        }
+    }
+}
+
+fn expand_methods(entity: &Ident, attrs: &BmcAttrs) -> TokenStream {
+    let filter = match &attrs.filter {
+        Some(filter) => quote! { #filter },
+        None => quote! { modql::filter::FilterGroups },
+    };
+
+    let mut methods = quote! {
+        pub async fn get(
+            ctx: &crate::ctx::Ctx,
+            mm: &crate::model::ModelManager,
+            id: i64,
+        ) -> crate::model::Result<#entity> {
+            crate::model::base::get::<Self, _>(ctx, mm, id).await
+        }
+
+        pub async fn list(
+            ctx: &crate::ctx::Ctx,
+            mm: &crate::model::ModelManager,
+            filter: Option<#filter>,
+            list_options: Option<modql::filter::ListOptions>,
+        ) -> crate::model::Result<Vec<#entity>> {
+            crate::model::base::list::<Self, _, _>(ctx, mm, filter, list_options).await
+        }
+
+        pub async fn delete(
+            ctx: &crate::ctx::Ctx,
+            mm: &crate::model::ModelManager,
+            id: i64,
+        ) -> crate::model::Result<()> {
+            crate::model::base::delete::<Self>(ctx, mm, id).await
+        }
+
+        pub async fn delete_many(
+            ctx: &crate::ctx::Ctx,
+            mm: &crate::model::ModelManager,
+            filter: #filter,
+        ) -> crate::model::Result<u64> {
+            crate::model::base::delete_many::<Self, _>(ctx, mm, filter).await
+        }
+    };
+
+    if let Some(for_create) = &attrs.for_create {
+        methods.extend(quote! {
+            pub async fn create(
+                ctx: &crate::ctx::Ctx,
+                mm: &crate::model::ModelManager,
+                data: #for_create,
+            ) -> crate::model::Result<i64> {
+                crate::model::base::create::<Self, _>(ctx, mm, data).await
+            }
+
+            pub async fn create_many(
+                ctx: &crate::ctx::Ctx,
+                mm: &crate::model::ModelManager,
+                data: Vec<#for_create>,
+            ) -> crate::model::Result<Vec<i64>> {
+                crate::model::base::create_many::<Self, _>(ctx, mm, data).await
+            }
+        });
+    }
+
+    if let Some(for_update) = &attrs.for_update {
+        methods.extend(quote! {
+            pub async fn update(
+                ctx: &crate::ctx::Ctx,
+                mm: &crate::model::ModelManager,
+                id: i64,
+                data: #for_update,
+            ) -> crate::model::Result<()> {
+                crate::model::base::update::<Self, _>(ctx, mm, id, data).await
+            }
+
+            pub async fn update_many(
+                ctx: &crate::ctx::Ctx,
+                mm: &crate::model::ModelManager,
+                filter: #filter,
+                data: #for_update,
+            ) -> crate::model::Result<u64> {
+                crate::model::base::update_many::<Self, _, _>(ctx, mm, filter, data).await
+            }
+        });
+
+        if attrs.versioned {
+            methods.extend(quote! {
+                /// 只有在 version 未被其他请求修改时才更新，返回更新后的 version
+                pub async fn update_versioned(
+                    ctx: &crate::ctx::Ctx,
+                    mm: &crate::model::ModelManager,
+                    id: i64,
+                    expected_version: i64,
+                    data: #for_update,
+                ) -> crate::model::Result<i64> {
+                    crate::model::base::update_versioned::<Self, _>(
+                        ctx,
+                        mm,
+                        id,
+                        expected_version,
+                        data,
+                    )
+                    .await
+                }
+            });
+        }
+    }
+
+    if attrs.soft_delete {
+        methods.extend(quote! {
+            /// 和 list 一样，但可以指定是否包含已删除的数据
+            pub async fn list_scoped(
+                ctx: &crate::ctx::Ctx,
+                mm: &crate::model::ModelManager,
+                filter: Option<#filter>,
+                list_options: Option<modql::filter::ListOptions>,
+                deleted_scope: crate::model::DeletedScope,
+            ) -> crate::model::Result<Vec<#entity>> {
+                crate::model::base::list_scoped::<Self, _, _>(
+                    ctx,
+                    mm,
+                    filter,
+                    list_options,
+                    deleted_scope,
+                )
+                .await
+            }
+
+            pub async fn restore(
+                ctx: &crate::ctx::Ctx,
+                mm: &crate::model::ModelManager,
+                id: i64,
+            ) -> crate::model::Result<()> {
+                crate::model::base::restore::<Self>(ctx, mm, id).await
+            }
+
+            /// 永久删除 older_than_days 天之前删除的数据
+            pub async fn purge(
+                ctx: &crate::ctx::Ctx,
+                mm: &crate::model::ModelManager,
+                older_than_days: u32,
+            ) -> crate::model::Result<u64> {
+                crate::model::base::purge::<Self>(ctx, mm, older_than_days).await
+            }
+        });
+    }
+
+    if attrs.history {
+        methods.extend(quote! {
+            /// 修改历史，最新的在前
+            pub async fn list_history(
+                ctx: &crate::ctx::Ctx,
+                mm: &crate::model::ModelManager,
+                id: i64,
+            ) -> crate::model::Result<Vec<crate::model::EntityHistory>> {
+                crate::model::base::list_history::<Self>(ctx, mm, id).await
+            }
+
+            /// 恢复为某条历史记录中的数据
+            pub async fn restore_revision(
+                ctx: &crate::ctx::Ctx,
+                mm: &crate::model::ModelManager,
+                id: i64,
+                history_id: i64,
+            ) -> crate::model::Result<()> {
+                crate::model::base::restore_revision::<Self>(ctx, mm, id, history_id).await
+            }
+        });
+    }
+
+    methods
+}
+
+// "ApiKey" => "api_key"
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, ch) in name.chars().enumerate() {
+        if ch.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(ch.to_lowercase());
+        } else {
+            snake.push(ch);
+        }
+    }
+    snake
+}
+
+// "deleted_at" => "DeletedAt"
+fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::ToTokens;
+    use std::collections::BTreeMap;
+
+    // 展开后的 Bmc，只保留测试需要检查的部分
+    struct Expanded {
+        bmc: String,
+        // impl DbBmc 中的常量和值
+        consts: BTreeMap<String, String>,
+        // impl XxxBmc 中的方法名和签名
+        methods: BTreeMap<String, String>,
+        iden_variants: Vec<String>,
+        iden_impl: String,
+    }
+
+    fn expand_str(input: &str) -> Result<Expanded> {
+        let file: syn::File = syn::parse2(expand(syn::parse_str(input)?)?)?;
+
+        let mut expanded = Expanded {
+            bmc: String::new(),
+            consts: BTreeMap::new(),
+            methods: BTreeMap::new(),
+            iden_variants: Vec::new(),
+            iden_impl: String::new(),
+        };
+        for item in file.items {
+            match item {
+                syn::Item::Struct(item) => expanded.bmc = item.ident.to_string(),
+                syn::Item::Enum(item) => {
+                    expanded.iden_variants =
+                        item.variants.iter().map(|v| v.ident.to_string()).collect();
+                }
+                syn::Item::Impl(item) => {
+                    let trait_name = item
+                        .trait_
+                        .as_ref()
+                        .map(|(_, path, _)| path.to_token_stream().to_string());
+                    match trait_name.as_deref() {
+                        Some("sea_query :: Iden") => {
+                            expanded.iden_impl = item.to_token_stream().to_string();
+                        }
+                        Some(_) => {
+                            for impl_item in item.items {
+                                if let syn::ImplItem::Const(c) = impl_item {
+                                    expanded.consts.insert(
+                                        c.ident.to_string(),
+                                        c.expr.to_token_stream().to_string(),
+                                    );
+                                }
+                            }
+                        }
+                        None => {
+                            for impl_item in item.items {
+                                if let syn::ImplItem::Fn(f) = impl_item {
+                                    expanded.methods.insert(
+                                        f.sig.ident.to_string(),
+                                        f.sig.to_token_stream().to_string(),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(expanded)
+    }
+
+    fn tokens(tokens: TokenStream) -> String {
+        tokens.to_string()
+    }
+
+    #[test]
+    fn test_expand_defaults() -> Result<()> {
+        // -- Exec
+        let expanded = expand_str("pub struct ApiKey { id: i64, user_id: i64 }")?;
+
+        // -- Check
+        assert_eq!(expanded.bmc, "ApiKeyBmc");
+        let expected_consts = [
+            ("TABLE", tokens(quote! { "api_key" })),
+            ("OWNER_COLUMN", tokens(quote! { None })),
+            ("OWNER_FILL_COLUMN", tokens(quote! { None })),
+            ("TENANT_COLUMN", tokens(quote! { None })),
+            ("SOFT_DELETE", tokens(quote! { false })),
+            ("VERSIONED", tokens(quote! { false })),
+            ("TIMESTAMPS", tokens(quote! { false })),
+            ("HISTORY", tokens(quote! { false })),
+            ("USER_DATA", tokens(quote! { &[] })),
+            ("DELETED_EVENT", tokens(quote! { None })),
+        ];
+        assert_eq!(
+            expanded.consts,
+            expected_consts
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect()
+        );
+        assert_eq!(
+            expanded.methods.keys().collect::<Vec<_>>(),
+            ["delete", "delete_many", "get", "list"]
+        );
+        assert!(expanded.methods["list"].contains("modql :: filter :: FilterGroups"));
+        assert_eq!(expanded.iden_variants, ["Table", "Id", "UserId"]);
+        assert!(expanded.iden_impl.contains(r#"Self :: Table => "api_key""#));
+        assert!(expanded
+            .iden_impl
+            .contains(r#"Self :: UserId => "user_id""#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_column_attrs() -> Result<()> {
+        // -- Exec
+        let expanded = expand_str(
+            r#"
+            #[bmc(
+                table = "projects",
+                owner_column = "user_id",
+                owner_fill = "owner_id",
+                tenant_column = "org_id",
+                user_data = &[UserData::delete("projects", "owner_id")],
+                deleted_event = project_deleted_event,
+                filter = ProjectFilter
+            )]
+            pub struct Project { id: i64 }
+            "#,
+        )?;
+
+        // -- Check
+        let consts = &expanded.consts;
+        assert_eq!(consts["TABLE"], tokens(quote! { "projects" }));
+        assert_eq!(consts["OWNER_COLUMN"], tokens(quote! { Some("user_id") }));
+        assert_eq!(
+            consts["OWNER_FILL_COLUMN"],
+            tokens(quote! { Some("owner_id") })
+        );
+        assert_eq!(consts["TENANT_COLUMN"], tokens(quote! { Some("org_id") }));
+        assert_eq!(
+            consts["USER_DATA"],
+            tokens(quote! { &[UserData::delete("projects", "owner_id")] })
+        );
+        assert_eq!(
+            consts["DELETED_EVENT"],
+            tokens(quote! { Some(project_deleted_event) })
+        );
+        assert!(expanded.methods["list"].contains("Option < ProjectFilter >"));
+        assert!(expanded.methods["delete_many"].contains("filter : ProjectFilter"));
+        assert!(expanded
+            .iden_impl
+            .contains(r#"Self :: Table => "projects""#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_flag_combinations() -> Result<()> {
+        // -- Fixtures
+        let fx_flags = [
+            "for_create = ItemForCreate",
+            "for_update = ItemForUpdate",
+            "versioned",
+            "soft_delete",
+            "timestamps",
+            "history",
+        ];
+
+        // -- Exec & Check
+        // 每种属性组合生成的常量和方法
+        for mask in 0..(1u32 << fx_flags.len()) {
+            let on = |i: usize| mask & (1 << i) != 0;
+            let attrs: Vec<&str> = (0..fx_flags.len())
+                .filter(|i| on(*i))
+                .map(|i| fx_flags[i])
+                .collect();
+            let input = format!("#[bmc({})] struct Item {{ id: i64 }}", attrs.join(", "));
+            let (for_create, for_update, versioned, soft_delete, timestamps, history) =
+                (on(0), on(1), on(2), on(3), on(4), on(5));
+
+            if versioned && !for_update {
+                assert!(expand_str(&input).is_err(), "{input}");
+                continue;
+            }
+            let expanded = expand_str(&input)?;
+
+            let mut expected = vec!["get", "list", "delete", "delete_many"];
+            if for_create {
+                expected.extend(["create", "create_many"]);
+            }
+            if for_update {
+                expected.extend(["update", "update_many"]);
+            }
+            if versioned {
+                expected.push("update_versioned");
+            }
+            if soft_delete {
+                expected.extend(["list_scoped", "restore", "purge"]);
+            }
+            if history {
+                expected.extend(["list_history", "restore_revision"]);
+            }
+            expected.sort();
+            assert_eq!(
+                expanded.methods.keys().collect::<Vec<_>>(),
+                expected,
+                "{input}"
+            );
+
+            for (name, value) in [
+                ("SOFT_DELETE", soft_delete),
+                ("VERSIONED", versioned),
+                ("TIMESTAMPS", timestamps),
+                ("HISTORY", history),
+            ] {
+                assert_eq!(expanded.consts[name], value.to_string(), "{input}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        // -- Exec & Check
+        for input in [
+            "#[bmc(unknown)] struct Item { id: i64 }",
+            "#[bmc(owner_fill = owner_id)] struct Item { id: i64 }",
+            "#[bmc(versioned)] struct Item { id: i64 }",
+            "enum Item { A }",
+            "struct Item(i64);",
+        ] {
+            assert!(expand_str(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_case_conversion() {
+        assert_eq!(to_snake_case("ApiKey"), "api_key");
+        assert_eq!(to_snake_case("Project"), "project");
+        assert_eq!(to_pascal_case("deleted_at"), "DeletedAt");
+        assert_eq!(to_pascal_case("id"), "Id");
+    }
+}
+// endregion: --- Tests
@@ -0,0 +1,46 @@
+//! lib-core 的 derive 宏
+//!
+//! - `#[derive(Bmc)]` 用在实体的结构体上，生成 `XxxBmc`、`impl DbBmc`、`XxxIden` 和类型化的 CRUD 方法。
+//! - 生成的代码通过 `crate::model` 引用 base 函数，只能在 lib-core 中使用。
+//!
+
+mod bmc;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+/// 为实体生成 Bmc
+///
+/// ```ignore
+/// #[derive(Bmc)]
+/// #[bmc(
+///     table = "project",
+///     tenant_column = "org_id",
+///     owner_fill = "owner_id",
+///     soft_delete,
+///     versioned,
+///     timestamps,
+///     filter = ProjectFilter,
+///     for_create = ProjectForCreate,
+///     for_update = ProjectForUpdate
+/// )]
+/// pub struct Project { .. }
+/// ```
+///
+/// 属性：
+/// - `table`：表名，默认为结构体名的 snake_case
+/// - `owner_column`、`tenant_column`：对应 `DbBmc::OWNER_COLUMN`、`DbBmc::TENANT_COLUMN`
+/// - `owner_fill`：对应 `DbBmc::OWNER_FILL_COLUMN`，生成的 create 会在该列写入 ctx 的用户 id
+/// - `soft_delete`、`versioned`、`timestamps`、`history`：对应 `DbBmc` 的同名开关，并生成相应的方法
+/// - `user_data`：对应 `DbBmc::USER_DATA`，例如 `user_data = &[UserData::delete("project", "owner_id")]`
+/// - `deleted_event`：对应 `DbBmc::DELETED_EVENT`，为 `fn(&Ctx, i64) -> DomainEvent` 的路径
+/// - `filter`：list/update_many/delete_many 使用的 filter 类型，默认为 `FilterGroups`
+/// - `for_create`、`for_update`：设置后才会生成 create、update 相关的方法
+#[proc_macro_derive(Bmc, attributes(bmc))]
+pub fn derive_bmc(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    bmc::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,289 @@
+//! 声明式的开发和测试数据
+//!
+//! - fixture 文件位于 `fixtures/` 目录下，文件名（不含扩展名）即 fixture 的名字，支持 `.toml` 和 `.json`。
+//! - 数据通过 Bmc 层写入，密码通过 `pwd::hash_pwd` 加密，和正常注册的账号一致。
+//! - 按 roles、users、orgs、projects 的顺序写入，后面的数据通过名字引用前面的数据，
+//!   名字也可以引用数据库中已有的数据。
+//!
+
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use serde::Deserialize;
+use tracing::info;
+
+use super::dev_db;
+use crate::{
+    ctx::Ctx,
+    model::{
+        invitation::InvitationBmc,
+        organization::{OrganizationBmc, OrganizationForCreate},
+        project::{ProjectBmc, ProjectForCreate},
+        role::{RoleBmc, RoleForCreate},
+        user::{User, UserBmc, UserForCreate},
+        ModelManager,
+    },
+};
+
+type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>;
+
+const FIXTURE_DIR: &str = "fixtures";
+
+// region:    --- Fixture Types
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+    #[serde(default)]
+    pub roles: Vec<RoleFixture>,
+    #[serde(default)]
+    pub users: Vec<UserFixture>,
+    #[serde(default)]
+    pub orgs: Vec<OrgFixture>,
+    #[serde(default)]
+    pub projects: Vec<ProjectFixture>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoleFixture {
+    pub name: String,
+    // "*" 表示所有权限
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserFixture {
+    pub username: String,
+    // 明文密码，写入时加密
+    pub pwd: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OrgFixture {
+    pub name: String,
+    // 创建者，自动成为 admin
+    pub owner: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectFixture {
+    pub name: String,
+    pub org: String,
+    pub owner: String,
+}
+
+/// 写入后各实体名字到 id 的映射，方便测试中引用
+#[derive(Debug, Default)]
+pub struct FixtureIds {
+    pub roles: HashMap<String, i64>,
+    pub users: HashMap<String, i64>,
+    pub orgs: HashMap<String, i64>,
+    pub projects: HashMap<String, i64>,
+}
+
+// endregion: --- Fixture Types
+
+/// 读取并写入 `fixtures/` 目录下名为 name 的 fixture
+pub async fn load_fixture(mm: &ModelManager, name: &str) -> Result<FixtureIds> {
+    info!("{:<12} - load_fixture: {name}", "FOR-DEV-ONLY");
+
+    let fixture = read_fixture(name)?;
+
+    insert_fixture(mm, fixture).await
+}
+
+pub fn read_fixture(name: &str) -> Result<Fixture> {
+    let dir = fixture_dir();
+
+    let toml_file = dir.join(format!("{name}.toml"));
+    if toml_file.is_file() {
+        return Ok(toml::from_str(&fs::read_to_string(toml_file)?)?);
+    }
+
+    let json_file = dir.join(format!("{name}.json"));
+    if json_file.is_file() {
+        return Ok(serde_json::from_str(&fs::read_to_string(json_file)?)?);
+    }
+
+    Err(format!("fixture '{name}' not found in {dir:?}").into())
+}
+
+pub async fn insert_fixture(mm: &ModelManager, fixture: Fixture) -> Result<FixtureIds> {
+    let root_ctx = Ctx::root_ctx();
+    let mut ids = FixtureIds::default();
+
+    for role in fixture.roles {
+        let role_c = RoleForCreate {
+            name: role.name.clone(),
+            permissions: role.permissions.join(" "),
+        };
+        let id = RoleBmc::create(&root_ctx, mm, role_c).await?;
+        ids.roles.insert(role.name, id);
+    }
+
+    for user in fixture.users {
+        let user_c = UserForCreate {
+            username: user.username.clone(),
+            pwd: user.pwd,
+        };
+        let user_id = UserBmc::create::<UserForCreate>(&root_ctx, mm, user_c).await?;
+
+        for role in &user.roles {
+            let role_id = role_id(mm, &ids, role).await?;
+            RoleBmc::assign(&root_ctx, mm, user_id, role_id).await?;
+        }
+
+        ids.users.insert(user.username, user_id);
+    }
+
+    for org in fixture.orgs {
+        let owner_ctx = Ctx::new(user_id(mm, &ids, &org.owner).await?)?;
+        let org_c = OrganizationForCreate {
+            name: org.name.clone(),
+        };
+        let org_id = OrganizationBmc::create(&owner_ctx, mm, org_c).await?;
+
+        // 和正常流程一样，由 admin 邀请，成员接受邀请
+        for member in org.members {
+            let member_ctx = Ctx::new(user_id(mm, &ids, &member).await?)?;
+            let invitation = InvitationBmc::create(&owner_ctx, mm, org_id, member).await?;
+            InvitationBmc::accept(&member_ctx, mm, &invitation.token).await?;
+        }
+
+        ids.orgs.insert(org.name, org_id);
+    }
+
+    for project in fixture.projects {
+        let org_id = org_id(&ids, &project.org)?;
+        let owner_ctx = Ctx::new(user_id(mm, &ids, &project.owner).await?)?.with_org(org_id);
+        let project_c = ProjectForCreate {
+            name: project.name.clone(),
+        };
+        let project_id = ProjectBmc::create(&owner_ctx, mm, project_c).await?;
+        ids.projects.insert(project.name, project_id);
+    }
+
+    Ok(ids)
+}
+
+/// 所有 fixture 文件，按文件名排序
+pub(super) fn fixture_files() -> std::io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(fixture_dir())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == "toml" || ext == "json")
+        })
+        .collect();
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn fixture_dir() -> PathBuf {
+    dev_db::base_dir().join(FIXTURE_DIR)
+}
+
+// region:    --- Name Lookup
+// 先在本次写入的数据中查找，再到数据库中查找
+
+async fn role_id(mm: &ModelManager, ids: &FixtureIds, name: &str) -> Result<i64> {
+    if let Some(id) = ids.roles.get(name) {
+        return Ok(*id);
+    }
+
+    RoleBmc::first_by_name(&Ctx::root_ctx(), mm, name)
+        .await?
+        .map(|role| role.id)
+        .ok_or_else(|| format!("fixture - role '{name}' not found").into())
+}
+
+async fn user_id(mm: &ModelManager, ids: &FixtureIds, username: &str) -> Result<i64> {
+    if let Some(id) = ids.users.get(username) {
+        return Ok(*id);
+    }
+
+    UserBmc::first_by_username::<User>(&Ctx::root_ctx(), mm, username)
+        .await?
+        .map(|user| user.id)
+        .ok_or_else(|| format!("fixture - user '{username}' not found").into())
+}
+
+// 组织名不唯一，只在本次写入的数据中查找
+fn org_id(ids: &FixtureIds, name: &str) -> Result<i64> {
+    ids.orgs
+        .get(name)
+        .copied()
+        .ok_or_else(|| format!("fixture - org '{name}' not found").into())
+}
+
+// endregion: --- Name Lookup
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use crate::model::organization::Membership;
+    use crate::model::user::UserForLogin;
+    use anyhow::{Context, Result};
+    use lib_auth::pwd::{self, ContentToHash};
+
+    #[tokio::test]
+    async fn test_load_fixture_org_demo() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+
+        // -- Exec
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+
+        // -- Check
+        // 密码和正常注册一样被加密
+        let member: UserForLogin = UserBmc::first_by_username(&root_ctx, mm, "demo_fx_member")
+            .await?
+            .context("Should have user 'demo_fx_member'")?;
+        pwd::validate_pwd(
+            &ContentToHash {
+                content: "welcome".to_string(),
+                salt: member.pwd_salt,
+            },
+            member.pwd.as_deref().context("Should have pwd")?,
+        )?;
+
+        // 组织成员，dev fixture 中的 demo1 也可以被引用
+        let org_id = ids.orgs["Demo Org"];
+        let members: Vec<Membership> =
+            OrganizationBmc::list_members(&Ctx::new(ids.users["demo_fx_admin"])?, mm, org_id)
+                .await?;
+        assert_eq!(members.len(), 3);
+
+        // 项目属于组织和 owner
+        let member_ctx = Ctx::new(member.id)?.with_org(org_id);
+        let project = ProjectBmc::get(&member_ctx, mm, ids.projects["Demo Project"]).await?;
+        assert_eq!(project.org_id, org_id);
+        assert_eq!(project.owner_id, member.id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_fixture_not_found() -> Result<()> {
+        // -- Exec & Check
+        assert!(read_fixture("no_such_fixture").is_err());
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -3,9 +3,13 @@
 use tokio::sync::OnceCell;
 use tracing::info;
 
-use crate::model::ModelManager;
-
 mod dev_db;
+mod fixture;
+mod sql_split;
+mod test_db;
+
+pub use self::fixture::{insert_fixture, load_fixture, read_fixture, Fixture, FixtureIds};
+pub use self::test_db::TestDb;
 
 // endregion: --- Modules
 
@@ -21,16 +25,9 @@ pub async fn init_dev() {
     .await;
 }
 
-// 初始化测试环境，返回一个用于测试的 ModelManager
-pub async fn init_test() -> ModelManager {
-    static INIT: OnceCell<ModelManager> = OnceCell::const_new();
-
-    let mm = INIT
-        .get_or_init(|| async {
-            init_dev().await;
-            ModelManager::new().await.unwrap()
-        })
-        .await;
-
-    mm.clone()
+// 初始化测试环境，返回一个测试独占的数据库，通过 `mm()` 拿到绑定的 ModelManager
+// 数据库由模板库复制而来，包含 migration 和 dev fixture，测试结束 drop 时删除
+// 需要更多数据时通过 `TestDb::load_fixture` 写入其他 fixture
+pub async fn init_test() -> TestDb {
+    TestDb::new().await.unwrap()
 }
@@ -0,0 +1,218 @@
+//! 将 sql 脚本切分成单条语句
+//!
+//! 只在语句之外的 `;` 处切分，以下内容中的 `;` 会被忽略：
+//! - 字符串 `'...'`（包括 `''` 转义和 `E'...'` 中的反斜杠转义）
+//! - 带引号的标识符 `"..."`
+//! - 单行注释 `-- ...` 和可嵌套的块注释 `/* ... */`
+//! - dollar quoting `$$ ... $$`、`$tag$ ... $tag$`（函数体、DO 块）
+
+/// 返回去掉首尾空白的语句，只包含空白或注释的片段会被丢弃
+pub fn split_sql(sql: &str) -> Vec<&str> {
+    let bytes = sql.as_bytes();
+    let len = bytes.len();
+
+    let mut stmts = Vec::new();
+    let mut start = 0;
+    let mut has_code = false;
+    let mut i = 0;
+
+    while i < len {
+        match bytes[i] {
+            // 单行注释
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = find(bytes, i + 2, b"\n").map_or(len, |pos| pos + 1);
+            }
+
+            // 块注释，postgres 中块注释可以嵌套
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let mut depth = 1;
+                i += 2;
+                while i < len && depth > 0 {
+                    if bytes[i..].starts_with(b"/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*/") {
+                        depth -= 1;
+                        i += 2;
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+
+            // 字符串，E'...' 中允许反斜杠转义
+            b'\'' => {
+                let backslash_escape = i > 0
+                    && matches!(bytes[i - 1], b'E' | b'e')
+                    && (i < 2 || !is_ident_byte(bytes[i - 2]));
+                has_code = true;
+                i = skip_quoted(bytes, i, b'\'', backslash_escape);
+            }
+
+            // 带引号的标识符
+            b'"' => {
+                has_code = true;
+                i = skip_quoted(bytes, i, b'"', false);
+            }
+
+            // dollar quoting，标识符中的 `$` 和 `$1` 这样的参数不是 dollar quoting
+            b'$' => {
+                has_code = true;
+                let tag_len = if i > 0 && is_ident_byte(bytes[i - 1]) {
+                    None
+                } else {
+                    dollar_tag_len(&bytes[i..])
+                };
+
+                i = match tag_len {
+                    Some(tag_len) => {
+                        let tag = &bytes[i..i + tag_len];
+                        find(bytes, i + tag_len, tag).map_or(len, |pos| pos + tag_len)
+                    }
+                    None => i + 1,
+                };
+            }
+
+            b';' => {
+                if has_code {
+                    stmts.push(sql[start..i].trim());
+                }
+                has_code = false;
+                i += 1;
+                start = i;
+            }
+
+            b => {
+                if !b.is_ascii_whitespace() {
+                    has_code = true;
+                }
+                i += 1;
+            }
+        }
+    }
+
+    if has_code {
+        stmts.push(sql[start..].trim());
+    }
+
+    stmts
+}
+
+/// 跳过以 quote 开始的内容，返回结束 quote 之后的位置，两个连续的 quote 表示转义
+fn skip_quoted(bytes: &[u8], start: usize, quote: u8, backslash_escape: bool) -> usize {
+    let mut i = start + 1;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if backslash_escape => i += 2,
+            b if b == quote && bytes.get(i + 1) == Some(&quote) => i += 2,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+
+    bytes.len()
+}
+
+/// `$$` 或 `$tag$` 的长度，tag 不能以数字开头
+fn dollar_tag_len(bytes: &[u8]) -> Option<usize> {
+    let mut j = 1;
+
+    if bytes
+        .get(j)
+        .is_some_and(|b| b.is_ascii_alphabetic() || *b == b'_')
+    {
+        while bytes
+            .get(j)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_' || !b.is_ascii())
+        {
+            j += 1;
+        }
+    }
+
+    (bytes.get(j) == Some(&b'$')).then_some(j + 1)
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || !b.is_ascii()
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|pos| from + pos)
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_split_sql_simple() -> Result<()> {
+        // -- Fixtures
+        let fx_sql = "CREATE TABLE a (id int);\n\nINSERT INTO a VALUES (1);\n";
+
+        // -- Exec
+        let stmts = split_sql(fx_sql);
+
+        // -- Check
+        assert_eq!(
+            stmts,
+            vec!["CREATE TABLE a (id int)", "INSERT INTO a VALUES (1)"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_sql_quotes_and_comments() -> Result<()> {
+        // -- Fixtures
+        let fx_sql = r#"
+-- 注释中的 ; 不会切分
+INSERT INTO "we;ird" (name) VALUES ('a;b'), ('it''s;'), (E'\';');
+/* 块注释 ; /* 嵌套 ; */ 仍然是注释 ; */
+SELECT 1;
+-- 只有注释的片段会被丢弃;
+"#;
+
+        // -- Exec
+        let stmts = split_sql(fx_sql);
+
+        // -- Check
+        assert_eq!(stmts.len(), 2);
+        assert!(stmts[0].ends_with(r#"(E'\';')"#));
+        assert!(stmts[1].ends_with("SELECT 1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_sql_dollar_quoting() -> Result<()> {
+        // -- Fixtures
+        let fx_sql = r#"
+CREATE FUNCTION f() RETURNS int AS $body$
+BEGIN
+  RETURN 1;
+END;
+$body$ LANGUAGE plpgsql;
+DO $$ BEGIN PERFORM f(); END $$;
+PREPARE p AS SELECT $1::int;
+"#;
+
+        // -- Exec
+        let stmts = split_sql(fx_sql);
+
+        // -- Check
+        assert_eq!(stmts.len(), 3);
+        assert!(stmts[0].ends_with("$body$ LANGUAGE plpgsql"));
+        assert_eq!(stmts[1], "DO $$ BEGIN PERFORM f(); END $$");
+        assert_eq!(stmts[2], "PREPARE p AS SELECT $1::int");
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -0,0 +1,250 @@
+//! 每个测试独立的数据库
+//!
+//! - 第一次使用时通过 migration 和开发数据创建模板库 `app_test_tpl_<checksum>`，
+//!   checksum 由 migration 和 fixture 文件计算，文件不变时多次运行会复用同一个模板库。
+//! - 每个测试通过 `CREATE DATABASE ... TEMPLATE` 复制出一个唯一命名的数据库，
+//!   TestDb 被 drop 时删除。
+//!
+
+use std::fs;
+
+use lib_utils::envs::get_env;
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, Executor, PgConnection};
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{
+    dev_db,
+    fixture::{self, FixtureIds},
+};
+use crate::{config::core_config, model::ModelManager};
+
+type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>;
+
+const TEMPLATE_DB_PREFIX: &str = "app_test_tpl_";
+const TEST_DB_PREFIX: &str = "app_test_";
+
+// 多个测试进程可能同时创建模板库，advisory lock 的 key 取任意固定值即可
+const TEMPLATE_LOCK_KEY: i64 = 7_326_105_035;
+
+/// 测试独占的数据库，drop 时删除
+pub struct TestDb {
+    mm: ModelManager,
+    db_name: String,
+}
+
+impl TestDb {
+    pub async fn new() -> Result<Self> {
+        let template = template_db().await?;
+        let db_name = format!("{TEST_DB_PREFIX}{}", Uuid::new_v4().simple());
+
+        let mut root = root_conn().await?;
+        root.execute(format!(r#"CREATE DATABASE "{db_name}" TEMPLATE "{template}""#).as_str())
+            .await?;
+        root.close().await?;
+
+        let mm = ModelManager::new_for_database(&db_name).await?;
+
+        Ok(TestDb { mm, db_name })
+    }
+
+    pub fn mm(&self) -> &ModelManager {
+        &self.mm
+    }
+
+    pub fn db_name(&self) -> &str {
+        &self.db_name
+    }
+
+    /// 在测试库中写入 `fixtures/` 目录下名为 name 的 fixture
+    pub async fn load_fixture(&self, name: &str) -> Result<FixtureIds> {
+        fixture::load_fixture(&self.mm, name).await
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let db_name = std::mem::take(&mut self.db_name);
+        let Ok(url) = get_env("SERVICE_DEV_POSTGRES_URL") else {
+            return;
+        };
+
+        // drop 中不能 await，在单独的线程和 runtime 中删除数据库
+        let res = std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|ex| ex.to_string())?;
+            rt.block_on(drop_db(&url, &db_name))
+                .map_err(|ex| ex.to_string())
+        })
+        .join();
+
+        if let Ok(Err(ex)) = res {
+            warn!("{:<12} - fail to drop test db - {ex}", "FOR-TEST-ONLY");
+        }
+    }
+}
+
+async fn drop_db(url: &str, db_name: &str) -> core::result::Result<(), sqlx::Error> {
+    let mut root = PgConnection::connect(url).await?;
+
+    // 测试中的连接池可能还持有连接，使用 FORCE 断开
+    root.execute(format!(r#"DROP DATABASE IF EXISTS "{db_name}" WITH (FORCE)"#).as_str())
+        .await?;
+
+    root.close().await
+}
+
+/// 返回模板库的名字，每个进程只检查一次
+async fn template_db() -> Result<&'static str> {
+    static TEMPLATE: OnceCell<String> = OnceCell::const_new();
+
+    let template = TEMPLATE.get_or_try_init(init_template_db).await?;
+
+    Ok(template.as_str())
+}
+
+async fn init_template_db() -> Result<String> {
+    let template = format!("{TEMPLATE_DB_PREFIX}{}", template_checksum()?);
+
+    // advisory lock 属于 session，加锁和解锁需要在同一个连接上
+    let mut root = root_conn().await?;
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(TEMPLATE_LOCK_KEY)
+        .execute(&mut root)
+        .await?;
+
+    let res = create_template_db(&mut root, &template).await;
+
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(TEMPLATE_LOCK_KEY)
+        .execute(&mut root)
+        .await?;
+    root.close().await?;
+
+    res.map(|_| template)
+}
+
+async fn create_template_db(root: &mut PgConnection, template: &str) -> Result<()> {
+    let db_names: Vec<String> = sqlx::query_scalar("SELECT datname FROM pg_database")
+        .fetch_all(&mut *root)
+        .await?;
+
+    if db_names.iter().any(|name| name == template) {
+        return Ok(());
+    }
+
+    info!("{:<12} - create template db {template}", "FOR-TEST-ONLY");
+
+    // 删除 migration 或开发数据修改之前的模板库
+    for name in db_names
+        .iter()
+        .filter(|name| name.starts_with(TEMPLATE_DB_PREFIX))
+    {
+        root.execute(format!(r#"DROP DATABASE IF EXISTS "{name}" WITH (FORCE)"#).as_str())
+            .await?;
+    }
+
+    // 先在临时的库中初始化，完成后再改名，失败时不会留下不完整的模板库
+    let building = format!("{template}_building");
+    root.execute(format!(r#"DROP DATABASE IF EXISTS "{building}" WITH (FORCE)"#).as_str())
+        .await?;
+    root.execute(format!(r#"CREATE DATABASE "{building}""#).as_str())
+        .await?;
+
+    let mm = ModelManager::new_for_database(&building).await?;
+    dev_db::init_app_db(mm.db()).await?;
+    mm.db().close().await;
+
+    root.execute(format!(r#"ALTER DATABASE "{building}" RENAME TO "{template}""#).as_str())
+        .await?;
+
+    Ok(())
+}
+
+/// migration 和 fixture 文件的 checksum，任何一个文件修改后都会创建新的模板库
+fn template_checksum() -> Result<String> {
+    let mut paths: Vec<_> = fs::read_dir(&core_config().MIGRATIONS_DIR)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sql"))
+        .collect();
+    paths.sort();
+    paths.extend(fixture::fixture_files()?);
+
+    let mut hasher = Sha256::new();
+    for path in &paths {
+        hasher.update(path.file_name().unwrap_or_default().as_encoded_bytes());
+        hasher.update(fs::read(path)?);
+    }
+
+    // 数据库名最长 63 个字符，取前 16 位即可
+    let checksum: String = hasher
+        .finalize()
+        .iter()
+        .take(8)
+        .map(|b| format!("{b:02x}"))
+        .collect();
+
+    Ok(checksum)
+}
+
+async fn root_conn() -> Result<PgConnection> {
+    let url = get_env("SERVICE_DEV_POSTGRES_URL")?;
+
+    Ok(PgConnection::connect(&url).await?)
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use crate::ctx::Ctx;
+    use crate::model::user::{User, UserBmc, UserForCreate};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_test_db_isolated() -> Result<()> {
+        // -- Fixtures
+        let ctx = Ctx::root_ctx();
+        let test_db_1 = init_test().await;
+        let test_db_2 = init_test().await;
+        let fx_username = "demo_test_db";
+
+        // -- Exec
+        UserBmc::create::<UserForCreate>(
+            &ctx,
+            test_db_1.mm(),
+            UserForCreate {
+                username: fx_username.to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+        let db_name_1 = test_db_1.db_name().to_string();
+        drop(test_db_1);
+
+        // -- Check
+        // 两个库都有开发数据，但彼此的修改互不影响
+        let demo1: Option<User> = UserBmc::first_by_username(&ctx, test_db_2.mm(), "demo1").await?;
+        let other: Option<User> =
+            UserBmc::first_by_username(&ctx, test_db_2.mm(), fx_username).await?;
+        assert!(demo1.is_some());
+        assert!(other.is_none());
+
+        // drop 后数据库被删除
+        let mut root = PgConnection::connect(&get_env("SERVICE_DEV_POSTGRES_URL")?).await?;
+        let exists: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_database WHERE datname = $1)")
+                .bind(&db_name_1)
+                .fetch_one(&mut root)
+                .await?;
+        assert!(!exists);
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -1,4 +1,4 @@
-use lib_utils::envs::get_env;
+use lib_utils::envs::{get_env, get_env_parse_or};
 use std::sync::OnceLock;
 
 pub fn core_config() -> &'static CoreConfig {
@@ -14,9 +14,19 @@ pub fn core_config() -> &'static CoreConfig {
 pub struct CoreConfig {
     // -- Db
     pub DB_URL: String,
+    // migration 文件目录
+    pub MIGRATIONS_DIR: String,
 
     // -- Web
     pub WEB_FOLDER: String,
+
+    // -- Account
+    // 申请删除账号后的宽限期，期间重新登录会取消删除
+    pub ACCOUNT_DELETION_GRACE_SEC: f64,
+
+    // -- Webhook
+    // 允许 http 和内网地址的 webhook，仅用于本地开发
+    pub WEBHOOK_ALLOW_INSECURE: bool,
 }
 
 impl CoreConfig {
@@ -24,9 +34,20 @@ impl CoreConfig {
         Ok(CoreConfig {
             // -- Db
             DB_URL: get_env("SERVICE_DB_URL")?,
+            MIGRATIONS_DIR: get_env("SERVICE_MIGRATIONS_DIR")?,
 
             // -- Web
             WEB_FOLDER: get_env("SERVICE_WEB_FOLDER")?,
+
+            // -- Account
+            // 默认 30 天
+            ACCOUNT_DELETION_GRACE_SEC: get_env_parse_or(
+                "SERVICE_ACCOUNT_DELETION_GRACE_SEC",
+                30.0 * 24.0 * 3600.0,
+            )?,
+
+            // -- Webhook
+            WEBHOOK_ALLOW_INSECURE: get_env_parse_or("SERVICE_WEBHOOK_ALLOW_INSECURE", false)?,
         })
     }
 }
@@ -1,3 +1,4 @@
+use super::Permission;
 use serde::Serialize;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -5,6 +6,18 @@ pub type Result<T> = core::result::Result<T, Error>;
 #[derive(Debug, Serialize)]
 pub enum Error {
     CtxCannotNewRootCtx,
+
+    PermissionDenied { permission: Permission },
+
+    ImpersonationForbidden,
+
+    // api key 的 scope 中没有 scope
+    ApiKeyScopeRequired { scope: &'static str },
+    // 不能通过 api key 认证执行
+    ApiKeyForbidden,
+
+    // 只能由系统内部的 root ctx 调用
+    SystemCtxRequired,
 }
 
 impl core::fmt::Display for Error {
@@ -1,28 +1,120 @@
 // region:    --- Modules
 mod error;
+mod permission;
 
 pub use self::error::{Error, Result};
+pub use self::permission::Permission;
 
 // endregion: --- Modules
 
+// api key 的 scope，write 包含 read
+pub const SCOPE_READ: &str = "read";
+pub const SCOPE_WRITE: &str = "write";
+
+/// api key 可以使用的 scope：read、write 以及管理权限对应的 scope
+pub fn is_known_scope(scope: &str) -> bool {
+    scope == SCOPE_READ
+        || scope == SCOPE_WRITE
+        || Permission::ALL.iter().any(|p| p.scope() == scope)
+}
+
 // region:    --- Constructor
 #[derive(Clone, Debug)]
 pub struct Ctx {
+    // 当前以哪个用户的身份执行
     user_id: i64,
+
+    // 实际发起请求的用户，模拟登录时为管理员的 id，否则与 user_id 相同
+    real_user_id: i64,
+
+    // 当前请求的认证方式
+    auth: CtxAuth,
+
+    // 用户的角色以及角色合并后的权限
+    roles: Vec<String>,
+    permissions: Vec<Permission>,
+
+    // 当前请求选择的组织，用于多租户数据隔离
+    org_id: Option<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CtxAuth {
+    // 系统内部调用，对应 root_ctx
+    System,
+    // 通过 cookie 中的 web token 认证
+    Cookie,
+    // 通过 Authorization: Bearer 头中的 web token 认证
+    Bearer,
+    // 通过 api key 认证，scopes 为该 key 的授权范围，为空时不能执行任何操作
+    ApiKey { key_id: i64, scopes: Vec<String> },
 }
 
 impl Ctx {
     pub fn root_ctx() -> Self {
-        Ctx { user_id: 0 }
+        Ctx {
+            user_id: 0,
+            real_user_id: 0,
+            auth: CtxAuth::System,
+            roles: Vec::new(),
+            permissions: Permission::ALL.to_vec(),
+            org_id: None,
+        }
     }
 
     pub fn new(user_id: i64) -> Result<Self> {
         if user_id == 0 {
             Err(Error::CtxCannotNewRootCtx)
         } else {
-            Ok(Self { user_id })
+            Ok(Self {
+                user_id,
+                real_user_id: user_id,
+                auth: CtxAuth::Cookie,
+                roles: Vec::new(),
+                permissions: Vec::new(),
+                org_id: None,
+            })
         }
     }
+
+    pub fn new_bearer(user_id: i64) -> Result<Self> {
+        let mut ctx = Self::new(user_id)?;
+        ctx.auth = CtxAuth::Bearer;
+
+        Ok(ctx)
+    }
+
+    pub fn new_api_key(user_id: i64, key_id: i64, scopes: Vec<String>) -> Result<Self> {
+        let mut ctx = Self::new(user_id)?;
+        ctx.auth = CtxAuth::ApiKey { key_id, scopes };
+
+        Ok(ctx)
+    }
+
+    pub fn with_roles(mut self, roles: Vec<String>, permissions: Vec<Permission>) -> Self {
+        for permission in permissions {
+            if !self.permissions.contains(&permission) {
+                self.permissions.push(permission);
+            }
+        }
+        self.roles = roles;
+
+        self
+    }
+
+    /// 调用方需要先确认模拟登录记录有效
+    pub fn with_impersonator(mut self, real_user_id: i64) -> Self {
+        self.real_user_id = real_user_id;
+
+        self
+    }
+
+    /// 调用方需要先确认用户是该组织的成员
+    pub fn with_org(mut self, org_id: i64) -> Self {
+        self.org_id = Some(org_id);
+
+        self
+    }
 }
 
 // endregion: --- Constructor
@@ -33,6 +125,97 @@ impl Ctx {
     pub fn user_id(&self) -> i64 {
         self.user_id
     }
+
+    pub fn real_user_id(&self) -> i64 {
+        self.real_user_id
+    }
+
+    pub fn is_impersonated(&self) -> bool {
+        self.real_user_id != self.user_id
+    }
+
+    /// 修改密码、创建 api key 等敏感操作不允许在模拟登录时执行
+    pub fn require_not_impersonated(&self) -> Result<()> {
+        if self.is_impersonated() {
+            Err(Error::ImpersonationForbidden)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn auth(&self) -> &CtxAuth {
+        &self.auth
+    }
+
+    /// 检查当前 ctx 是否为系统内部的 root ctx
+    pub fn require_system(&self) -> Result<()> {
+        if self.auth == CtxAuth::System {
+            Ok(())
+        } else {
+            Err(Error::SystemCtxRequired)
+        }
+    }
+
+    pub fn is_api_key(&self) -> bool {
+        matches!(self.auth, CtxAuth::ApiKey { .. })
+    }
+
+    pub fn api_key_id(&self) -> Option<i64> {
+        match &self.auth {
+            CtxAuth::ApiKey { key_id, .. } => Some(*key_id),
+            _ => None,
+        }
+    }
+
+    /// 非 api key 认证的 Ctx 不受 scope 限制，没有 scope 的 api key 不能执行任何操作
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.auth {
+            CtxAuth::ApiKey { scopes, .. } => scopes
+                .iter()
+                .any(|s| s == scope || (s == SCOPE_WRITE && scope == SCOPE_READ)),
+            _ => true,
+        }
+    }
+
+    /// 所有写操作都需要 SCOPE_WRITE
+    pub fn require_scope(&self, scope: &'static str) -> Result<()> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(Error::ApiKeyScopeRequired { scope })
+        }
+    }
+
+    /// 创建 api key 只能在用户登录后执行，泄露的 key 不能用来生成新的 key
+    pub fn require_not_api_key(&self) -> Result<()> {
+        if self.is_api_key() {
+            Err(Error::ApiKeyForbidden)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn org_id(&self) -> Option<i64> {
+        self.org_id
+    }
+
+    pub fn roles(&self) -> &[String] {
+        &self.roles
+    }
+
+    /// api key 认证时还需要 key 的 scope 包含该权限对应的 scope
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission) && self.has_scope(permission.scope())
+    }
+
+    /// 检查当前 ctx 是否拥有某个权限
+    pub fn require(&self, permission: Permission) -> Result<()> {
+        if !self.permissions.contains(&permission) {
+            return Err(Error::PermissionDenied { permission });
+        }
+
+        self.require_scope(permission.scope())
+    }
 }
 
 // endregion: --- Property Accessors
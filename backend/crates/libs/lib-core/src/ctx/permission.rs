@@ -0,0 +1,79 @@
+use super::{SCOPE_READ, SCOPE_WRITE};
+use serde::Serialize;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// 应用中可授予角色的权限
+/// 在数据库 role.permissions 中以字符串形式保存，"*" 表示所有权限
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum Permission {
+    UserRead,
+    UserWrite,
+    RoleManage,
+    // 可以访问其他用户拥有的数据
+    OwnerOverride,
+    // 可以模拟其他用户登录
+    Impersonate,
+}
+
+impl Permission {
+    pub const ALL: &'static [Permission] = &[
+        Permission::UserRead,
+        Permission::UserWrite,
+        Permission::RoleManage,
+        Permission::OwnerOverride,
+        Permission::Impersonate,
+    ];
+
+    pub const WILDCARD: &'static str = "*";
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::UserRead => "user:read",
+            Permission::UserWrite => "user:write",
+            Permission::RoleManage => "role:manage",
+            Permission::OwnerOverride => "owner:override",
+            Permission::Impersonate => "user:impersonate",
+        }
+    }
+
+    /// api key 使用该权限时需要的 scope
+    /// 用户读写对应 read、write，其他管理权限需要在 scope 中单独列出
+    pub fn scope(&self) -> &'static str {
+        match self {
+            Permission::UserRead => SCOPE_READ,
+            Permission::UserWrite => SCOPE_WRITE,
+            _ => self.as_str(),
+        }
+    }
+
+    /// 解析以空格分隔的权限列表，无法识别的权限会被忽略
+    pub fn parse_list(permissions: &str) -> Vec<Permission> {
+        if permissions.split_whitespace().any(|p| p == Self::WILDCARD) {
+            return Self::ALL.to_vec();
+        }
+
+        permissions
+            .split_whitespace()
+            .filter_map(|p| p.parse().ok())
+            .collect()
+    }
+}
+
+impl FromStr for Permission {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|p| p.as_str() == s)
+            .copied()
+            .ok_or(())
+    }
+}
+
+impl Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -1,6 +1,8 @@
 mod config;
 pub mod ctx;
+pub mod migration;
 pub mod model;
+pub mod worker;
 
 // #[cfg(test)] // Commented during early development.
 pub mod _dev_utils;
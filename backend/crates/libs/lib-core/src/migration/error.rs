@@ -0,0 +1,48 @@
+use derive_more::From;
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[serde_as]
+#[derive(Debug, Serialize, From)]
+pub enum Error {
+    // 文件名需要是 NNNN_name.sql 的格式
+    InvalidFileName {
+        file: String,
+    },
+    DuplicateVersion {
+        version: i64,
+    },
+
+    // 已执行的 migration 文件被删除或被修改
+    AppliedMigrationMissing {
+        version: i64,
+        name: String,
+    },
+    ChecksumMismatch {
+        version: i64,
+        name: String,
+    },
+    // 新的 migration 的版本号小于已执行的最大版本号
+    OutOfOrder {
+        version: i64,
+        applied_max: i64,
+    },
+
+    #[from]
+    Io(#[serde_as(as = "DisplayFromStr")] std::io::Error),
+
+    #[from]
+    Sqlx(#[serde_as(as = "DisplayFromStr")] sqlx::Error),
+}
+
+// region:    --- Error Boilerplate
+impl core::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for Error {}
+// endregion: --- Error Boilerplate
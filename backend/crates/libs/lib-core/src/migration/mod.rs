@@ -0,0 +1,216 @@
+//! 数据库版本迁移
+//!
+//! Design:
+//!
+//! - migration 文件位于 `SERVICE_MIGRATIONS_DIR` 目录下，文件名格式为 `NNNN_name.sql`。
+//! - 已执行的 migration 记录在 `_migrations` 表中，包含文件内容的 sha256 checksum。
+//! - 只会向前执行，已执行的文件被修改或删除时拒绝启动。
+//! - 执行期间持有 postgres advisory lock，多个实例同时启动时只会有一个在执行。
+//!
+
+// region:    --- Modules
+
+mod error;
+
+pub use self::error::{Error, Result};
+
+use crate::config::core_config;
+use crate::model::ModelManager;
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, Executor, PgConnection, Pool, Postgres};
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+// endregion: --- Modules
+
+// advisory lock 的 key，取任意固定值即可
+const MIGRATION_LOCK_KEY: i64 = 7_326_105_034;
+
+const SQL_CREATE_MIGRATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS _migrations (
+  version BIGINT PRIMARY KEY,
+  name varchar(256) NOT NULL,
+  checksum varchar(64) NOT NULL,
+  applied_at timestamp with time zone NOT NULL DEFAULT now()
+)";
+
+#[derive(Debug)]
+struct Migration {
+    version: i64,
+    name: String,
+    checksum: String,
+    sql: String,
+}
+
+/// 执行配置目录下所有未执行的 migration，返回本次执行的版本号
+pub async fn migrate(mm: &ModelManager) -> Result<Vec<i64>> {
+    migrate_dir(mm, Path::new(&core_config().MIGRATIONS_DIR)).await
+}
+
+pub async fn migrate_dir(mm: &ModelManager, dir: &Path) -> Result<Vec<i64>> {
+    migrate_db(mm.db(), dir).await
+}
+
+pub(crate) async fn migrate_db(db: &Pool<Postgres>, dir: &Path) -> Result<Vec<i64>> {
+    let migrations = load_migrations(dir)?;
+
+    // advisory lock 属于 session，加锁和解锁需要在同一个连接上
+    let mut conn = db.acquire().await?;
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    let res = apply_migrations(&mut conn, &migrations).await;
+
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    res
+}
+
+async fn apply_migrations(conn: &mut PgConnection, migrations: &[Migration]) -> Result<Vec<i64>> {
+    conn.execute(SQL_CREATE_MIGRATIONS_TABLE).await?;
+
+    let applied: Vec<(i64, String, String)> =
+        sqlx::query_as("SELECT version, name, checksum FROM _migrations ORDER BY version")
+            .fetch_all(&mut *conn)
+            .await?;
+
+    // 已执行的 migration 文件不能被删除或修改
+    for (version, name, checksum) in &applied {
+        let migration = migrations
+            .iter()
+            .find(|m| m.version == *version)
+            .ok_or_else(|| Error::AppliedMigrationMissing {
+                version: *version,
+                name: name.clone(),
+            })?;
+
+        if &migration.checksum != checksum {
+            return Err(Error::ChecksumMismatch {
+                version: *version,
+                name: name.clone(),
+            });
+        }
+    }
+
+    // 只能向前执行，不允许在已执行的版本之前插入新的 migration
+    let applied_max = applied.last().map(|(version, ..)| *version).unwrap_or(0);
+    let pending: Vec<&Migration> = migrations
+        .iter()
+        .filter(|m| !applied.iter().any(|(version, ..)| *version == m.version))
+        .collect();
+    if let Some(migration) = pending.iter().find(|m| m.version < applied_max) {
+        return Err(Error::OutOfOrder {
+            version: migration.version,
+            applied_max,
+        });
+    }
+
+    // 每个 migration 在独立的事务中执行
+    let mut versions = Vec::new();
+    for migration in pending {
+        info!(
+            "{:<12} - apply {:04}_{}",
+            "MIGRATION", migration.version, migration.name
+        );
+
+        let mut tx = conn.begin().await?;
+        // 不带参数的 execute 使用 simple query，支持一个文件中包含多条语句
+        tx.execute(migration.sql.as_str()).await?;
+        sqlx::query("INSERT INTO _migrations (version, name, checksum) VALUES ($1, $2, $3)")
+            .bind(migration.version)
+            .bind(&migration.name)
+            .bind(&migration.checksum)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        versions.push(migration.version);
+    }
+
+    Ok(versions)
+}
+
+fn load_migrations(dir: &Path) -> Result<Vec<Migration>> {
+    let mut migrations = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "sql") {
+            continue;
+        }
+
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let (version, name) =
+            parse_file_name(&file_name).ok_or_else(|| Error::InvalidFileName {
+                file: file_name.to_string(),
+            })?;
+
+        let sql = fs::read_to_string(&path)?;
+        migrations.push(Migration {
+            version,
+            name,
+            checksum: checksum(&sql),
+            sql,
+        });
+    }
+
+    migrations.sort_by_key(|m| m.version);
+    if let Some(pair) = migrations.windows(2).find(|w| w[0].version == w[1].version) {
+        return Err(Error::DuplicateVersion {
+            version: pair[0].version,
+        });
+    }
+
+    Ok(migrations)
+}
+
+/// `0001_initial_schema.sql` => (1, "initial_schema")
+fn parse_file_name(file_name: &str) -> Option<(i64, String)> {
+    let stem = file_name.strip_suffix(".sql")?;
+    let (version, name) = stem.split_once('_')?;
+
+    if version.is_empty() || !version.chars().all(|c| c.is_ascii_digit()) || name.is_empty() {
+        return None;
+    }
+
+    Some((version.parse().ok()?, name.to_string()))
+}
+
+fn checksum(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_parse_file_name() -> Result<()> {
+        // -- Exec & Check
+        assert_eq!(
+            parse_file_name("0001_initial_schema.sql"),
+            Some((1, "initial_schema".to_string()))
+        );
+        assert_eq!(
+            parse_file_name("0012_add_user_profile.sql"),
+            Some((12, "add_user_profile".to_string()))
+        );
+        assert_eq!(parse_file_name("initial_schema.sql"), None);
+        assert_eq!(parse_file_name("0001-initial.sql"), None);
+        assert_eq!(parse_file_name("0001_.sql"), None);
+        assert_eq!(parse_file_name("0001_initial.txt"), None);
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -0,0 +1,276 @@
+use crate::{
+    ctx::{self, Ctx, SCOPE_WRITE},
+    model::Error,
+    model::Result,
+};
+use lib_auth::api_key::ApiKey as ApiKeyParts;
+use lib_auth::pwd::{self, ContentToHash};
+use lib_utils::time::{now_utc_plus_sec, Rfc3339};
+use modql::field::{Fields, HasFields};
+use sea_query::{Expr, Iden, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::Serialize;
+use serde_with::serde_as;
+use sqlx::{postgres::PgRow, FromRow};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{
+    base::{self, DbBmc, UserData},
+    ModelManager,
+};
+
+// api key 最长 1 年有效
+const API_KEY_EXPIRES_IN_SEC_MAX: f64 = 365.0 * 86_400.0;
+
+// region:    --- ApiKey Types
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct ApiKey {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub prefix: String,
+
+    // 以空格分隔的 scope 列表
+    pub scopes: String,
+
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub revoked_at: Option<OffsetDateTime>,
+    #[serde_as(as = "Rfc3339")]
+    pub ctime: OffsetDateTime,
+}
+
+/// scopes 不能为空，只能包含 ctx::is_known_scope 中的 scope
+/// expires_in_sec 为 None 时不会过期
+#[derive(Clone, Debug)]
+pub struct ApiKeyForCreate {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_sec: Option<f64>,
+}
+
+/// 创建成功后返回的 key 明文，仅此一次
+#[derive(Debug, Serialize)]
+pub struct ApiKeyCreated {
+    pub id: i64,
+    pub key: String,
+}
+
+#[derive(Fields)]
+struct ApiKeyForInsert {
+    user_id: i64,
+    name: String,
+    prefix: String,
+    key_hash: String,
+    key_salt: Uuid,
+    scopes: String,
+    expires_at: Option<OffsetDateTime>,
+}
+
+#[derive(Clone, FromRow, Fields, Debug)]
+pub struct ApiKeyForAuth {
+    pub id: i64,
+    pub user_id: i64,
+
+    // -- key info
+    pub key_hash: String,
+    pub key_salt: Uuid,
+    pub scopes: String,
+
+    pub expires_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+pub trait ApiKeyBy: HasFields + for<'r> FromRow<'r, PgRow> + Unpin + Send {}
+
+impl ApiKeyBy for ApiKey {}
+impl ApiKeyBy for ApiKeyForAuth {}
+
+impl ApiKey {
+    pub fn scope_list(&self) -> Vec<String> {
+        split_scopes(&self.scopes)
+    }
+}
+
+impl ApiKeyForAuth {
+    pub fn scope_list(&self) -> Vec<String> {
+        split_scopes(&self.scopes)
+    }
+}
+
+fn split_scopes(scopes: &str) -> Vec<String> {
+    scopes.split_whitespace().map(String::from).collect()
+}
+
+#[derive(Iden)]
+enum ApiKeyIden {
+    Id,
+    UserId,
+    Prefix,
+    RevokedAt,
+}
+
+// endregion: --- ApiKey Types
+
+pub struct ApiKeyBmc {}
+
+impl DbBmc for ApiKeyBmc {
+    const TABLE: &'static str = "api_key";
+    const OWNER_COLUMN: Option<&'static str> = Some("user_id");
+    const USER_DATA: &'static [UserData] =
+        &[UserData::delete("api_key", "user_id").secret(&["key_hash", "key_salt"])];
+}
+
+impl ApiKeyBmc {
+    /// 为当前 ctx 的用户创建一个 api key，返回的 key 明文不会被保存
+    pub async fn create(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        api_key_c: ApiKeyForCreate,
+    ) -> Result<ApiKeyCreated> {
+        ctx.require_not_impersonated()?;
+        ctx.require_not_api_key()?;
+
+        if api_key_c.scopes.is_empty() {
+            return Err(Error::ApiKeyScopesEmpty);
+        }
+        if let Some(scope) = api_key_c.scopes.iter().find(|s| !ctx::is_known_scope(s)) {
+            return Err(Error::ApiKeyScopeUnknown {
+                scope: scope.clone(),
+            });
+        }
+
+        // NaN 和超出范围的值都不合法，不足 1 秒按 1 秒计算
+        let expires_at = match api_key_c.expires_in_sec {
+            Some(sec) if !(sec > 0.0 && sec <= API_KEY_EXPIRES_IN_SEC_MAX) => {
+                return Err(Error::ApiKeyExpiresInvalid {
+                    max_sec: API_KEY_EXPIRES_IN_SEC_MAX,
+                });
+            }
+            Some(sec) => Some(now_utc_plus_sec(sec.clamp(1.0, API_KEY_EXPIRES_IN_SEC_MAX))),
+            None => None,
+        };
+
+        let api_key = ApiKeyParts::generate();
+        let key_salt = Uuid::new_v4();
+        let key_hash = pwd::hash_pwd(&ContentToHash {
+            content: api_key.secret.clone(),
+            salt: key_salt,
+        })?;
+
+        let api_key_i = ApiKeyForInsert {
+            user_id: ctx.user_id(),
+            name: api_key_c.name,
+            prefix: api_key.prefix.clone(),
+            key_hash,
+            key_salt,
+            scopes: api_key_c.scopes.join(" "),
+            expires_at,
+        };
+
+        let id = base::create::<Self, _>(ctx, mm, api_key_i).await?;
+
+        Ok(ApiKeyCreated {
+            id,
+            key: api_key.to_string(),
+        })
+    }
+
+    pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<ApiKey> {
+        base::get::<Self, _>(ctx, mm, id).await
+    }
+
+    /// 列出当前 ctx 用户的所有 api key
+    pub async fn list(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<ApiKey>> {
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .columns(ApiKey::field_idens())
+            .and_where(Expr::col(ApiKeyIden::UserId).eq(ctx.user_id()))
+            .order_by(ApiKeyIden::Id, sea_query::Order::Asc);
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let api_keys = sqlx::query_as_with::<_, ApiKey, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        Ok(api_keys)
+    }
+
+    pub async fn first_by_prefix<E>(
+        _ctx: &Ctx,
+        mm: &ModelManager,
+        prefix: &str,
+    ) -> Result<Option<E>>
+    where
+        E: ApiKeyBy,
+    {
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .columns(E::field_idens())
+            .and_where(Expr::col(ApiKeyIden::Prefix).eq(prefix));
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let api_key = sqlx::query_as_with::<_, E, _>(&sql, values)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(api_key)
+    }
+
+    /// 吊销当前 ctx 用户的 api key，已吊销的 key 视为不存在
+    pub async fn revoke(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+        ctx.require_scope(SCOPE_WRITE)?;
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::update();
+        query
+            .table(Self::table_ref())
+            .value(ApiKeyIden::RevokedAt, Expr::current_timestamp())
+            .and_where(Expr::col(ApiKeyIden::Id).eq(id))
+            .and_where(Expr::col(ApiKeyIden::UserId).eq(ctx.user_id()))
+            .and_where(Expr::col(ApiKeyIden::RevokedAt).is_null());
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let count = sqlx::query_with(&sql, values)
+            .execute(db)
+            .await?
+            .rows_affected();
+
+        // 检查
+        if count == 0 {
+            Err(Error::EntityNotFound {
+                entity: Self::TABLE,
+                id,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// 吊销某个用户所有未吊销的 api key 的 query，用于删除账号
+pub(in crate::model) fn revoke_all_query(user_id: i64) -> sea_query::UpdateStatement {
+    let mut query = Query::update();
+    query
+        .table(ApiKeyBmc::table_ref())
+        .value(ApiKeyIden::RevokedAt, Expr::current_timestamp())
+        .and_where(Expr::col(ApiKeyIden::UserId).eq(user_id))
+        .and_where(Expr::col(ApiKeyIden::RevokedAt).is_null());
+
+    query
+}
@@ -1,33 +1,233 @@
-use crate::ctx::Ctx;
+use crate::ctx::{Ctx, Permission, SCOPE_WRITE};
 use crate::model::{Error, Result};
+use lib_utils::time::{now_utc_plus_sec, Rfc3339};
 use modql::field::HasFields;
 use modql::filter::{FilterGroups, ListOptions};
 use modql::SIden;
-use sea_query::{Condition, Expr, Iden, IntoIden, PostgresQueryBuilder, Query, TableRef};
-use sea_query_binder::SqlxBinder;
+use sea_query::{
+    Condition, DynIden, Expr, Iden, IntoIden, Keyword, LockType, OnConflict, Order,
+    PostgresQueryBuilder, Query, SelectStatement, SimpleExpr, TableRef, UpdateStatement,
+};
+use sea_query_binder::{SqlxBinder, SqlxValues};
+use serde::Serialize;
+use serde_with::serde_as;
 use sqlx::postgres::PgRow;
-use sqlx::FromRow;
+use sqlx::{FromRow, PgConnection};
+use time::OffsetDateTime;
 
+use super::outbox::{DomainEvent, OutboxBmc};
 use super::ModelManager;
 
 const LIST_LIMIT_DEFAULT: i64 = 300;
 const LIST_LIMIT_MAX: i64 = 1000;
 
+// 批量操作一次最多影响的行数
+const BULK_LIMIT_MAX: u64 = 1000;
+
 // TODO: 学习Iden
 #[derive(Iden)]
 pub enum CommonIden {
     Id,
+    DeletedAt,
+    Version,
+    Mtime,
+}
+
+/// 软删除实体在 list 时的范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeletedScope {
+    // 只返回未删除的数据
+    #[default]
+    Exclude,
+    // 同时返回已删除的数据
+    Include,
+    // 只返回已删除的数据
+    Only,
+}
+
+/// 表中属于某个用户的数据，用于导出个人数据和删除账号
+#[derive(Debug, Clone, Copy)]
+pub struct UserData {
+    pub table: &'static str,
+    // 记录用户 id 的列
+    pub user_column: &'static str,
+    // 为 Some 时是该实体在 entity_history 中的快照，user_column 为快照数据中的字段
+    pub history_entity: Option<&'static str>,
+    // 额外的 sql 条件，例如排除属于组织的数据
+    pub filter: Option<&'static str>,
+    // 导出时不包含的列，例如加密后的密钥
+    pub secret_columns: &'static [&'static str],
+    pub on_erase: ErasePolicy,
+}
+
+/// 删除账号时对用户数据的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErasePolicy {
+    // 删除数据
+    Delete,
+    // 保留数据，将列出的列重置为默认值（没有默认值的列为 NULL）
+    Anonymize(&'static [&'static str]),
+    // 保留数据，例如其他用户仍在使用的组织
+    Keep,
+}
+
+impl UserData {
+    pub const fn delete(table: &'static str, user_column: &'static str) -> Self {
+        Self::new(table, user_column, ErasePolicy::Delete)
+    }
+
+    pub const fn anonymize(
+        table: &'static str,
+        user_column: &'static str,
+        columns: &'static [&'static str],
+    ) -> Self {
+        Self::new(table, user_column, ErasePolicy::Anonymize(columns))
+    }
+
+    pub const fn keep(table: &'static str, user_column: &'static str) -> Self {
+        Self::new(table, user_column, ErasePolicy::Keep)
+    }
+
+    /// entity 在 entity_history 中的快照，按快照数据中的 user_column 找到用户，删除账号时一起删除
+    pub const fn history(entity: &'static str, user_column: &'static str) -> Self {
+        let mut user_data = Self::new("entity_history", user_column, ErasePolicy::Delete);
+        user_data.history_entity = Some(entity);
+        user_data
+    }
+
+    pub const fn secret(mut self, columns: &'static [&'static str]) -> Self {
+        self.secret_columns = columns;
+        self
+    }
+
+    pub const fn filter(mut self, cond: &'static str) -> Self {
+        self.filter = Some(cond);
+        self
+    }
+
+    /// 匹配用户数据的 sql 条件，$1 为用户 id
+    pub fn user_cond_sql(&self) -> String {
+        let cond = match self.history_entity {
+            Some(entity) => format!(
+                r#""entity" = '{entity}' AND ("data" ->> '{}')::bigint = $1"#,
+                self.user_column
+            ),
+            None => format!(r#""{}" = $1"#, self.user_column),
+        };
+
+        match self.filter {
+            Some(filter) => format!("{cond} AND ({filter})"),
+            None => cond,
+        }
+    }
+
+    const fn new(table: &'static str, user_column: &'static str, on_erase: ErasePolicy) -> Self {
+        Self {
+            table,
+            user_column,
+            history_entity: None,
+            filter: None,
+            secret_columns: &[],
+            on_erase,
+        }
+    }
 }
 
 pub trait DbBmc {
     // 数据库表的名字
     const TABLE: &'static str;
 
+    // 记录所属用户 id 的列，设置后 get/list/update/delete 只能访问当前 ctx 用户的数据
+    const OWNER_COLUMN: Option<&'static str> = None;
+
+    // 创建时写入 ctx 用户 id 的列，例如记录创建者但不限制访问的 owner_id
+    const OWNER_FILL_COLUMN: Option<&'static str> = None;
+
+    // 记录所属组织 id 的列，设置后所有 base 函数都限定在 ctx 选择的组织内
+    const TENANT_COLUMN: Option<&'static str> = None;
+
+    // 是否软删除，开启后表中需要有 deleted_at 列
+    // delete 只标记删除时间，get/list/update 默认不包含已删除的数据
+    const SOFT_DELETE: bool = false;
+
+    // 是否使用乐观锁，开启后表中需要有 version 列，每次 update 时加 1
+    const VERSIONED: bool = false;
+
+    // 是否记录修改时间，开启后表中需要有 mtime 列，每次 update 时写入当前时间
+    const TIMESTAMPS: bool = false;
+
+    // 是否记录修改历史，开启后 update/delete 会把修改前的数据写入 entity_history
+    const HISTORY: bool = false;
+
+    // 删除时写入 outbox 的事件，参数为删除时的 ctx 和实体 id
+    // 软删除同样会产生事件，restore 和 purge 不会
+    const DELETED_EVENT: Option<fn(&Ctx, i64) -> DomainEvent> = None;
+
+    // 该 Bmc 管理的表中属于用户的数据，导出个人数据和删除账号时按这里的声明处理
+    const USER_DATA: &'static [UserData] = &[];
+
     fn table_ref() -> TableRef {
         TableRef::Table(SIden(Self::TABLE).into_iden())
     }
 }
 
+/// upsert 的结果，action 表示数据是新插入的、更新了已有的数据，还是已有的数据没有需要更新的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpsertResult {
+    pub id: i64,
+    pub action: UpsertAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertAction {
+    Inserted,
+    Updated,
+    Unchanged,
+}
+
+/// 根据 DbBmc 的 OWNER_COLUMN 生成限定当前用户的条件
+/// 条件带有表名，可以用于 ON CONFLICT DO UPDATE 的 WHERE
+/// root ctx 或拥有 OwnerOverride 权限的 ctx 不受限制
+pub fn owner_cond<MC>(ctx: &Ctx) -> Option<SimpleExpr>
+where
+    MC: DbBmc,
+{
+    let owner_column = MC::OWNER_COLUMN?;
+
+    if ctx.user_id() == 0 || ctx.has_permission(Permission::OwnerOverride) {
+        None
+    } else {
+        Some(Expr::col((SIden(MC::TABLE), SIden(owner_column))).eq(ctx.user_id()))
+    }
+}
+
+/// 根据 DbBmc 的 SOFT_DELETE 生成限定删除状态的条件
+pub fn deleted_cond<MC>(scope: DeletedScope) -> Option<SimpleExpr>
+where
+    MC: DbBmc,
+{
+    if !MC::SOFT_DELETE {
+        return None;
+    }
+
+    match scope {
+        DeletedScope::Exclude => Some(Expr::col(CommonIden::DeletedAt).is_null()),
+        DeletedScope::Include => None,
+        DeletedScope::Only => Some(Expr::col(CommonIden::DeletedAt).is_not_null()),
+    }
+}
+
+/// owner、tenant 和删除状态的条件，写入历史时和 update/delete 使用相同的条件
+fn scope_cond<MC>(ctx: &Ctx, deleted_scope: DeletedScope) -> Result<Condition>
+where
+    MC: DbBmc,
+{
+    Ok(Condition::all()
+        .add_option(owner_cond::<MC>(ctx))
+        .add_option(tenant_cond::<MC>(ctx)?)
+        .add_option(deleted_cond::<MC>(deleted_scope)))
+}
+
 pub fn finalize_list_options(list_options: Option<ListOptions>) -> Result<ListOptions> {
     if let Some(mut list_options) = list_options {
         if let Some(limit) = list_options.limit {
@@ -50,16 +250,82 @@ pub fn finalize_list_options(list_options: Option<ListOptions>) -> Result<ListOp
     }
 }
 
-pub async fn create<MC, E>(_ctx: &Ctx, mm: &ModelManager, data: E) -> Result<i64>
+/// 根据 DbBmc 的 TENANT_COLUMN 生成限定当前组织的条件
+/// root ctx 在没有选择组织时不受限制，其他 ctx 必须先选择组织
+pub fn tenant_cond<MC>(ctx: &Ctx) -> Result<Option<SimpleExpr>>
+where
+    MC: DbBmc,
+{
+    let Some(tenant_column) = MC::TENANT_COLUMN else {
+        return Ok(None);
+    };
+
+    match ctx.org_id() {
+        Some(org_id) => Ok(Some(
+            Expr::col((SIden(MC::TABLE), SIden(tenant_column))).eq(org_id),
+        )),
+        None if ctx.user_id() == 0 => Ok(None),
+        None => Err(Error::TenantNotSelected { entity: MC::TABLE }),
+    }
+}
+
+// 插入的列和值，自动写入 OWNER_FILL_COLUMN 和 ctx 选择的组织
+fn insert_fields<MC, E>(ctx: &Ctx, data: E) -> Result<(Vec<DynIden>, Vec<SimpleExpr>)>
+where
+    MC: DbBmc,
+    E: HasFields,
+{
+    let (columns, sea_values, _) = insert_fields_filled::<MC, E>(ctx, data)?;
+
+    Ok((columns, sea_values))
+}
+
+// 和 insert_fields 一样，同时返回自动写入的列
+fn insert_fields_filled<MC, E>(
+    ctx: &Ctx,
+    data: E,
+) -> Result<(Vec<DynIden>, Vec<SimpleExpr>, Vec<&'static str>)>
+where
+    MC: DbBmc,
+    E: HasFields,
+{
+    let fields = data.not_none_fields();
+    let (mut columns, mut sea_values) = fields.for_sea_insert();
+    let mut filled = Vec::new();
+
+    if let Some(owner_column) = MC::OWNER_FILL_COLUMN {
+        if !columns.iter().any(|c| c.to_string() == owner_column) {
+            columns.push(SIden(owner_column).into_iden());
+            sea_values.push(ctx.user_id().into());
+            filled.push(owner_column);
+        }
+    }
+
+    if let Some(tenant_column) = MC::TENANT_COLUMN {
+        if !columns.iter().any(|c| c.to_string() == tenant_column) {
+            let org_id = ctx
+                .org_id()
+                .ok_or(Error::TenantNotSelected { entity: MC::TABLE })?;
+            columns.push(SIden(tenant_column).into_iden());
+            sea_values.push(org_id.into());
+            filled.push(tenant_column);
+        }
+    }
+
+    Ok((columns, sea_values, filled))
+}
+
+pub async fn create<MC, E>(ctx: &Ctx, mm: &ModelManager, data: E) -> Result<i64>
 where
     MC: DbBmc,
     E: HasFields,
 {
+    ctx.require_scope(SCOPE_WRITE)?;
+
     let db = mm.db();
 
     // Extract(提取) fields
-    let fields = data.not_none_fields();
-    let (columns, sea_values) = fields.for_sea_insert();
+    let (columns, sea_values) = insert_fields::<MC, E>(ctx, data)?;
 
     // 创建 query
     let mut query = Query::insert();
@@ -78,7 +344,153 @@ where
     Ok(id)
 }
 
-pub async fn get<MC, E>(_ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<E>
+/// 插入数据，conflict_columns 上的唯一约束冲突时更新已有数据的非空字段
+///
+/// 冲突的数据不属于当前 ctx 的用户或组织，或者已被软删除时不会更新，返回 UniqueViolation
+/// 除 conflict_columns 和自动写入的列之外没有其他字段时不修改已有数据，返回 Unchanged
+pub async fn upsert<MC, E>(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    conflict_columns: &[&'static str],
+    data: E,
+) -> Result<UpsertResult>
+where
+    MC: DbBmc,
+    E: HasFields,
+{
+    let mut tx = mm.db().begin().await?;
+    let res = upsert_in::<MC, E>(ctx, &mut tx, conflict_columns, data).await?;
+    tx.commit().await?;
+
+    Ok(res)
+}
+
+/// 在 conn 上执行 upsert，调用方可以在同一个事务中根据结果写入 event
+/// 开启 HISTORY 时会写入修改前的数据，conn 需要是事务
+pub(in crate::model) async fn upsert_in<MC, E>(
+    ctx: &Ctx,
+    conn: &mut PgConnection,
+    conflict_columns: &[&'static str],
+    data: E,
+) -> Result<UpsertResult>
+where
+    MC: DbBmc,
+    E: HasFields,
+{
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    // Extract(提取) fields
+    let (columns, sea_values, filled) = insert_fields_filled::<MC, E>(ctx, data)?;
+
+    // 冲突时更新除 conflict_columns 和自动写入的列之外的字段
+    // 自动写入的 owner 和组织来自当前 ctx，不能用来修改已有数据的所属
+    let update_columns: Vec<DynIden> = columns
+        .iter()
+        .filter(|c| {
+            let c = c.to_string();
+            !conflict_columns.contains(&c.as_str()) && !filled.contains(&c.as_str())
+        })
+        .cloned()
+        .collect();
+    let unchanged = update_columns.is_empty();
+
+    // 冲突的已有数据：conflict_columns 等于插入的值，并且在当前 ctx 的范围内
+    // 有 conflict_column 没有值时不会发生冲突，为 None
+    let conflict_values: Option<Vec<(&str, SimpleExpr)>> = conflict_columns
+        .iter()
+        .map(|conflict_column| {
+            columns
+                .iter()
+                .position(|c| c.to_string() == *conflict_column)
+                .map(|i| (*conflict_column, sea_values[i].clone()))
+        })
+        .collect();
+    let conflict_cond = match conflict_values {
+        Some(conflict_values) => Some(conflict_values.into_iter().fold(
+            scope_cond::<MC>(ctx, DeletedScope::Exclude)?,
+            |cond, (column, value)| cond.add(Expr::col(SIden(column)).eq(value)),
+        )),
+        None => None,
+    };
+
+    let mut on_conflict = OnConflict::columns(conflict_columns.iter().map(|c| SIden(c)));
+    if unchanged {
+        // 没有需要更新的字段时不修改已有数据，mtime、version 和 history 都保持不变
+        on_conflict.do_nothing();
+    } else {
+        // 和 update_query 一样更新 mtime 和 version，已软删除的数据不会被更新
+        // 和 owner_cond 一样带有表名，避免和 excluded 中的同名列混淆
+        let table_col = |column: CommonIden| Expr::col((SIden(MC::TABLE), column));
+        on_conflict
+            .update_columns(update_columns)
+            .action_and_where_option(owner_cond::<MC>(ctx))
+            .action_and_where_option(tenant_cond::<MC>(ctx)?);
+        if MC::TIMESTAMPS {
+            on_conflict.value(CommonIden::Mtime, Expr::current_timestamp());
+        }
+        if MC::VERSIONED {
+            on_conflict.value(CommonIden::Version, table_col(CommonIden::Version).add(1));
+        }
+        if MC::SOFT_DELETE {
+            on_conflict.action_and_where(table_col(CommonIden::DeletedAt).is_null());
+        }
+
+        // 冲突的数据会被更新，先写入修改前的数据
+        if MC::HISTORY {
+            if let Some(conflict_cond) = conflict_cond.clone() {
+                record_history::<MC>(ctx, conn, HistoryOp::Update, conflict_cond).await?;
+            }
+        }
+    }
+
+    // 创建 query
+    // xmax 为 0 表示这一行是新插入的，否则是被更新的
+    let mut query = Query::insert();
+    query
+        .into_table(MC::table_ref())
+        .columns(columns)
+        .values(sea_values)?
+        .on_conflict(on_conflict)
+        .returning(
+            Query::returning().exprs([Expr::col(CommonIden::Id).into(), Expr::cust("(xmax = 0)")]),
+        );
+
+    // 执行 query
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let returned = sqlx::query_as_with::<_, (i64, bool), _>(&sql, values)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    let unique_violation = || Error::UniqueViolation {
+        table: MC::TABLE.to_string(),
+        constraint: String::new(),
+        field: conflict_columns.first().map(|c| c.to_string()),
+    };
+    let (id, action) = match returned {
+        Some((id, true)) => (id, UpsertAction::Inserted),
+        Some((id, false)) => (id, UpsertAction::Updated),
+        // DO NOTHING 不会返回已有的数据，在当前 ctx 的范围内查询 id
+        None if unchanged => {
+            let conflict_cond = conflict_cond.ok_or_else(unique_violation)?;
+            let mut query = Query::select();
+            query
+                .from(MC::table_ref())
+                .column(CommonIden::Id)
+                .cond_where(conflict_cond);
+            let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+            let (id,) = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+                .fetch_optional(&mut *conn)
+                .await?
+                .ok_or_else(unique_violation)?;
+            (id, UpsertAction::Unchanged)
+        }
+        None => return Err(unique_violation()),
+    };
+
+    Ok(UpsertResult { id, action })
+}
+
+pub async fn get<MC, E>(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<E>
 where
     MC: DbBmc,
     E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
@@ -91,7 +503,10 @@ where
     query
         .from(MC::table_ref())
         .columns(E::field_column_refs())
-        .and_where(Expr::col(CommonIden::Id).eq(id));
+        .and_where(Expr::col(CommonIden::Id).eq(id))
+        .and_where_option(owner_cond::<MC>(ctx))
+        .and_where_option(tenant_cond::<MC>(ctx)?)
+        .and_where_option(deleted_cond::<MC>(DeletedScope::Exclude));
 
     // 执行 query
     // 获取一个实体
@@ -108,10 +523,27 @@ where
 }
 
 pub async fn list<MC, E, F>(
-    _ctx: &Ctx,
+    ctx: &Ctx,
+    mm: &ModelManager,
+    filter: Option<F>,
+    list_options: Option<ListOptions>,
+) -> Result<Vec<E>>
+where
+    MC: DbBmc,
+    F: Into<FilterGroups>,
+    E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
+    E: HasFields,
+{
+    list_scoped::<MC, E, F>(ctx, mm, filter, list_options, DeletedScope::Exclude).await
+}
+
+/// 和 list 一样，但可以指定是否包含已软删除的数据
+pub async fn list_scoped<MC, E, F>(
+    ctx: &Ctx,
     mm: &ModelManager,
     filter: Option<F>,
     list_options: Option<ListOptions>,
+    deleted_scope: DeletedScope,
 ) -> Result<Vec<E>>
 where
     MC: DbBmc,
@@ -122,7 +554,12 @@ where
     let db = mm.db();
 
     let mut query = Query::select();
-    query.from(MC::table_ref()).columns(E::field_column_refs());
+    query
+        .from(MC::table_ref())
+        .columns(E::field_column_refs())
+        .and_where_option(owner_cond::<MC>(ctx))
+        .and_where_option(tenant_cond::<MC>(ctx)?)
+        .and_where_option(deleted_cond::<MC>(deleted_scope));
 
     // filter 的条件
     if let Some(filter) = filter {
@@ -144,30 +581,341 @@ where
     Ok(entities)
 }
 
-pub async fn update<MC, E>(_ctx: &Ctx, mm: &ModelManager, id: i64, data: E) -> Result<()>
+pub async fn update<MC, E>(ctx: &Ctx, mm: &ModelManager, id: i64, data: E) -> Result<()>
 where
     MC: DbBmc,
     E: HasFields,
 {
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    // 创建 query
+    let mut query = update_query::<MC, E>(ctx, data)?;
+    query.and_where(Expr::col(CommonIden::Id).eq(id));
+    let history_cond =
+        scope_cond::<MC>(ctx, DeletedScope::Exclude)?.add(Expr::col(CommonIden::Id).eq(id));
+
+    // 执行 query
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let count =
+        exec_write::<MC>(ctx, mm, HistoryOp::Update, history_cond, None, &sql, values).await?;
+
+    // 检查
+    if count == 0 {
+        Err(Error::EntityNotFound {
+            entity: MC::TABLE,
+            id,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// 只有在数据库中的 version 等于 expected_version 时才更新，返回更新后的 version
+pub async fn update_versioned<MC, E>(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    id: i64,
+    expected_version: i64,
+    data: E,
+) -> Result<i64>
+where
+    MC: DbBmc,
+    E: HasFields,
+{
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    if !MC::VERSIONED {
+        return Err(Error::VersionNotEnabled { entity: MC::TABLE });
+    }
+
     let db = mm.db();
+
+    // 创建 query
+    let mut query = update_query::<MC, E>(ctx, data)?;
+    query
+        .and_where(Expr::col(CommonIden::Id).eq(id))
+        .and_where(Expr::col(CommonIden::Version).eq(expected_version))
+        .returning(Query::returning().columns([CommonIden::Version]));
+    let history_cond = scope_cond::<MC>(ctx, DeletedScope::Exclude)?
+        .add(Expr::col(CommonIden::Id).eq(id))
+        .add(Expr::col(CommonIden::Version).eq(expected_version));
+
+    // 执行 query
+    let mut tx = db.begin().await?;
+    record_history::<MC>(ctx, &mut tx, HistoryOp::Update, history_cond).await?;
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let version = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+        .fetch_optional(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    if let Some((version,)) = version {
+        return Ok(version);
+    }
+
+    // 没有更新时，区分实体不存在和 version 不一致
+    let mut query = Query::select();
+    query
+        .from(MC::table_ref())
+        .column(CommonIden::Version)
+        .and_where(Expr::col(CommonIden::Id).eq(id))
+        .and_where_option(owner_cond::<MC>(ctx))
+        .and_where_option(tenant_cond::<MC>(ctx)?)
+        .and_where_option(deleted_cond::<MC>(DeletedScope::Exclude));
+
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let (actual,) = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+        .fetch_optional(db)
+        .await?
+        .ok_or(Error::EntityNotFound {
+            entity: MC::TABLE,
+            id,
+        })?;
+
+    Err(Error::VersionConflict {
+        entity: MC::TABLE,
+        id,
+        expected: expected_version,
+        actual,
+    })
+}
+
+fn update_query<MC, E>(ctx: &Ctx, data: E) -> Result<UpdateStatement>
+where
+    MC: DbBmc,
+    E: HasFields,
+{
     let fields = data.not_none_fields();
     let fields = fields.for_sea_update();
 
-    // 创建 query
     let mut query = Query::update();
     query
         .table(MC::table_ref())
         .values(fields)
-        .and_where(Expr::col(CommonIden::Id).eq(id));
+        .and_where_option(owner_cond::<MC>(ctx))
+        .and_where_option(tenant_cond::<MC>(ctx)?)
+        .and_where_option(deleted_cond::<MC>(DeletedScope::Exclude));
+
+    if MC::TIMESTAMPS {
+        query.value(CommonIden::Mtime, Expr::current_timestamp());
+    }
+
+    // 任何更新都会使之前读到的 version 失效
+    if MC::VERSIONED {
+        query.value(CommonIden::Version, Expr::col(CommonIden::Version).add(1));
+    }
+
+    Ok(query)
+}
+
+// region:    --- Bulk
+
+/// 批量创建，在一条 insert 语句中写入，返回按顺序创建的 id
+/// 所有数据的非空字段需要相同
+pub async fn create_many<MC, E>(ctx: &Ctx, mm: &ModelManager, data: Vec<E>) -> Result<Vec<i64>>
+where
+    MC: DbBmc,
+    E: HasFields,
+{
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+    check_bulk_limit(data.len() as u64)?;
+
+    // 创建 query
+    let mut query = Query::insert();
+    query.into_table(MC::table_ref());
+
+    let mut columns_ref: Option<Vec<String>> = None;
+    for item in data {
+        let (columns, sea_values) = insert_fields::<MC, E>(ctx, item)?;
+        let names: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+
+        match &columns_ref {
+            None => {
+                query.columns(columns);
+                columns_ref = Some(names);
+            }
+            Some(columns_ref) if *columns_ref != names => {
+                return Err(Error::BulkFieldsMismatch { entity: MC::TABLE });
+            }
+            Some(_) => {}
+        }
+
+        query.values(sea_values)?;
+    }
+    query.returning(Query::returning().columns([CommonIden::Id]));
 
     // 执行 query
+    let mut tx = mm.db().begin().await?;
     let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-    let count = sqlx::query_with(&sql, values)
-        .execute(db)
-        .await?
-        .rows_affected();
+    let ids = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+        .fetch_all(&mut *tx)
+        .await?;
+    tx.commit().await?;
 
-    // 检查
+    Ok(ids.into_iter().map(|(id,)| id).collect())
+}
+
+/// 批量更新符合 filter 的数据，返回更新的行数
+pub async fn update_many<MC, E, F>(ctx: &Ctx, mm: &ModelManager, filter: F, data: E) -> Result<u64>
+where
+    MC: DbBmc,
+    E: HasFields,
+    F: Into<FilterGroups>,
+{
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    let cond = bulk_cond::<MC, F>(filter)?;
+
+    // 创建 query
+    let mut query = update_query::<MC, E>(ctx, data)?;
+    query.cond_where(cond.clone()).returning_col(CommonIden::Id);
+    let history_cond = scope_cond::<MC>(ctx, DeletedScope::Exclude)?.add(cond);
+
+    // 执行 query
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    exec_bulk::<MC>(ctx, mm, HistoryOp::Update, history_cond, &sql, values).await
+}
+
+/// 批量删除符合 filter 的数据，软删除的实体只标记删除时间，返回删除的行数
+pub async fn delete_many<MC, F>(ctx: &Ctx, mm: &ModelManager, filter: F) -> Result<u64>
+where
+    MC: DbBmc,
+    F: Into<FilterGroups>,
+{
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    let cond = bulk_cond::<MC, F>(filter)?;
+    let history_cond = scope_cond::<MC>(ctx, DeletedScope::Exclude)?.add(cond.clone());
+
+    // 创建 query
+    let (sql, values) = if MC::SOFT_DELETE {
+        let mut query = Query::update();
+        query
+            .table(MC::table_ref())
+            .value(CommonIden::DeletedAt, Expr::current_timestamp())
+            .cond_where(cond)
+            .and_where_option(owner_cond::<MC>(ctx))
+            .and_where_option(tenant_cond::<MC>(ctx)?)
+            .and_where_option(deleted_cond::<MC>(DeletedScope::Exclude))
+            .returning_col(CommonIden::Id);
+        query.build_sqlx(PostgresQueryBuilder)
+    } else {
+        let mut query = Query::delete();
+        query
+            .from_table(MC::table_ref())
+            .cond_where(cond)
+            .and_where_option(owner_cond::<MC>(ctx))
+            .and_where_option(tenant_cond::<MC>(ctx)?)
+            .returning_col(CommonIden::Id);
+        query.build_sqlx(PostgresQueryBuilder)
+    };
+
+    // 执行 query
+    exec_bulk::<MC>(ctx, mm, HistoryOp::Delete, history_cond, &sql, values).await
+}
+
+// 批量更新和删除必须带有 filter，不允许不带条件地修改整张表
+fn bulk_cond<MC, F>(filter: F) -> Result<Condition>
+where
+    MC: DbBmc,
+    F: Into<FilterGroups>,
+{
+    let filters: FilterGroups = filter.into();
+
+    // 多个 group 之间是 OR 的关系，任何一个 group 为空都会匹配所有数据
+    let groups = filters.groups();
+    if groups.is_empty() || groups.iter().any(|group| group.nodes().is_empty()) {
+        return Err(Error::BulkFilterRequired { entity: MC::TABLE });
+    }
+
+    Ok(filters.try_into()?)
+}
+
+// 在事务中执行，影响的行数超过上限时回滚
+// sql 需要返回修改的 id，删除时为每个 id 写入 DELETED_EVENT
+async fn exec_bulk<MC>(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    op: HistoryOp,
+    history_cond: Condition,
+    sql: &str,
+    values: SqlxValues,
+) -> Result<u64>
+where
+    MC: DbBmc,
+{
+    let mut tx = mm.db().begin().await?;
+
+    record_history::<MC>(ctx, &mut tx, op, history_cond).await?;
+
+    let ids: Vec<(i64,)> = sqlx::query_as_with(sql, values).fetch_all(&mut *tx).await?;
+    let count = ids.len() as u64;
+    check_bulk_limit(count)?;
+
+    if let (HistoryOp::Delete, Some(deleted_event)) = (op, MC::DELETED_EVENT) {
+        for (id,) in ids {
+            OutboxBmc::emit(&mut tx, &deleted_event(ctx, id)).await?;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(count)
+}
+
+fn check_bulk_limit(actual: u64) -> Result<()> {
+    if actual > BULK_LIMIT_MAX {
+        Err(Error::BulkLimitOverMax {
+            max: BULK_LIMIT_MAX,
+            actual,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+// endregion: --- Bulk
+
+/// 删除实体，软删除的实体只标记删除时间
+pub async fn delete<MC>(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()>
+where
+    MC: DbBmc,
+{
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    if MC::SOFT_DELETE {
+        return set_deleted_at::<MC>(ctx, mm, id, true).await;
+    }
+
+    // 创建 query
+    let mut query = Query::delete();
+    query
+        .from_table(MC::table_ref())
+        .and_where(Expr::col(CommonIden::Id).eq(id))
+        .and_where_option(owner_cond::<MC>(ctx))
+        .and_where_option(tenant_cond::<MC>(ctx)?);
+    let history_cond =
+        scope_cond::<MC>(ctx, DeletedScope::Include)?.add(Expr::col(CommonIden::Id).eq(id));
+
+    // 执行 query
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let event = MC::DELETED_EVENT.map(|deleted_event| deleted_event(ctx, id));
+    let count = exec_write::<MC>(
+        ctx,
+        mm,
+        HistoryOp::Delete,
+        history_cond,
+        event,
+        &sql,
+        values,
+    )
+    .await?;
+
+    // 检查 结果
     if count == 0 {
         Err(Error::EntityNotFound {
             entity: MC::TABLE,
@@ -178,17 +926,41 @@ where
     }
 }
 
-pub async fn delete<MC>(_ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()>
+/// 恢复已软删除的实体
+pub async fn restore<MC>(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()>
 where
     MC: DbBmc,
 {
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    if !MC::SOFT_DELETE {
+        return Err(Error::SoftDeleteNotEnabled { entity: MC::TABLE });
+    }
+
+    set_deleted_at::<MC>(ctx, mm, id, false).await
+}
+
+/// 永久删除 older_than_days 天之前软删除的数据，返回删除的行数
+pub async fn purge<MC>(ctx: &Ctx, mm: &ModelManager, older_than_days: u32) -> Result<u64>
+where
+    MC: DbBmc,
+{
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    if !MC::SOFT_DELETE {
+        return Err(Error::SoftDeleteNotEnabled { entity: MC::TABLE });
+    }
+
     let db = mm.db();
+    let deleted_before = now_utc_plus_sec(-(older_than_days as f64) * 86_400.0);
 
     // 创建 query
     let mut query = Query::delete();
     query
         .from_table(MC::table_ref())
-        .and_where(Expr::col(CommonIden::Id).eq(id));
+        .and_where(Expr::col(CommonIden::DeletedAt).lt(deleted_before))
+        .and_where_option(owner_cond::<MC>(ctx))
+        .and_where_option(tenant_cond::<MC>(ctx)?);
 
     // 执行 query
     let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
@@ -197,6 +969,46 @@ where
         .await?
         .rows_affected();
 
+    Ok(count)
+}
+
+// deleted 为 true 时标记未删除的数据，为 false 时恢复已删除的数据
+async fn set_deleted_at<MC>(ctx: &Ctx, mm: &ModelManager, id: i64, deleted: bool) -> Result<()>
+where
+    MC: DbBmc,
+{
+    let (value, scope, op) = if deleted {
+        (
+            Expr::current_timestamp().into(),
+            DeletedScope::Exclude,
+            HistoryOp::Delete,
+        )
+    } else {
+        (
+            SimpleExpr::Keyword(Keyword::Null),
+            DeletedScope::Only,
+            HistoryOp::Update,
+        )
+    };
+
+    // 创建 query
+    let mut query = Query::update();
+    query
+        .table(MC::table_ref())
+        .value(CommonIden::DeletedAt, value)
+        .and_where(Expr::col(CommonIden::Id).eq(id))
+        .and_where_option(owner_cond::<MC>(ctx))
+        .and_where_option(tenant_cond::<MC>(ctx)?)
+        .and_where_option(deleted_cond::<MC>(scope));
+    let history_cond = scope_cond::<MC>(ctx, scope)?.add(Expr::col(CommonIden::Id).eq(id));
+
+    // 执行 query
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let event = MC::DELETED_EVENT
+        .filter(|_| deleted)
+        .map(|deleted_event| deleted_event(ctx, id));
+    let count = exec_write::<MC>(ctx, mm, op, history_cond, event, &sql, values).await?;
+
     // 检查 结果
     if count == 0 {
         Err(Error::EntityNotFound {
@@ -207,3 +1019,298 @@ where
         Ok(())
     }
 }
+
+// region:    --- History
+
+/// entity_history 中的一条记录，data 为修改之前的整行数据
+#[serde_as]
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct EntityHistory {
+    pub id: i64,
+    pub entity: String,
+    pub entity_id: i64,
+    // update 或 delete
+    pub op: String,
+    pub data: serde_json::Value,
+    // 执行修改的用户，root ctx 为 0
+    pub changed_by: i64,
+    #[serde_as(as = "Rfc3339")]
+    pub ctime: OffsetDateTime,
+}
+
+#[derive(Iden)]
+#[iden = "entity_history"]
+enum EntityHistoryIden {
+    Table,
+    Id,
+    Entity,
+    EntityId,
+    Op,
+    Data,
+    ChangedBy,
+    Ctime,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum HistoryOp {
+    Update,
+    Delete,
+}
+
+impl HistoryOp {
+    fn as_str(self) -> &'static str {
+        match self {
+            HistoryOp::Update => "update",
+            HistoryOp::Delete => "delete",
+        }
+    }
+}
+
+/// 实体的修改历史，最新的在前，只能查看当前 ctx 可以访问的数据
+pub async fn list_history<MC>(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<Vec<EntityHistory>>
+where
+    MC: DbBmc,
+{
+    if !MC::HISTORY {
+        return Err(Error::HistoryNotEnabled { entity: MC::TABLE });
+    }
+
+    let mut query = history_select_query::<MC>(ctx, id)?;
+    query
+        .order_by(EntityHistoryIden::Id, Order::Desc)
+        .limit(LIST_LIMIT_MAX as u64);
+
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let revisions = sqlx::query_as_with::<_, EntityHistory, _>(&sql, values)
+        .fetch_all(mm.db())
+        .await?;
+
+    Ok(revisions)
+}
+
+/// 将实体恢复为某条历史记录中的数据，恢复之前的数据同样会写入历史
+/// 实体已被永久删除时重新插入
+pub async fn restore_revision<MC>(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    id: i64,
+    history_id: i64,
+) -> Result<()>
+where
+    MC: DbBmc,
+{
+    ctx.require_scope(SCOPE_WRITE)?;
+
+    if !MC::HISTORY {
+        return Err(Error::HistoryNotEnabled { entity: MC::TABLE });
+    }
+
+    let mut query = history_select_query::<MC>(ctx, id)?;
+    query.and_where(Expr::col(EntityHistoryIden::Id).eq(history_id));
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let revision = sqlx::query_as_with::<_, EntityHistory, _>(&sql, values)
+        .fetch_optional(mm.db())
+        .await?
+        .ok_or(Error::EntityNotFound {
+            entity: "entity_history",
+            id: history_id,
+        })?;
+
+    let mut tx = mm.db().begin().await?;
+    let table = quote_ident(MC::TABLE);
+
+    // 历史数据中在当前表里仍然存在的列
+    let columns: Vec<String> = sqlx::query_scalar(
+        "SELECT attname::text FROM pg_attribute
+         WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped AND $2::jsonb ? attname
+         ORDER BY attnum",
+    )
+    .bind(&table)
+    .bind(&revision.data)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // 当前数据存在时先加锁，避免和其他修改交错
+    let scope_cond =
+        scope_cond::<MC>(ctx, DeletedScope::Include)?.add(Expr::col(CommonIden::Id).eq(id));
+    let mut query = Query::select();
+    query
+        .from(MC::table_ref())
+        .column(CommonIden::Id)
+        .cond_where(scope_cond.clone())
+        .lock(LockType::Update);
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let exists = sqlx::query_with(&sql, values)
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
+
+    if exists {
+        record_history::<MC>(ctx, &mut tx, HistoryOp::Update, scope_cond).await?;
+
+        // version 和 mtime 不恢复，和普通的 update 一样更新
+        let mut sets: Vec<String> = columns
+            .iter()
+            .map(String::as_str)
+            .filter(|c| *c != "id")
+            .filter(|c| !(MC::VERSIONED && *c == "version"))
+            .filter(|c| !(MC::TIMESTAMPS && *c == "mtime"))
+            .map(|c| format!("{0} = r.{0}", quote_ident(c)))
+            .collect();
+        if MC::VERSIONED {
+            sets.push(format!("version = {table}.version + 1"));
+        }
+        if MC::TIMESTAMPS {
+            sets.push("mtime = now()".to_string());
+        }
+
+        let sql = format!(
+            "UPDATE {table} SET {} FROM jsonb_populate_record(NULL::{table}, $1) r WHERE {table}.id = $2",
+            sets.join(", ")
+        );
+        sqlx::query(&sql)
+            .bind(&revision.data)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    } else {
+        let columns = columns
+            .iter()
+            .map(|c| quote_ident(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let sql = format!(
+            "INSERT INTO {table} ({columns}) SELECT {columns} FROM jsonb_populate_record(NULL::{table}, $1)"
+        );
+        sqlx::query(&sql)
+            .bind(&revision.data)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// 执行单个实体的 update/delete，返回影响的行数
+/// 开启 HISTORY 时在同一个事务中先写入修改前的数据，有修改时写入 event
+async fn exec_write<MC>(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    op: HistoryOp,
+    history_cond: Condition,
+    event: Option<DomainEvent>,
+    sql: &str,
+    values: SqlxValues,
+) -> Result<u64>
+where
+    MC: DbBmc,
+{
+    if !MC::HISTORY && event.is_none() {
+        let count = sqlx::query_with(sql, values)
+            .execute(mm.db())
+            .await?
+            .rows_affected();
+        return Ok(count);
+    }
+
+    let mut tx = mm.db().begin().await?;
+
+    record_history::<MC>(ctx, &mut tx, op, history_cond).await?;
+    let count = sqlx::query_with(sql, values)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if let (Some(event), true) = (event, count > 0) {
+        OutboxBmc::emit(&mut tx, &event).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(count)
+}
+
+/// 把符合 cond 的数据写入 entity_history，cond 需要和接下来的 update/delete 相同
+async fn record_history<MC>(
+    ctx: &Ctx,
+    conn: &mut PgConnection,
+    op: HistoryOp,
+    cond: Condition,
+) -> Result<()>
+where
+    MC: DbBmc,
+{
+    if !MC::HISTORY {
+        return Ok(());
+    }
+
+    // 加锁后读取，写入的是这次修改之前的数据
+    let mut select = Query::select();
+    select
+        .expr(Expr::val(MC::TABLE))
+        .column(CommonIden::Id)
+        .expr(Expr::val(op.as_str()))
+        .expr(Expr::cust(format!("to_jsonb({})", quote_ident(MC::TABLE))))
+        .expr(Expr::val(ctx.user_id()))
+        .from(MC::table_ref())
+        .cond_where(cond)
+        .lock(LockType::Update);
+
+    let mut query = Query::insert();
+    query
+        .into_table(EntityHistoryIden::Table)
+        .columns([
+            EntityHistoryIden::Entity,
+            EntityHistoryIden::EntityId,
+            EntityHistoryIden::Op,
+            EntityHistoryIden::Data,
+            EntityHistoryIden::ChangedBy,
+        ])
+        .select_from(select)?;
+
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    sqlx::query_with(&sql, values).execute(conn).await?;
+
+    Ok(())
+}
+
+// 查询某个实体的历史记录，owner 和 tenant 的条件作用在历史数据上
+fn history_select_query<MC>(ctx: &Ctx, id: i64) -> Result<SelectStatement>
+where
+    MC: DbBmc,
+{
+    let data_col = |column: &'static str| Expr::cust_with_values("(data ->> $1)::bigint", [column]);
+
+    let mut query = Query::select();
+    query
+        .from(EntityHistoryIden::Table)
+        .columns([
+            EntityHistoryIden::Id,
+            EntityHistoryIden::Entity,
+            EntityHistoryIden::EntityId,
+            EntityHistoryIden::Op,
+            EntityHistoryIden::Data,
+            EntityHistoryIden::ChangedBy,
+            EntityHistoryIden::Ctime,
+        ])
+        .and_where(Expr::col(EntityHistoryIden::Entity).eq(MC::TABLE))
+        .and_where(Expr::col(EntityHistoryIden::EntityId).eq(id));
+
+    if let (Some(owner_column), Some(_)) = (MC::OWNER_COLUMN, owner_cond::<MC>(ctx)) {
+        query.and_where(data_col(owner_column).eq(ctx.user_id()));
+    }
+    if let (Some(tenant_column), Some(_)) = (MC::TENANT_COLUMN, tenant_cond::<MC>(ctx)?) {
+        query.and_where(data_col(tenant_column).eq(ctx.org_id()));
+    }
+
+    Ok(query)
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// endregion: --- History
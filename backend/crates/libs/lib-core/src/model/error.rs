@@ -1,8 +1,10 @@
 use super::store;
+use crate::ctx;
 use derive_more::From;
 use lib_auth::{pwd, token};
 use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
+use sqlx::postgres::PgDatabaseError;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -14,15 +16,124 @@ pub enum Error {
         id: i64,
     },
 
+    // 实体按组织隔离，但 ctx 中没有选择组织
+    TenantNotSelected {
+        entity: &'static str,
+    },
+
+    // 需要组织管理员身份
+    OrgAdminRequired {
+        org_id: i64,
+    },
+
+    // 用户仍是组织的所有者，不能直接删除
+    UserOwnsOrganization {
+        user_id: i64,
+    },
+
+    // 邀请不存在、已被接受、已过期或不属于当前用户
+    InvitationNotValid,
+
+    // 实体没有开启软删除，不能 restore 或 purge
+    SoftDeleteNotEnabled {
+        entity: &'static str,
+    },
+
+    // 实体没有开启修改历史
+    HistoryNotEnabled {
+        entity: &'static str,
+    },
+
+    // 实体没有开启乐观锁，不能按 version 更新
+    VersionNotEnabled {
+        entity: &'static str,
+    },
+    // 数据已被其他请求修改，需要重新读取后再更新
+    VersionConflict {
+        entity: &'static str,
+        id: i64,
+        expected: i64,
+        actual: i64,
+    },
+
+    // -- ApiKey
+    // api key 至少需要一个 scope
+    ApiKeyScopesEmpty,
+    ApiKeyScopeUnknown {
+        scope: String,
+    },
+    // 有效期需要大于 0 且不超过 max_sec
+    ApiKeyExpiresInvalid {
+        max_sec: f64,
+    },
+
+    // -- Job
+    // 任务超时后已被其他 worker 重新领取或已结束，当前 worker 不再持有这次执行
+    JobLost {
+        id: i64,
+        attempts: i32,
+    },
+
+    // -- Webhook
+    // url 不是 https 地址（本地开发时允许 http）
+    WebhookUrlInvalid {
+        url: String,
+    },
+    // url 指向内网、本机或云服务的 metadata 地址，或者无法解析
+    WebhookAddressForbidden {
+        host: String,
+    },
+    WebhookEventTypeUnknown {
+        event_type: String,
+    },
+
     ListLimitOverMax {
         max: i64,
         actual: i64,
     },
 
+    // -- 批量操作
+    BulkLimitOverMax {
+        max: u64,
+        actual: u64,
+    },
+    // 批量更新和删除必须带有 filter
+    BulkFilterRequired {
+        entity: &'static str,
+    },
+    // 批量创建的数据需要有相同的非空字段
+    BulkFieldsMismatch {
+        entity: &'static str,
+    },
+
+    // -- 数据库约束错误，由 sqlx::Error 转换而来
+    // field 为约束对应的字段，无法识别时为 None
+    UniqueViolation {
+        table: String,
+        constraint: String,
+        field: Option<String>,
+    },
+    ForeignKeyViolation {
+        table: String,
+        constraint: String,
+        field: Option<String>,
+    },
+    NotNullViolation {
+        table: String,
+        field: String,
+    },
+    CheckViolation {
+        table: String,
+        constraint: String,
+    },
+
     // Modules
     #[from]
     Store(store::Error),
 
+    #[from]
+    Ctx(ctx::Error),
+
     // Token
     #[from]
     Token(token::Error),
@@ -31,7 +142,7 @@ pub enum Error {
     #[from]
     Pwd(pwd::Error),
 
-    #[from]
+    // 约束错误之外的数据库错误，见 From<sqlx::Error>
     Sqlx(#[serde_as(as = "DisplayFromStr")] sqlx::Error),
 
     #[from]
@@ -39,10 +150,78 @@ pub enum Error {
 
     #[from]
     ModqlIntoSea(#[serde_as(as = "DisplayFromStr")] modql::filter::IntoSeaError),
+
+    #[from]
+    SerdeJson(#[serde_as(as = "DisplayFromStr")] serde_json::Error),
 }
 
 // region:    --- Froms
 
+// postgres 的 SQLSTATE，https://www.postgresql.org/docs/current/errcodes-appendix.html
+const PG_UNIQUE_VIOLATION: &str = "23505";
+const PG_FOREIGN_KEY_VIOLATION: &str = "23503";
+const PG_NOT_NULL_VIOLATION: &str = "23502";
+const PG_CHECK_VIOLATION: &str = "23514";
+
+// 不符合 postgres 默认命名规则的约束对应的字段
+const CONSTRAINT_FIELDS: &[(&str, &str)] = &[
+    ("membership_pkey", "user_id"),
+    ("user_role_pkey", "role_id"),
+];
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        let Some(pg_err) = err
+            .as_database_error()
+            .and_then(|db_err| db_err.try_downcast_ref::<PgDatabaseError>())
+        else {
+            return Self::Sqlx(err);
+        };
+
+        let table = pg_err.table().unwrap_or_default().to_string();
+        let constraint = pg_err.constraint().unwrap_or_default().to_string();
+
+        match pg_err.code() {
+            PG_UNIQUE_VIOLATION => Self::UniqueViolation {
+                field: constraint_field(&table, &constraint),
+                table,
+                constraint,
+            },
+            PG_FOREIGN_KEY_VIOLATION => Self::ForeignKeyViolation {
+                field: constraint_field(&table, &constraint),
+                table,
+                constraint,
+            },
+            PG_NOT_NULL_VIOLATION => Self::NotNullViolation {
+                field: pg_err.column().unwrap_or_default().to_string(),
+                table,
+            },
+            PG_CHECK_VIOLATION => Self::CheckViolation { table, constraint },
+            _ => Self::Sqlx(err),
+        }
+    }
+}
+
+/// 约束名对应的字段
+///
+/// postgres 默认的约束名为 `{table}_{column}_key`、`{table}_{column}_fkey`，
+/// 其他命名的约束需要在 CONSTRAINT_FIELDS 中声明
+fn constraint_field(table: &str, constraint: &str) -> Option<String> {
+    if let Some((_, field)) = CONSTRAINT_FIELDS
+        .iter()
+        .find(|(name, _)| *name == constraint)
+    {
+        return Some(field.to_string());
+    }
+
+    let rest = constraint.strip_prefix(table)?.strip_prefix('_')?;
+    let column = rest
+        .strip_suffix("_fkey")
+        .or_else(|| rest.strip_suffix("_key"))?;
+
+    (!column.is_empty()).then(|| column.to_string())
+}
+
 // endregion: --- Froms
 
 // region:    --- Error Boilerplate
@@ -54,3 +233,32 @@ impl core::fmt::Display for Error {
 
 impl std::error::Error for Error {}
 // endregion: --- Error Boilerplate
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_constraint_field() -> Result<()> {
+        // -- Exec & Check
+        assert_eq!(
+            constraint_field("user", "user_username_key").as_deref(),
+            Some("username")
+        );
+        assert_eq!(
+            constraint_field("api_key", "api_key_user_id_fkey").as_deref(),
+            Some("user_id")
+        );
+        assert_eq!(
+            constraint_field("membership", "membership_pkey").as_deref(),
+            Some("user_id")
+        );
+        assert_eq!(constraint_field("user", "user_pkey"), None);
+        assert_eq!(constraint_field("user", "other_name_key"), None);
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -0,0 +1,201 @@
+use crate::ctx::{self, Ctx, Permission, SCOPE_WRITE};
+use crate::model::user::{User, UserBmc};
+use crate::model::{Error, Result};
+use lib_auth::token::generate_impersonation_token;
+use lib_utils::time::{now_utc_plus_sec, Rfc3339};
+use modql::field::{Fields, HasFields};
+use sea_query::{Expr, Iden, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::Serialize;
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{
+    base::{self, DbBmc, UserData},
+    ModelManager,
+};
+
+// 模拟登录默认 15 分钟有效，最长 1 小时
+const IMPERSONATION_DURATION_SEC_DEFAULT: f64 = 900.0;
+const IMPERSONATION_DURATION_SEC_MAX: f64 = 3600.0;
+
+// region:    --- Impersonation Types
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct Impersonation {
+    pub id: i64,
+    pub admin_id: i64,
+    pub user_id: i64,
+    pub reason: String,
+
+    #[serde_as(as = "Rfc3339")]
+    pub expires_at: OffsetDateTime,
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub ended_at: Option<OffsetDateTime>,
+    #[serde_as(as = "Rfc3339")]
+    pub ctime: OffsetDateTime,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImpersonationForCreate {
+    pub user_id: i64,
+    pub reason: String,
+    pub duration_sec: Option<f64>,
+}
+
+/// 创建成功后返回的 token，仅此一次
+#[derive(Debug)]
+pub struct ImpersonationCreated {
+    pub id: i64,
+    pub token: String,
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(Fields)]
+struct ImpersonationForInsert {
+    admin_id: i64,
+    user_id: i64,
+    reason: String,
+    expires_at: OffsetDateTime,
+}
+
+#[derive(Clone, FromRow, Fields, Debug)]
+pub struct ImpersonationForAuth {
+    pub id: i64,
+    pub admin_id: i64,
+    pub user_id: i64,
+
+    // -- token info
+    pub token_salt: Uuid,
+
+    pub expires_at: OffsetDateTime,
+    pub ended_at: Option<OffsetDateTime>,
+}
+
+#[derive(Iden)]
+enum ImpersonationIden {
+    Id,
+    AdminId,
+    EndedAt,
+}
+
+// endregion: --- Impersonation Types
+
+pub struct ImpersonationBmc {}
+
+impl DbBmc for ImpersonationBmc {
+    const TABLE: &'static str = "impersonation";
+    // 审计记录需要保留，只清除被模拟用户的相关描述
+    const USER_DATA: &'static [UserData] = &[
+        UserData::anonymize("impersonation", "user_id", &["reason"]).secret(&["token_salt"]),
+        UserData::keep("impersonation", "admin_id").secret(&["token_salt"]),
+    ];
+}
+
+impl ImpersonationBmc {
+    /// 管理员开始模拟某个用户，返回有时限的模拟登录 token
+    pub async fn create(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        imp_c: ImpersonationForCreate,
+    ) -> Result<ImpersonationCreated> {
+        ctx.require(Permission::Impersonate)?;
+        ctx.require_not_impersonated()?;
+        if imp_c.user_id == ctx.user_id() {
+            return Err(ctx::Error::ImpersonationForbidden.into());
+        }
+
+        // 被模拟的用户必须存在
+        let user: User = UserBmc::get(ctx, mm, imp_c.user_id).await?;
+
+        let duration_sec = imp_c
+            .duration_sec
+            .unwrap_or(IMPERSONATION_DURATION_SEC_DEFAULT)
+            .clamp(1.0, IMPERSONATION_DURATION_SEC_MAX);
+
+        let imp_i = ImpersonationForInsert {
+            admin_id: ctx.user_id(),
+            user_id: user.id,
+            reason: imp_c.reason,
+            expires_at: now_utc_plus_sec(duration_sec),
+        };
+        let id = base::create::<Self, _>(ctx, mm, imp_i).await?;
+
+        let imp: ImpersonationForAuth = base::get::<Self, _>(ctx, mm, id).await?;
+        let token = generate_impersonation_token(id, duration_sec, imp.token_salt)?;
+
+        Ok(ImpersonationCreated {
+            id,
+            token: token.to_string(),
+            expires_at: imp.expires_at,
+        })
+    }
+
+    pub async fn first_for_auth(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        id: i64,
+    ) -> Result<Option<ImpersonationForAuth>> {
+        match base::get::<Self, _>(ctx, mm, id).await {
+            Ok(imp) => Ok(Some(imp)),
+            Err(Error::EntityNotFound { .. }) => Ok(None),
+            Err(ex) => Err(ex),
+        }
+    }
+
+    /// 列出所有模拟登录记录，最新的在前
+    pub async fn list(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Impersonation>> {
+        ctx.require(Permission::Impersonate)?;
+
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .columns(Impersonation::field_idens())
+            .order_by(ImpersonationIden::Id, sea_query::Order::Desc);
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let imps = sqlx::query_as_with::<_, Impersonation, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        Ok(imps)
+    }
+
+    /// 提前结束模拟登录，只能结束自己发起的模拟
+    pub async fn end(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+        ctx.require_scope(SCOPE_WRITE)?;
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::update();
+        query
+            .table(Self::table_ref())
+            .value(ImpersonationIden::EndedAt, Expr::current_timestamp())
+            .and_where(Expr::col(ImpersonationIden::Id).eq(id))
+            .and_where(Expr::col(ImpersonationIden::AdminId).eq(ctx.real_user_id()))
+            .and_where(Expr::col(ImpersonationIden::EndedAt).is_null());
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let count = sqlx::query_with(&sql, values)
+            .execute(db)
+            .await?
+            .rows_affected();
+
+        // 检查
+        if count == 0 {
+            Err(Error::EntityNotFound {
+                entity: Self::TABLE,
+                id,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use crate::ctx::{Ctx, SCOPE_WRITE};
+use crate::model::user::{User, UserBmc};
+use crate::model::{Error, Result};
+use lib_utils::time::{now_utc_plus_sec, Rfc3339};
+use modql::field::{Fields, HasFields};
+use sea_query::{Expr, Iden, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::Serialize;
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{
+    base::{self, DbBmc, UserData},
+    organization::{insert_membership_query, Membership, OrganizationBmc},
+    outbox::{DomainEvent, OutboxBmc},
+    ModelManager,
+};
+
+// 邀请默认 7 天有效
+const INVITATION_DURATION_SEC: f64 = 7.0 * 24.0 * 3600.0;
+
+// region:    --- Invitation Types
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct Invitation {
+    pub id: i64,
+    pub org_id: i64,
+    pub username: String,
+    pub token: String,
+    pub invited_by: i64,
+
+    #[serde_as(as = "Rfc3339")]
+    pub expires_at: OffsetDateTime,
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub accepted_at: Option<OffsetDateTime>,
+}
+
+#[derive(Fields)]
+struct InvitationForInsert {
+    org_id: i64,
+    username: String,
+    token: String,
+    invited_by: i64,
+    expires_at: OffsetDateTime,
+}
+
+#[derive(Iden)]
+enum InvitationIden {
+    Id,
+    OrgId,
+    Username,
+    Token,
+    ExpiresAt,
+    AcceptedAt,
+}
+
+// endregion: --- Invitation Types
+
+pub struct InvitationBmc {}
+
+impl DbBmc for InvitationBmc {
+    const TABLE: &'static str = "invitation";
+    const USER_DATA: &'static [UserData] =
+        &[UserData::delete("invitation", "invited_by").secret(&["token"])];
+}
+
+impl InvitationBmc {
+    /// 组织 admin 邀请某个用户加入组织，返回的 token 交给被邀请人用于接受邀请
+    pub async fn create(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        org_id: i64,
+        username: String,
+    ) -> Result<Invitation> {
+        OrganizationBmc::require_admin(ctx, mm, org_id).await?;
+
+        let invitation_i = InvitationForInsert {
+            org_id,
+            username,
+            token: Uuid::new_v4().simple().to_string(),
+            invited_by: ctx.user_id(),
+            expires_at: now_utc_plus_sec(INVITATION_DURATION_SEC),
+        };
+        let id = base::create::<Self, _>(ctx, mm, invitation_i).await?;
+
+        base::get::<Self, _>(ctx, mm, id).await
+    }
+
+    /// 列出组织中还未被接受的邀请
+    pub async fn list_pending(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        org_id: i64,
+    ) -> Result<Vec<Invitation>> {
+        OrganizationBmc::require_admin(ctx, mm, org_id).await?;
+
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .columns(Invitation::field_idens())
+            .and_where(Expr::col(InvitationIden::OrgId).eq(org_id))
+            .and_where(Expr::col(InvitationIden::AcceptedAt).is_null())
+            .order_by(InvitationIden::Id, sea_query::Order::Asc);
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let invitations = sqlx::query_as_with::<_, Invitation, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        Ok(invitations)
+    }
+
+    /// 当前 ctx 用户接受邀请并成为组织成员，返回组织 id
+    /// 邀请只能被邀请的用户接受一次，且不能过期
+    pub async fn accept(ctx: &Ctx, mm: &ModelManager, token: &str) -> Result<i64> {
+        ctx.require_scope(SCOPE_WRITE)?;
+        let user: User = UserBmc::get(ctx, mm, ctx.user_id()).await?;
+
+        let mut tx = mm.db().begin().await?;
+
+        // 标记邀请为已接受
+        let mut query = Query::update();
+        query
+            .table(Self::table_ref())
+            .value(InvitationIden::AcceptedAt, Expr::current_timestamp())
+            .and_where(Expr::col(InvitationIden::Token).eq(token))
+            .and_where(Expr::col(InvitationIden::Username).eq(user.username))
+            .and_where(Expr::col(InvitationIden::AcceptedAt).is_null())
+            .and_where(Expr::col(InvitationIden::ExpiresAt).gt(Expr::current_timestamp()))
+            .returning(Query::returning().columns([InvitationIden::OrgId]));
+
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let (org_id,) = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(Error::InvitationNotValid)?;
+
+        // 添加成员
+        let (sql, values) = insert_membership_query(org_id, user.id, Membership::ROLE_MEMBER)?
+            .build_sqlx(PostgresQueryBuilder);
+        sqlx::query_with(&sql, values).execute(&mut *tx).await?;
+
+        let event = DomainEvent::MemberJoined {
+            org_id,
+            user_id: user.id,
+        };
+        OutboxBmc::emit(&mut tx, &event).await?;
+
+        tx.commit().await?;
+
+        Ok(org_id)
+    }
+}
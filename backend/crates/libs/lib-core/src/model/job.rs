@@ -0,0 +1,520 @@
+//! Postgres 上的后台任务队列
+//!
+//! - 任务的 payload 为实现了 `JobPayload` 的类型，以 json 保存，`JOB_TYPE` 用于找到对应的处理函数。
+//! - worker 通过 `FOR UPDATE SKIP LOCKED` 领取任务，多个 worker 不会领取到同一个任务。
+//! - 失败的任务按重试次数推迟执行，超过 `max_attempts` 后进入 dead 状态，需要手动 `retry_dead`。
+//! - 执行中的任务超过 `LOCK_TIMEOUT_SEC` 没有结束时视为 worker 已退出，可以被重新领取，
+//!   已达到 `max_attempts` 的任务不再领取，直接进入 dead 状态。
+//! - `complete` 和 `fail` 只更新 `claim` 领取的那一次执行（按 `attempts` 区分），
+//!   任务已被重新领取时返回 `JobLost`，超时的 worker 不会覆盖新一次执行的结果。
+//!
+
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use lib_utils::time::{now_utc, now_utc_plus_sec};
+use sea_query::{Expr, Iden, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+use super::{base::DbBmc, ModelManager};
+
+// 执行中的任务超过这个时间视为 worker 已退出
+const LOCK_TIMEOUT_SEC: f64 = 15.0 * 60.0;
+// 执行次数用完的超时任务进入 dead 状态时记录的错误
+const LOCK_TIMEOUT_ERROR: &str = "lock timeout: worker did not finish the job";
+
+// 第 n 次失败后推迟 RETRY_BASE_SEC * 2^(n-1) 秒，最多推迟 RETRY_MAX_SEC 秒
+const RETRY_BASE_SEC: f64 = 10.0;
+const RETRY_MAX_SEC: f64 = 3600.0;
+
+// region:    --- Job Types
+
+/// 任务的 payload
+pub trait JobPayload: Serialize + DeserializeOwned + Send + 'static {
+    const JOB_TYPE: &'static str;
+    // 包括第一次在内的最多执行次数
+    const MAX_ATTEMPTS: i32 = 5;
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct Job {
+    pub id: i64,
+    pub job_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    // 已经领取的次数，包括正在执行的这一次
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: OffsetDateTime,
+    pub last_error: Option<String>,
+    pub ctime: OffsetDateTime,
+}
+
+impl Job {
+    pub const STATUS_PENDING: &'static str = "pending";
+    pub const STATUS_RUNNING: &'static str = "running";
+    pub const STATUS_DONE: &'static str = "done";
+    pub const STATUS_DEAD: &'static str = "dead";
+
+    pub fn payload<P: JobPayload>(&self) -> Result<P> {
+        Ok(serde_json::from_value(self.payload.clone())?)
+    }
+}
+
+#[derive(Iden)]
+#[iden = "job"]
+enum JobIden {
+    Table,
+    Id,
+    JobType,
+    Payload,
+    Status,
+    Attempts,
+    MaxAttempts,
+    RunAt,
+    LockedAt,
+    LastError,
+    Ctime,
+    Mtime,
+}
+
+// endregion: --- Job Types
+
+pub struct JobBmc {}
+
+impl DbBmc for JobBmc {
+    const TABLE: &'static str = "job";
+}
+
+impl JobBmc {
+    /// 添加立即执行的任务
+    pub async fn enqueue<P: JobPayload>(ctx: &Ctx, mm: &ModelManager, payload: &P) -> Result<i64> {
+        Self::enqueue_at(ctx, mm, payload, now_utc()).await
+    }
+
+    /// 添加在 run_at 之后执行的任务，只能由系统内部添加
+    pub async fn enqueue_at<P: JobPayload>(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        payload: &P,
+        run_at: OffsetDateTime,
+    ) -> Result<i64> {
+        ctx.require_system()?;
+
+        let payload = serde_json::to_value(payload)?;
+
+        // 创建 query
+        let mut query = Query::insert();
+        query
+            .into_table(JobIden::Table)
+            .columns([
+                JobIden::JobType,
+                JobIden::Payload,
+                JobIden::MaxAttempts,
+                JobIden::RunAt,
+            ])
+            .values([
+                P::JOB_TYPE.into(),
+                payload.into(),
+                P::MAX_ATTEMPTS.into(),
+                run_at.into(),
+            ])?
+            .returning(Query::returning().columns([JobIden::Id]));
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let (id,) = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+            .fetch_one(mm.db())
+            .await?;
+
+        Ok(id)
+    }
+
+    /// 领取最多 limit 个 job_types 中到期的任务，领取后状态为 running
+    pub async fn claim(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        job_types: &[&str],
+        limit: i64,
+    ) -> Result<Vec<Job>> {
+        ctx.require_system()?;
+
+        // 执行次数用完的超时任务不再重试，例如每次执行都会让 worker 崩溃的任务
+        sqlx::query(
+            "UPDATE job
+             SET status = 'dead', locked_at = NULL, last_error = $3, mtime = now()
+             WHERE job_type = ANY($1)
+               AND status = 'running' AND locked_at < now() - make_interval(secs => $2)
+               AND attempts >= max_attempts",
+        )
+        .bind(job_types)
+        .bind(LOCK_TIMEOUT_SEC)
+        .bind(LOCK_TIMEOUT_ERROR)
+        .execute(mm.db())
+        .await?;
+
+        let mut jobs: Vec<Job> = sqlx::query_as(
+            "UPDATE job
+             SET status = 'running', attempts = attempts + 1, locked_at = now(), mtime = now()
+             WHERE id IN (
+               SELECT id FROM job
+               WHERE job_type = ANY($1)
+                 AND ((status = 'pending' AND run_at <= now())
+                   OR (status = 'running' AND locked_at < now() - make_interval(secs => $2)
+                       AND attempts < max_attempts))
+               ORDER BY run_at, id
+               LIMIT $3
+               FOR UPDATE SKIP LOCKED
+             )
+             RETURNING id, job_type, payload, status, attempts, max_attempts, run_at, last_error, ctime",
+        )
+        .bind(job_types)
+        .bind(LOCK_TIMEOUT_SEC)
+        .bind(limit)
+        .fetch_all(mm.db())
+        .await?;
+
+        // RETURNING 不保证顺序
+        jobs.sort_by_key(|job| (job.run_at, job.id));
+
+        Ok(jobs)
+    }
+
+    /// 完成 claim 领取的这一次执行
+    pub async fn complete(ctx: &Ctx, mm: &ModelManager, job: &Job) -> Result<()> {
+        ctx.require_system()?;
+
+        let mut query = Query::update();
+        query
+            .table(JobIden::Table)
+            .value(JobIden::Status, Job::STATUS_DONE)
+            .value(JobIden::LockedAt, Expr::cust("NULL"))
+            .value(JobIden::Mtime, Expr::current_timestamp());
+
+        exec_running_update(mm, query, job).await
+    }
+
+    /// 记录失败，retry 为 false 或已达到最多执行次数时进入 dead 状态，否则推迟后重试
+    /// 返回失败后的状态
+    pub async fn fail(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        job: &Job,
+        error: &str,
+        retry: bool,
+    ) -> Result<&'static str> {
+        ctx.require_system()?;
+
+        let mut query = Query::update();
+        query.table(JobIden::Table);
+
+        let status = if retry && job.attempts < job.max_attempts {
+            query.value(JobIden::Status, Job::STATUS_PENDING).value(
+                JobIden::RunAt,
+                now_utc_plus_sec(retry_delay_sec(job.attempts)),
+            );
+            Job::STATUS_PENDING
+        } else {
+            query.value(JobIden::Status, Job::STATUS_DEAD);
+            Job::STATUS_DEAD
+        };
+
+        query
+            .value(JobIden::LastError, error)
+            .value(JobIden::LockedAt, Expr::cust("NULL"))
+            .value(JobIden::Mtime, Expr::current_timestamp());
+
+        exec_running_update(mm, query, job).await?;
+
+        Ok(status)
+    }
+
+    /// 列出 dead 状态的任务，最早失败的在前
+    pub async fn list_dead(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Job>> {
+        ctx.require_system()?;
+
+        let mut query = Query::select();
+        query
+            .from(JobIden::Table)
+            .columns([
+                JobIden::Id,
+                JobIden::JobType,
+                JobIden::Payload,
+                JobIden::Status,
+                JobIden::Attempts,
+                JobIden::MaxAttempts,
+                JobIden::RunAt,
+                JobIden::LastError,
+                JobIden::Ctime,
+            ])
+            .and_where(Expr::col(JobIden::Status).eq(Job::STATUS_DEAD))
+            .order_by(JobIden::Mtime, sea_query::Order::Asc);
+
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let jobs = sqlx::query_as_with::<_, Job, _>(&sql, values)
+            .fetch_all(mm.db())
+            .await?;
+
+        Ok(jobs)
+    }
+
+    /// 将 dead 状态的任务重新放回队列，重新计算执行次数
+    pub async fn retry_dead(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+        ctx.require_system()?;
+
+        let mut query = Query::update();
+        query
+            .table(JobIden::Table)
+            .value(JobIden::Status, Job::STATUS_PENDING)
+            .value(JobIden::Attempts, 0)
+            .value(JobIden::RunAt, Expr::current_timestamp())
+            .value(JobIden::Mtime, Expr::current_timestamp())
+            .and_where(Expr::col(JobIden::Id).eq(id))
+            .and_where(Expr::col(JobIden::Status).eq(Job::STATUS_DEAD));
+
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let count = sqlx::query_with(&sql, values)
+            .execute(mm.db())
+            .await?
+            .rows_affected();
+
+        if count == 0 {
+            Err(Error::EntityNotFound {
+                entity: Self::TABLE,
+                id,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// 更新 claim 领取的这一次执行，用 attempts 区分同一个任务的不同执行
+/// 任务不再是这一次执行时（例如超时后已被其他 worker 重新领取）返回 JobLost
+async fn exec_running_update(
+    mm: &ModelManager,
+    mut query: sea_query::UpdateStatement,
+    job: &Job,
+) -> Result<()> {
+    query
+        .and_where(Expr::col(JobIden::Id).eq(job.id))
+        .and_where(Expr::col(JobIden::Attempts).eq(job.attempts))
+        .and_where(Expr::col(JobIden::Status).eq(Job::STATUS_RUNNING));
+
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let count = sqlx::query_with(&sql, values)
+        .execute(mm.db())
+        .await?
+        .rows_affected();
+
+    if count == 0 {
+        Err(Error::JobLost {
+            id: job.id,
+            attempts: job.attempts,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// 第 attempts 次失败后推迟的秒数
+fn retry_delay_sec(attempts: i32) -> f64 {
+    let exp = attempts.saturating_sub(1).clamp(0, 30);
+    (RETRY_BASE_SEC * 2f64.powi(exp)).min(RETRY_MAX_SEC)
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use crate::ctx;
+    use anyhow::{Context, Result};
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestJob {
+        n: i64,
+    }
+
+    impl JobPayload for TestJob {
+        const JOB_TYPE: &'static str = "test_job";
+        const MAX_ATTEMPTS: i32 = 2;
+    }
+
+    async fn fx_make_due(mm: &ModelManager, id: i64) -> Result<()> {
+        sqlx::query("UPDATE job SET run_at = now() WHERE id = $1")
+            .bind(id)
+            .execute(mm.db())
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_claim_skip_locked() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let fx_types = [TestJob::JOB_TYPE];
+        let id_01 = JobBmc::enqueue(&root_ctx, mm, &TestJob { n: 1 }).await?;
+        let id_02 = JobBmc::enqueue(&root_ctx, mm, &TestJob { n: 2 }).await?;
+        JobBmc::enqueue_at(&root_ctx, mm, &TestJob { n: 3 }, now_utc_plus_sec(3600.0)).await?;
+
+        // -- Exec
+        // 其他事务锁住的任务会被跳过
+        let mut tx = mm.db().begin().await?;
+        sqlx::query("SELECT id FROM job WHERE id = $1 FOR UPDATE")
+            .bind(id_01)
+            .execute(&mut *tx)
+            .await?;
+        let claimed_locked = JobBmc::claim(&root_ctx, mm, &fx_types, 10).await?;
+        tx.rollback().await?;
+
+        let claimed = JobBmc::claim(&root_ctx, mm, &fx_types, 10).await?;
+        let claimed_again = JobBmc::claim(&root_ctx, mm, &fx_types, 10).await?;
+        let other_types = JobBmc::claim(&root_ctx, mm, &["other_job"], 10).await?;
+        let user_res = JobBmc::claim(&Ctx::new(1000)?, mm, &fx_types, 10).await;
+        let user_enqueue_res = JobBmc::enqueue(&Ctx::new(1000)?, mm, &TestJob { n: 4 }).await;
+
+        // -- Check
+        assert_eq!(
+            claimed_locked.iter().map(|j| j.id).collect::<Vec<_>>(),
+            vec![id_02]
+        );
+        assert_eq!(
+            claimed.iter().map(|j| j.id).collect::<Vec<_>>(),
+            vec![id_01]
+        );
+        assert_eq!(claimed[0].payload::<TestJob>()?, TestJob { n: 1 });
+        assert_eq!(claimed[0].status, Job::STATUS_RUNNING);
+        assert_eq!(claimed[0].attempts, 1);
+        // 执行中和未到期的任务不会被领取
+        assert!(claimed_again.is_empty());
+        assert!(other_types.is_empty());
+        assert!(user_res.is_err());
+        assert!(matches!(
+            user_enqueue_res,
+            Err(Error::Ctx(ctx::Error::SystemCtxRequired))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_retry_and_dead() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let fx_types = [TestJob::JOB_TYPE];
+        let id = JobBmc::enqueue(&root_ctx, mm, &TestJob { n: 1 }).await?;
+
+        // -- Exec
+        let first = JobBmc::claim(&root_ctx, mm, &fx_types, 1).await?;
+        let first_status = JobBmc::fail(&root_ctx, mm, &first[0], "boom 1", true).await?;
+        // 推迟后未到期
+        let not_due = JobBmc::claim(&root_ctx, mm, &fx_types, 1).await?;
+
+        fx_make_due(mm, id).await?;
+        let second = JobBmc::claim(&root_ctx, mm, &fx_types, 1).await?;
+        let second_status = JobBmc::fail(&root_ctx, mm, &second[0], "boom 2", true).await?;
+        let dead = JobBmc::list_dead(&root_ctx, mm).await?;
+
+        JobBmc::retry_dead(&root_ctx, mm, id).await?;
+        let retried = JobBmc::claim(&root_ctx, mm, &fx_types, 1).await?;
+        JobBmc::complete(&root_ctx, mm, &retried[0]).await?;
+        // 已完成的任务不能再次完成
+        let complete_again = JobBmc::complete(&root_ctx, mm, &retried[0]).await;
+
+        // -- Check
+        assert_eq!(first_status, Job::STATUS_PENDING);
+        assert!(not_due.is_empty());
+        assert_eq!(second[0].attempts, 2);
+        assert_eq!(second_status, Job::STATUS_DEAD);
+        let dead = dead.first().context("Should have dead job")?;
+        assert_eq!(dead.id, id);
+        assert_eq!(dead.last_error.as_deref(), Some("boom 2"));
+        assert_eq!(retried[0].attempts, 1);
+        assert!(matches!(complete_again, Err(Error::JobLost { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_reclaim_stale_lock() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let fx_types = [TestJob::JOB_TYPE];
+        let id_retry = JobBmc::enqueue(&root_ctx, mm, &TestJob { n: 1 }).await?;
+        let id_dead = JobBmc::enqueue(&root_ctx, mm, &TestJob { n: 2 }).await?;
+        // 模拟 worker 执行中退出，id_dead 已经用完了执行次数
+        sqlx::query(
+            "UPDATE job SET status = 'running', locked_at = now() - interval '1 day',
+               attempts = CASE WHEN id = $2 THEN max_attempts ELSE 1 END
+             WHERE id IN ($1, $2)",
+        )
+        .bind(id_retry)
+        .bind(id_dead)
+        .execute(mm.db())
+        .await?;
+
+        // 退出前领取的那一次执行，attempts 为 1
+        let fx_stale = Job {
+            id: id_retry,
+            job_type: TestJob::JOB_TYPE.to_string(),
+            payload: serde_json::json!({ "n": 1 }),
+            status: Job::STATUS_RUNNING.to_string(),
+            attempts: 1,
+            max_attempts: TestJob::MAX_ATTEMPTS,
+            run_at: now_utc(),
+            last_error: None,
+            ctime: now_utc(),
+        };
+
+        // -- Exec
+        let claimed = JobBmc::claim(&root_ctx, mm, &fx_types, 10).await?;
+        let dead = JobBmc::list_dead(&root_ctx, mm).await?;
+        // 重新领取后，旧的执行不能再完成或记录失败
+        let stale_complete = JobBmc::complete(&root_ctx, mm, &fx_stale).await;
+        let stale_fail = JobBmc::fail(&root_ctx, mm, &fx_stale, "late", true).await;
+        JobBmc::complete(&root_ctx, mm, &claimed[0]).await?;
+
+        // -- Check
+        assert_eq!(
+            claimed
+                .iter()
+                .map(|j| (j.id, j.attempts))
+                .collect::<Vec<_>>(),
+            vec![(id_retry, 2)]
+        );
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].id, id_dead);
+        assert_eq!(dead[0].last_error.as_deref(), Some(LOCK_TIMEOUT_ERROR));
+        assert!(matches!(
+            stale_complete,
+            Err(Error::JobLost { attempts: 1, .. })
+        ));
+        assert!(matches!(
+            stale_fail,
+            Err(Error::JobLost { attempts: 1, .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_delay_sec() -> Result<()> {
+        // -- Exec & Check
+        assert_eq!(retry_delay_sec(1), 10.0);
+        assert_eq!(retry_delay_sec(3), 40.0);
+        assert_eq!(retry_delay_sec(100), RETRY_MAX_SEC);
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -0,0 +1,68 @@
+//! postgres session 级别的 advisory lock
+//!
+//! - 锁属于连接，持有期间占用连接池中的一个连接，但不开启事务，不会推迟其他事务看到的 xmin。
+//! - `unlock` 释放锁并归还连接。没有 `unlock` 就 drop 时直接关闭连接，由 postgres 释放锁，
+//!   避免连接带着锁回到连接池。
+//!
+
+use crate::model::{ModelManager, Result};
+use sqlx::pool::PoolConnection;
+use sqlx::Postgres;
+
+/// 持有中的 advisory lock，key 为 (namespace, name) 的 hash
+pub struct AdvisoryLock {
+    conn: Option<PoolConnection<Postgres>>,
+    namespace: &'static str,
+    name: String,
+}
+
+impl AdvisoryLock {
+    /// 尝试获取锁，已被其他连接持有时返回 None
+    pub(in crate::model) async fn try_lock(
+        mm: &ModelManager,
+        namespace: &'static str,
+        name: &str,
+    ) -> Result<Option<Self>> {
+        let mut conn = mm.db().acquire().await?;
+
+        let locked: bool =
+            sqlx::query_scalar("SELECT pg_try_advisory_lock(hashtext($1), hashtext($2))")
+                .bind(namespace)
+                .bind(name)
+                .fetch_one(&mut *conn)
+                .await?;
+        if !locked {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            conn: Some(conn),
+            namespace,
+            name: name.to_string(),
+        }))
+    }
+
+    /// 释放锁并归还连接
+    pub async fn unlock(mut self) -> Result<()> {
+        if let Some(conn) = self.conn.as_mut() {
+            sqlx::query("SELECT pg_advisory_unlock(hashtext($1), hashtext($2))")
+                .bind(self.namespace)
+                .bind(&self.name)
+                .execute(&mut **conn)
+                .await?;
+        }
+
+        // 解锁成功后连接可以正常回到连接池
+        self.conn.take();
+
+        Ok(())
+    }
+}
+
+impl Drop for AdvisoryLock {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            drop(conn.detach());
+        }
+    }
+}
@@ -13,13 +13,28 @@
 
 // region:    --- Modules
 
+pub mod api_key;
 mod base;
 mod error;
+pub mod impersonation;
+pub mod invitation;
+pub mod job;
+mod lock;
+pub mod organization;
+pub mod outbox;
+pub mod project;
+pub mod role;
+pub mod scheduled_task;
 mod store;
 pub mod user;
+pub mod webhook;
 
+pub use self::base::{
+    DeletedScope, EntityHistory, ErasePolicy, UpsertAction, UpsertResult, UserData,
+};
 pub use self::error::{Error, Result};
-use self::store::{new_db_pool, Db};
+pub use self::lock::AdvisoryLock;
+use self::store::{new_db_pool, new_db_pool_for_database, Db};
 
 // endregion: --- Modules
 
@@ -35,7 +50,18 @@ impl ModelManager {
         Ok(ModelManager { db })
     }
 
-    pub(in crate::model) fn db(&self) -> &Db {
+    /// 连接同一个 postgres 上的另一个数据库，用于测试数据库
+    pub(crate) async fn new_for_database(database: &str) -> Result<Self> {
+        let db = new_db_pool_for_database(database).await?;
+
+        Ok(ModelManager { db })
+    }
+
+    pub(crate) fn from_db(db: Db) -> Self {
+        ModelManager { db }
+    }
+
+    pub(crate) fn db(&self) -> &Db {
         &self.db
     }
 }
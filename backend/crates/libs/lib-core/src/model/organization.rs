@@ -0,0 +1,293 @@
+use crate::ctx::{Ctx, SCOPE_WRITE};
+use crate::model::{Error, Result};
+use lib_utils::time::Rfc3339;
+use modql::field::{Fields, HasFields};
+use sea_query::{Expr, Iden, OnConflict, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+use super::{
+    base::{DbBmc, UserData},
+    webhook::delete_for_member_query,
+    ModelManager,
+};
+
+// region:    --- Organization Types
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct Organization {
+    pub id: i64,
+    pub name: String,
+    pub owner_id: i64,
+
+    #[serde_as(as = "Rfc3339")]
+    pub ctime: OffsetDateTime,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OrganizationForCreate {
+    pub name: String,
+}
+
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct Membership {
+    pub org_id: i64,
+    pub user_id: i64,
+    pub role: String,
+
+    #[serde_as(as = "Rfc3339")]
+    pub ctime: OffsetDateTime,
+}
+
+impl Membership {
+    pub const ROLE_ADMIN: &'static str = "admin";
+    pub const ROLE_MEMBER: &'static str = "member";
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Self::ROLE_ADMIN
+    }
+}
+
+#[derive(Iden)]
+#[iden = "organization"]
+enum OrganizationIden {
+    Table,
+    Id,
+    Name,
+    OwnerId,
+}
+
+#[derive(Iden)]
+#[iden = "membership"]
+enum MembershipIden {
+    Table,
+    OrgId,
+    UserId,
+    Role,
+}
+
+// endregion: --- Organization Types
+
+pub struct OrganizationBmc {}
+
+impl DbBmc for OrganizationBmc {
+    const TABLE: &'static str = "organization";
+    // 组织中还有其他成员，删除账号时只退出组织
+    const USER_DATA: &'static [UserData] = &[
+        UserData::keep("organization", "owner_id"),
+        UserData::delete("membership", "user_id"),
+    ];
+}
+
+impl OrganizationBmc {
+    /// 创建组织，创建者自动成为该组织的 admin
+    pub async fn create(ctx: &Ctx, mm: &ModelManager, org_c: OrganizationForCreate) -> Result<i64> {
+        ctx.require_scope(SCOPE_WRITE)?;
+
+        let mut tx = mm.db().begin().await?;
+
+        // 创建组织
+        let mut query = Query::insert();
+        query
+            .into_table(Self::table_ref())
+            .columns([OrganizationIden::Name, OrganizationIden::OwnerId])
+            .values([org_c.name.into(), ctx.user_id().into()])?
+            .returning(Query::returning().columns([OrganizationIden::Id]));
+
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let (org_id,) = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        // 创建者成为 admin
+        let (sql, values) = insert_membership_query(org_id, ctx.user_id(), Membership::ROLE_ADMIN)?
+            .build_sqlx(PostgresQueryBuilder);
+        sqlx::query_with(&sql, values).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(org_id)
+    }
+
+    /// 列出当前 ctx 用户所属的所有组织
+    pub async fn list_for_user(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Organization>> {
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            // membership 也有 ctime 列，需要带上表名
+            .columns(
+                Organization::field_idens()
+                    .into_iter()
+                    .map(|iden| (OrganizationIden::Table, iden)),
+            )
+            .inner_join(
+                MembershipIden::Table,
+                Expr::col((MembershipIden::Table, MembershipIden::OrgId))
+                    .equals((OrganizationIden::Table, OrganizationIden::Id)),
+            )
+            .and_where(Expr::col((MembershipIden::Table, MembershipIden::UserId)).eq(ctx.user_id()))
+            .order_by(
+                (OrganizationIden::Table, OrganizationIden::Id),
+                sea_query::Order::Asc,
+            );
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let orgs = sqlx::query_as_with::<_, Organization, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        Ok(orgs)
+    }
+
+    pub async fn first_membership(
+        _ctx: &Ctx,
+        mm: &ModelManager,
+        org_id: i64,
+        user_id: i64,
+    ) -> Result<Option<Membership>> {
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(MembershipIden::Table)
+            .columns(Membership::field_idens())
+            .and_where(Expr::col(MembershipIden::OrgId).eq(org_id))
+            .and_where(Expr::col(MembershipIden::UserId).eq(user_id));
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let membership = sqlx::query_as_with::<_, Membership, _>(&sql, values)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(membership)
+    }
+
+    /// 非成员访问组织时和组织不存在一样返回 EntityNotFound
+    pub async fn require_member(ctx: &Ctx, mm: &ModelManager, org_id: i64) -> Result<Membership> {
+        Self::first_membership(ctx, mm, org_id, ctx.user_id())
+            .await?
+            .ok_or(Error::EntityNotFound {
+                entity: Self::TABLE,
+                id: org_id,
+            })
+    }
+
+    pub async fn require_admin(ctx: &Ctx, mm: &ModelManager, org_id: i64) -> Result<Membership> {
+        let membership = Self::require_member(ctx, mm, org_id).await?;
+
+        if membership.is_admin() {
+            Ok(membership)
+        } else {
+            Err(Error::OrgAdminRequired { org_id })
+        }
+    }
+
+    /// 列出组织的所有成员，仅组织成员可以查看
+    pub async fn list_members(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        org_id: i64,
+    ) -> Result<Vec<Membership>> {
+        Self::require_member(ctx, mm, org_id).await?;
+
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(MembershipIden::Table)
+            .columns(Membership::field_idens())
+            .and_where(Expr::col(MembershipIden::OrgId).eq(org_id))
+            .order_by(MembershipIden::UserId, sea_query::Order::Asc);
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let members = sqlx::query_as_with::<_, Membership, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        Ok(members)
+    }
+
+    /// 移除组织成员，admin 可以移除任何人，成员可以移除自己（退出组织）
+    pub async fn remove_member(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        org_id: i64,
+        user_id: i64,
+    ) -> Result<()> {
+        ctx.require_scope(SCOPE_WRITE)?;
+        if user_id == ctx.user_id() {
+            Self::require_member(ctx, mm, org_id).await?;
+        } else {
+            Self::require_admin(ctx, mm, org_id).await?;
+        }
+
+        let mut tx = mm.db().begin().await?;
+
+        // 创建 query
+        let mut query = Query::delete();
+        query
+            .from_table(MembershipIden::Table)
+            .and_where(Expr::col(MembershipIden::OrgId).eq(org_id))
+            .and_where(Expr::col(MembershipIden::UserId).eq(user_id));
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let count = sqlx::query_with(&sql, values)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        // 检查
+        if count == 0 {
+            return Err(Error::EntityNotFound {
+                entity: "membership",
+                id: user_id,
+            });
+        }
+
+        // 离开组织后不再是组织管理员，创建的组织 webhook 一起删除
+        let (sql, values) =
+            delete_for_member_query(org_id, user_id).build_sqlx(PostgresQueryBuilder);
+        sqlx::query_with(&sql, values).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// 添加成员的 query，已经是成员时不做修改
+pub(in crate::model) fn insert_membership_query(
+    org_id: i64,
+    user_id: i64,
+    role: &str,
+) -> Result<sea_query::InsertStatement> {
+    let mut query = Query::insert();
+    query
+        .into_table(MembershipIden::Table)
+        .columns([
+            MembershipIden::OrgId,
+            MembershipIden::UserId,
+            MembershipIden::Role,
+        ])
+        .values([org_id.into(), user_id.into(), role.into()])?
+        .on_conflict(
+            OnConflict::columns([MembershipIden::OrgId, MembershipIden::UserId])
+                .do_nothing()
+                .to_owned(),
+        );
+
+    Ok(query)
+}
@@ -0,0 +1,431 @@
+//! 领域事件的 transactional outbox
+//!
+//! - 事件和产生它的修改在同一个事务中写入 `outbox` 表，修改回滚时事件也不会写入。
+//! - 消费者按 (txid, id) 的顺序读取，已处理到的位置记录在 `outbox_cursor` 表中。
+//! - 只读取 txid 小于当前快照 xmin 的事件，更早的事务此时都已结束，
+//!   id 较小但提交较晚的事件不会被跳过。
+//! - 多个实例共用同一个 cursor 时，分发前先通过 `try_lock_cursor` 获取锁，同时只有一个实例在分发。
+//!
+
+use crate::ctx::Ctx;
+use crate::model::Result;
+use lib_utils::time::Rfc3339;
+use sea_query::{Expr, Iden, OnConflict, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::{FromRow, PgConnection};
+use time::OffsetDateTime;
+
+use super::{base::DbBmc, AdvisoryLock, ModelManager};
+
+// region:    --- Outbox Types
+
+/// 领域事件，以 `{"type": .., "data": ..}` 的格式保存在 payload 中
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum DomainEvent {
+    UserCreated {
+        user_id: i64,
+        username: String,
+    },
+    PasswordChanged {
+        user_id: i64,
+    },
+    AccountDeletionScheduled {
+        user_id: i64,
+        #[serde_as(as = "Rfc3339")]
+        due_at: OffsetDateTime,
+    },
+    AccountErased {
+        user_id: i64,
+    },
+    ProjectDeleted {
+        project_id: i64,
+        org_id: Option<i64>,
+    },
+    // 接受邀请加入组织
+    MemberJoined {
+        org_id: i64,
+        user_id: i64,
+    },
+}
+
+impl DomainEvent {
+    /// 所有的事件类型，和 event_type 的返回值一致
+    pub const EVENT_TYPES: &'static [&'static str] = &[
+        "UserCreated",
+        "PasswordChanged",
+        "AccountDeletionScheduled",
+        "AccountErased",
+        "ProjectDeleted",
+        "MemberJoined",
+    ];
+
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            DomainEvent::UserCreated { .. } => "UserCreated",
+            DomainEvent::PasswordChanged { .. } => "PasswordChanged",
+            DomainEvent::AccountDeletionScheduled { .. } => "AccountDeletionScheduled",
+            DomainEvent::AccountErased { .. } => "AccountErased",
+            DomainEvent::ProjectDeleted { .. } => "ProjectDeleted",
+            DomainEvent::MemberJoined { .. } => "MemberJoined",
+        }
+    }
+
+    /// 事件相关的用户，用于找到该用户的 webhook
+    pub fn user_id(&self) -> Option<i64> {
+        match self {
+            DomainEvent::UserCreated { user_id, .. }
+            | DomainEvent::PasswordChanged { user_id }
+            | DomainEvent::AccountDeletionScheduled { user_id, .. }
+            | DomainEvent::AccountErased { user_id }
+            | DomainEvent::MemberJoined { user_id, .. } => Some(*user_id),
+            DomainEvent::ProjectDeleted { .. } => None,
+        }
+    }
+
+    /// 事件相关的组织，用于找到该组织的 webhook
+    pub fn org_id(&self) -> Option<i64> {
+        match self {
+            DomainEvent::ProjectDeleted { org_id, .. } => *org_id,
+            DomainEvent::MemberJoined { org_id, .. } => Some(*org_id),
+            _ => None,
+        }
+    }
+}
+
+/// 从 outbox 中读取的事件
+#[derive(Debug, Clone)]
+pub struct OutboxEvent {
+    pub id: i64,
+    pub txid: i64,
+    pub event: DomainEvent,
+    pub ctime: OffsetDateTime,
+}
+
+impl OutboxEvent {
+    pub fn cursor(&self) -> OutboxCursor {
+        OutboxCursor {
+            txid: self.txid,
+            event_id: self.id,
+        }
+    }
+}
+
+/// 消费者已处理到的位置，新的消费者从头开始读取
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromRow)]
+pub struct OutboxCursor {
+    pub txid: i64,
+    pub event_id: i64,
+}
+
+#[derive(FromRow)]
+struct OutboxRow {
+    id: i64,
+    txid: i64,
+    payload: serde_json::Value,
+    ctime: OffsetDateTime,
+}
+
+#[derive(Iden)]
+#[iden = "outbox"]
+enum OutboxIden {
+    Table,
+    EventType,
+    Payload,
+}
+
+#[derive(Iden)]
+#[iden = "outbox_cursor"]
+enum OutboxCursorIden {
+    Table,
+    Name,
+    Txid,
+    EventId,
+    Mtime,
+}
+
+// endregion: --- Outbox Types
+
+pub struct OutboxBmc {}
+
+impl DbBmc for OutboxBmc {
+    const TABLE: &'static str = "outbox";
+}
+
+impl OutboxBmc {
+    /// 在 conn 所在的事务中写入事件
+    pub(in crate::model) async fn emit(conn: &mut PgConnection, event: &DomainEvent) -> Result<()> {
+        let payload = serde_json::to_value(event)?;
+
+        let mut query = Query::insert();
+        query
+            .into_table(OutboxIden::Table)
+            .columns([OutboxIden::EventType, OutboxIden::Payload])
+            .values([event.event_type().into(), payload.into()])?;
+
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        sqlx::query_with(&sql, values).execute(conn).await?;
+
+        Ok(())
+    }
+
+    /// 读取 cursor 之后的事件，最多 limit 条
+    pub async fn list_after(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        cursor: OutboxCursor,
+        limit: i64,
+    ) -> Result<Vec<OutboxEvent>> {
+        ctx.require_system()?;
+
+        let rows: Vec<OutboxRow> = sqlx::query_as(
+            "SELECT id, txid, payload, ctime FROM outbox
+             WHERE (txid, id) > ($1, $2)
+               AND txid < pg_snapshot_xmin(pg_current_snapshot())::text::bigint
+             ORDER BY txid, id
+             LIMIT $3",
+        )
+        .bind(cursor.txid)
+        .bind(cursor.event_id)
+        .bind(limit)
+        .fetch_all(mm.db())
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(OutboxEvent {
+                    id: row.id,
+                    txid: row.txid,
+                    event: serde_json::from_value(row.payload)?,
+                    ctime: row.ctime,
+                })
+            })
+            .collect()
+    }
+
+    /// 获取 cursor name 的锁，其他实例正在使用这个 cursor 时返回 None
+    pub async fn try_lock_cursor(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        name: &str,
+    ) -> Result<Option<AdvisoryLock>> {
+        ctx.require_system()?;
+
+        AdvisoryLock::try_lock(mm, "outbox_cursor", name).await
+    }
+
+    pub async fn get_cursor(ctx: &Ctx, mm: &ModelManager, name: &str) -> Result<OutboxCursor> {
+        ctx.require_system()?;
+
+        let mut query = Query::select();
+        query
+            .from(OutboxCursorIden::Table)
+            .columns([OutboxCursorIden::Txid, OutboxCursorIden::EventId])
+            .and_where(Expr::col(OutboxCursorIden::Name).eq(name));
+
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let cursor = sqlx::query_as_with::<_, OutboxCursor, _>(&sql, values)
+            .fetch_optional(mm.db())
+            .await?;
+
+        Ok(cursor.unwrap_or_default())
+    }
+
+    pub async fn save_cursor(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        name: &str,
+        cursor: OutboxCursor,
+    ) -> Result<()> {
+        ctx.require_system()?;
+
+        let mut query = Query::insert();
+        query
+            .into_table(OutboxCursorIden::Table)
+            .columns([
+                OutboxCursorIden::Name,
+                OutboxCursorIden::Txid,
+                OutboxCursorIden::EventId,
+            ])
+            .values([name.into(), cursor.txid.into(), cursor.event_id.into()])?
+            .on_conflict(
+                OnConflict::column(OutboxCursorIden::Name)
+                    .update_columns([OutboxCursorIden::Txid, OutboxCursorIden::EventId])
+                    .value(OutboxCursorIden::Mtime, Expr::current_timestamp())
+                    .to_owned(),
+            );
+
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        sqlx::query_with(&sql, values).execute(mm.db()).await?;
+
+        Ok(())
+    }
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use crate::model::project::ProjectBmc;
+    use crate::model::user::{User, UserBmc, UserForCreate};
+    use anyhow::{Context, Result};
+    use std::time::Duration;
+
+    /// 读取 cursor 之后的所有事件
+    /// 其他数据库中进行中的事务也会推迟 xmin，事件可能稍后才能读到，最多等待 5 秒
+    async fn fx_events_after(
+        mm: &ModelManager,
+        cursor: OutboxCursor,
+        expected: usize,
+    ) -> Result<Vec<OutboxEvent>> {
+        let root_ctx = Ctx::root_ctx();
+
+        for _ in 0..50 {
+            let events = OutboxBmc::list_after(&root_ctx, mm, cursor, 100).await?;
+            if events.len() >= expected {
+                return Ok(events);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        Ok(OutboxBmc::list_after(&root_ctx, mm, cursor, 100).await?)
+    }
+
+    /// 模板库中 dev fixture 产生的事件之后的位置
+    async fn fx_last_cursor(mm: &ModelManager) -> Result<OutboxCursor> {
+        let (txid, event_id) = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COALESCE(MAX(txid), 0), COALESCE(MAX(id), 0) FROM outbox",
+        )
+        .fetch_one(mm.db())
+        .await?;
+
+        Ok(OutboxCursor { txid, event_id })
+    }
+
+    #[tokio::test]
+    async fn test_outbox_events() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let start = fx_last_cursor(mm).await?;
+        let fx_user_c = UserForCreate {
+            username: "test_outbox_user".to_string(),
+            pwd: "welcome".to_string(),
+        };
+
+        // -- Exec
+        let user_id = UserBmc::create::<UserForCreate>(&root_ctx, mm, fx_user_c.clone()).await?;
+        UserBmc::update_pwd(&root_ctx, mm, user_id, "changed").await?;
+
+        // 事务回滚时不会写入事件
+        let dup_res = UserBmc::create::<UserForCreate>(&root_ctx, mm, fx_user_c).await;
+
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let project_id = ids.projects["Demo Project"];
+        let member_ctx = Ctx::new(ids.users["demo_fx_member"])?.with_org(ids.orgs["Demo Org"]);
+        ProjectBmc::delete(&member_ctx, mm, project_id).await?;
+        // restore 和 purge 不产生事件
+        ProjectBmc::restore(&member_ctx, mm, project_id).await?;
+
+        let events = fx_events_after(mm, start, 7).await?;
+        let demo1 = UserBmc::first_by_username::<User>(&root_ctx, mm, "demo1")
+            .await?
+            .context("Should have dev user 'demo1'")?;
+
+        // -- Check
+        assert!(dup_res.is_err());
+        let fx_events = vec![
+            DomainEvent::UserCreated {
+                user_id,
+                username: "test_outbox_user".to_string(),
+            },
+            DomainEvent::PasswordChanged { user_id },
+            DomainEvent::UserCreated {
+                user_id: ids.users["demo_fx_admin"],
+                username: "demo_fx_admin".to_string(),
+            },
+            DomainEvent::UserCreated {
+                user_id: ids.users["demo_fx_member"],
+                username: "demo_fx_member".to_string(),
+            },
+            DomainEvent::MemberJoined {
+                org_id: ids.orgs["Demo Org"],
+                user_id: ids.users["demo_fx_member"],
+            },
+            DomainEvent::MemberJoined {
+                org_id: ids.orgs["Demo Org"],
+                user_id: demo1.id,
+            },
+            DomainEvent::ProjectDeleted {
+                project_id,
+                org_id: Some(ids.orgs["Demo Org"]),
+            },
+        ];
+        assert_eq!(
+            events.into_iter().map(|e| e.event).collect::<Vec<_>>(),
+            fx_events
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_outbox_cursor() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let start = fx_last_cursor(mm).await?;
+        for username in ["test_cursor_01", "test_cursor_02"] {
+            let user_c = UserForCreate {
+                username: username.to_string(),
+                pwd: "welcome".to_string(),
+            };
+            UserBmc::create::<UserForCreate>(&root_ctx, mm, user_c).await?;
+        }
+        let events = fx_events_after(mm, start, 2).await?;
+
+        // -- Exec
+        let initial = OutboxBmc::get_cursor(&root_ctx, mm, "test_cursor").await?;
+        OutboxBmc::save_cursor(&root_ctx, mm, "test_cursor", events[0].cursor()).await?;
+        let saved = OutboxBmc::get_cursor(&root_ctx, mm, "test_cursor").await?;
+        let rest = OutboxBmc::list_after(&root_ctx, mm, saved, 100).await?;
+
+        // 同一个 cursor 同时只有一个持有者
+        let lock = OutboxBmc::try_lock_cursor(&root_ctx, mm, "test_cursor").await?;
+        let while_locked = OutboxBmc::try_lock_cursor(&root_ctx, mm, "test_cursor").await?;
+        lock.expect("Should lock the cursor").unlock().await?;
+        let after_unlock = OutboxBmc::try_lock_cursor(&root_ctx, mm, "test_cursor").await?;
+
+        // 只有 system ctx 可以读取 outbox
+        let user_res = OutboxBmc::list_after(&Ctx::new(1000)?, mm, start, 100).await;
+
+        // -- Check
+        assert_eq!(initial, OutboxCursor::default());
+        assert_eq!(saved, events[0].cursor());
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].id, events[1].id);
+        assert!(while_locked.is_none());
+        after_unlock
+            .expect("Should lock after unlock")
+            .unlock()
+            .await?;
+        assert!(matches!(
+            user_res,
+            Err(crate::model::Error::Ctx(
+                crate::ctx::Error::SystemCtxRequired
+            ))
+        ));
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
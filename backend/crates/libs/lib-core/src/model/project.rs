@@ -0,0 +1,375 @@
+use crate::ctx::Ctx;
+use lib_core_macros::Bmc;
+use lib_utils::time::Rfc3339;
+use modql::field::Fields;
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+use super::{outbox::DomainEvent, UserData};
+
+// region:    --- Project Types
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize, Bmc)]
+#[bmc(
+    table = "project",
+    tenant_column = "org_id",
+    owner_fill = "owner_id",
+    soft_delete,
+    versioned,
+    timestamps,
+    history,
+    user_data = &[
+        UserData::delete("project", "owner_id"),
+        UserData::history("project", "owner_id"),
+    ],
+    deleted_event = project_deleted_event,
+    filter = ProjectFilter,
+    for_create = ProjectForCreate,
+    for_update = ProjectForUpdate
+)]
+pub struct Project {
+    pub id: i64,
+    pub org_id: i64,
+    pub owner_id: i64,
+    pub name: String,
+    // 乐观锁的版本号，每次更新加 1
+    pub version: i64,
+
+    #[serde_as(as = "Rfc3339")]
+    pub ctime: OffsetDateTime,
+    #[serde_as(as = "Rfc3339")]
+    pub mtime: OffsetDateTime,
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub deleted_at: Option<OffsetDateTime>,
+}
+
+#[derive(Clone, Fields, Debug, Deserialize)]
+pub struct ProjectForCreate {
+    pub name: String,
+}
+
+#[derive(Clone, Fields, Debug, Default, Deserialize)]
+pub struct ProjectForUpdate {
+    pub name: Option<String>,
+}
+
+#[derive(FilterNodes, Deserialize, Default, Debug)]
+pub struct ProjectFilter {
+    pub id: Option<OpValsInt64>,
+    pub owner_id: Option<OpValsInt64>,
+    pub name: Option<OpValsString>,
+}
+
+// 项目按组织隔离，删除时 ctx 选择的组织即项目所属的组织
+fn project_deleted_event(ctx: &Ctx, project_id: i64) -> DomainEvent {
+    DomainEvent::ProjectDeleted {
+        project_id,
+        org_id: ctx.org_id(),
+    }
+}
+
+// endregion: --- Project Types
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use crate::model::base;
+    use crate::model::{DeletedScope, Error};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_project_soft_delete() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let ctx = Ctx::new(ids.users["demo_fx_member"])?.with_org(ids.orgs["Demo Org"]);
+        let fx_id = ids.projects["Demo Project"];
+
+        // -- Exec & Check
+        // 删除后 get/list 不再返回，但可以通过 Include 查到
+        ProjectBmc::delete(&ctx, mm, fx_id).await?;
+        assert!(matches!(
+            ProjectBmc::get(&ctx, mm, fx_id).await,
+            Err(Error::EntityNotFound { .. })
+        ));
+        assert!(ProjectBmc::list(&ctx, mm, None, None).await?.is_empty());
+        let deleted = ProjectBmc::list_scoped(&ctx, mm, None, None, DeletedScope::Only).await?;
+        assert_eq!(deleted.len(), 1);
+        assert!(deleted[0].deleted_at.is_some());
+
+        // 恢复后可以再次访问，未删除的数据不能恢复
+        ProjectBmc::restore(&ctx, mm, fx_id).await?;
+        assert_eq!(ProjectBmc::get(&ctx, mm, fx_id).await?.deleted_at, None);
+        assert!(matches!(
+            ProjectBmc::restore(&ctx, mm, fx_id).await,
+            Err(Error::EntityNotFound { .. })
+        ));
+
+        // 只会永久删除超过期限的数据
+        ProjectBmc::delete(&ctx, mm, fx_id).await?;
+        assert_eq!(ProjectBmc::purge(&ctx, mm, 30).await?, 0);
+        assert_eq!(ProjectBmc::purge(&ctx, mm, 0).await?, 1);
+        let all = ProjectBmc::list_scoped(&ctx, mm, None, None, DeletedScope::Include).await?;
+        assert!(all.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_update_versioned() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let ctx = Ctx::new(ids.users["demo_fx_member"])?.with_org(ids.orgs["Demo Org"]);
+        let fx_id = ids.projects["Demo Project"];
+        let fx_version = ProjectBmc::get(&ctx, mm, fx_id).await?.version;
+        let fx_update = |name: &str| ProjectForUpdate {
+            name: Some(name.to_string()),
+        };
+
+        // -- Exec
+        // 两个页面读到同一个 version，先提交的成功，后提交的冲突
+        let first =
+            ProjectBmc::update_versioned(&ctx, mm, fx_id, fx_version, fx_update("tab 1")).await?;
+        let second =
+            ProjectBmc::update_versioned(&ctx, mm, fx_id, fx_version, fx_update("tab 2")).await;
+        // 不带 version 的更新也会增加 version
+        ProjectBmc::update(&ctx, mm, fx_id, fx_update("tab 3")).await?;
+
+        // -- Check
+        assert_eq!(first, fx_version + 1);
+        assert!(matches!(
+            second,
+            Err(Error::VersionConflict { expected, actual, .. })
+                if expected == fx_version && actual == fx_version + 1
+        ));
+        let project = ProjectBmc::get(&ctx, mm, fx_id).await?;
+        assert_eq!(project.name, "tab 3");
+        assert_eq!(project.version, fx_version + 2);
+        // timestamps 开启后 update 会写入修改时间
+        assert!(project.mtime > project.ctime);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_bulk() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let ctx = Ctx::new(ids.users["demo_fx_admin"])?.with_org(ids.orgs["Demo Org"]);
+        let fx_names = ["bulk-01", "bulk-02", "bulk-03"];
+
+        // -- Exec
+        let created = ProjectBmc::create_many(
+            &ctx,
+            mm,
+            fx_names
+                .iter()
+                .map(|name| ProjectForCreate {
+                    name: name.to_string(),
+                })
+                .collect(),
+        )
+        .await?;
+        let bulk_filter = || -> Result<ProjectFilter> {
+            Ok(serde_json::from_value(
+                serde_json::json!({ "name": { "$startsWith": "bulk-" } }),
+            )?)
+        };
+        let updated = ProjectBmc::update_many(
+            &ctx,
+            mm,
+            bulk_filter()?,
+            ProjectForUpdate {
+                name: Some("bulk-renamed".to_string()),
+            },
+        )
+        .await?;
+        let filterless = ProjectBmc::delete_many(&ctx, mm, ProjectFilter::default()).await;
+        let deleted = ProjectBmc::delete_many(&ctx, mm, bulk_filter()?).await?;
+
+        // -- Check
+        assert_eq!(created.len(), 3);
+        assert_eq!(updated, 3);
+        assert!(matches!(filterless, Err(Error::BulkFilterRequired { .. })));
+        assert_eq!(deleted, 3);
+        // fixture 中的项目不受影响
+        let projects = ProjectBmc::list(&ctx, mm, None, None).await?;
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].id, ids.projects["Demo Project"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_upsert() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let member_id = ids.users["demo_fx_member"];
+        let ctx = Ctx::new(member_id)?.with_org(ids.orgs["Demo Org"]);
+        let fx_id = ids.projects["Demo Project"];
+        let fx_version = ProjectBmc::get(&ctx, mm, fx_id).await?.version;
+
+        #[derive(Fields)]
+        struct ProjectForUpsert {
+            id: i64,
+            owner_id: i64,
+            name: String,
+        }
+        let fx_upsert = |name: &str| ProjectForUpsert {
+            id: fx_id,
+            owner_id: member_id,
+            name: name.to_string(),
+        };
+
+        // -- Exec
+        let updated =
+            base::upsert::<ProjectBmc, _>(&ctx, mm, &["id"], fx_upsert("upserted")).await?;
+        let project = ProjectBmc::get(&ctx, mm, fx_id).await?;
+        let revisions = ProjectBmc::list_history(&ctx, mm, fx_id).await?;
+
+        // 已软删除的数据不会被更新
+        ProjectBmc::delete(&ctx, mm, fx_id).await?;
+        let deleted_res = base::upsert::<ProjectBmc, _>(&ctx, mm, &["id"], fx_upsert("x")).await;
+
+        // -- Check
+        // 和 update 一样增加 version、写入修改时间和历史
+        assert_eq!(updated.action, base::UpsertAction::Updated);
+        assert_eq!(project.name, "upserted");
+        assert_eq!(project.version, fx_version + 1);
+        assert!(project.mtime > project.ctime);
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].data["name"], "Demo Project");
+        assert!(matches!(deleted_res, Err(Error::UniqueViolation { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_upsert_keeps_owner() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let org_id = ids.orgs["Demo Org"];
+        let admin_ctx = Ctx::new(ids.users["demo_fx_admin"])?.with_org(org_id);
+        let fx_id = ids.projects["Demo Project"];
+
+        #[derive(Fields)]
+        struct ProjectForUpsert {
+            id: i64,
+            name: String,
+        }
+
+        // -- Exec
+        // owner_id 和 org_id 由 ctx 自动写入，冲突时不会更新
+        let updated = base::upsert::<ProjectBmc, _>(
+            &admin_ctx,
+            mm,
+            &["id"],
+            ProjectForUpsert {
+                id: fx_id,
+                name: "upserted by admin".to_string(),
+            },
+        )
+        .await?;
+
+        // -- Check
+        assert_eq!(updated.action, base::UpsertAction::Updated);
+        let project = ProjectBmc::get(&admin_ctx, mm, fx_id).await?;
+        assert_eq!(project.name, "upserted by admin");
+        assert_eq!(project.owner_id, ids.users["demo_fx_member"]);
+        assert_eq!(project.org_id, org_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_history() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let member_id = ids.users["demo_fx_member"];
+        let ctx = Ctx::new(member_id)?.with_org(ids.orgs["Demo Org"]);
+        let fx_id = ids.projects["Demo Project"];
+        let fx_update = |name: &str| ProjectForUpdate {
+            name: Some(name.to_string()),
+        };
+
+        // -- Exec
+        ProjectBmc::update(&ctx, mm, fx_id, fx_update("renamed")).await?;
+        ProjectBmc::delete(&ctx, mm, fx_id).await?;
+        let revisions = ProjectBmc::list_history(&ctx, mm, fx_id).await?;
+
+        // 恢复为第一次修改之前的数据
+        ProjectBmc::restore_revision(&ctx, mm, fx_id, revisions[1].id).await?;
+        let restored = ProjectBmc::get(&ctx, mm, fx_id).await?;
+
+        // 永久删除后仍然可以从历史中恢复
+        ProjectBmc::delete(&ctx, mm, fx_id).await?;
+        ProjectBmc::purge(&Ctx::root_ctx(), mm, 0).await?;
+        ProjectBmc::restore_revision(&ctx, mm, fx_id, revisions[0].id).await?;
+        let reinserted = ProjectBmc::list_scoped(&ctx, mm, None, None, DeletedScope::Only).await?;
+
+        // 其他组织看不到历史
+        let other_ctx = Ctx::new(member_id)?.with_org(0);
+        let other_revisions = ProjectBmc::list_history(&other_ctx, mm, fx_id).await?;
+
+        // -- Check
+        assert_eq!(
+            revisions
+                .iter()
+                .map(|r| (r.op.as_str(), r.data["name"].as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("delete", Some("renamed")),
+                ("update", Some("Demo Project"))
+            ]
+        );
+        assert!(revisions.iter().all(|r| r.changed_by == member_id));
+
+        // version 继续增加，不会回到历史中的值
+        assert_eq!(restored.name, "Demo Project");
+        assert!(restored.deleted_at.is_none());
+        assert_eq!(restored.version, 3);
+
+        // 恢复的是删除之前的数据，所以重新插入后仍然是未删除的状态
+        assert!(reinserted.is_empty());
+        let reinserted = ProjectBmc::get(&ctx, mm, fx_id).await?;
+        assert_eq!(reinserted.name, "renamed");
+        assert!(other_revisions.is_empty());
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -0,0 +1,186 @@
+use crate::ctx::{Ctx, Permission};
+use crate::model::{Error, Result};
+use modql::field::{Fields, HasFields};
+use sea_query::{Expr, Iden, OnConflict, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::Serialize;
+use sqlx::FromRow;
+
+use super::{
+    base::{self, DbBmc, UserData},
+    ModelManager,
+};
+
+// region:    --- Role Types
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct Role {
+    pub id: i64,
+    pub name: String,
+
+    // 以空格分隔的权限列表，"*" 表示所有权限
+    pub permissions: String,
+}
+
+impl Role {
+    pub fn permission_list(&self) -> Vec<Permission> {
+        Permission::parse_list(&self.permissions)
+    }
+}
+
+#[derive(Clone, Fields, Debug)]
+pub struct RoleForCreate {
+    pub name: String,
+    pub permissions: String,
+}
+
+#[derive(Iden)]
+#[iden = "role"]
+enum RoleIden {
+    Table,
+    Id,
+    Name,
+}
+
+#[derive(Iden)]
+#[iden = "user_role"]
+enum UserRoleIden {
+    Table,
+    UserId,
+    RoleId,
+}
+
+// endregion: --- Role Types
+
+pub struct RoleBmc {}
+
+impl DbBmc for RoleBmc {
+    const TABLE: &'static str = "role";
+    const USER_DATA: &'static [UserData] = &[UserData::delete("user_role", "user_id")];
+}
+
+impl RoleBmc {
+    pub async fn create(ctx: &Ctx, mm: &ModelManager, role_c: RoleForCreate) -> Result<i64> {
+        ctx.require(Permission::RoleManage)?;
+
+        base::create::<Self, _>(ctx, mm, role_c).await
+    }
+
+    pub async fn list(_ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Role>> {
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .columns(Role::field_idens())
+            .order_by(RoleIden::Id, sea_query::Order::Asc);
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let roles = sqlx::query_as_with::<_, Role, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        Ok(roles)
+    }
+
+    pub async fn first_by_name(_ctx: &Ctx, mm: &ModelManager, name: &str) -> Result<Option<Role>> {
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .columns(Role::field_idens())
+            .and_where(Expr::col(RoleIden::Name).eq(name));
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let role = sqlx::query_as_with::<_, Role, _>(&sql, values)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(role)
+    }
+
+    /// 获取用户拥有的所有角色
+    pub async fn list_for_user(_ctx: &Ctx, mm: &ModelManager, user_id: i64) -> Result<Vec<Role>> {
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .columns(Role::field_column_refs())
+            .inner_join(
+                UserRoleIden::Table,
+                Expr::col((UserRoleIden::Table, UserRoleIden::RoleId))
+                    .equals((RoleIden::Table, RoleIden::Id)),
+            )
+            .and_where(Expr::col((UserRoleIden::Table, UserRoleIden::UserId)).eq(user_id))
+            .order_by((RoleIden::Table, RoleIden::Id), sea_query::Order::Asc);
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let roles = sqlx::query_as_with::<_, Role, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        Ok(roles)
+    }
+
+    /// 给用户分配角色，重复分配不会报错
+    pub async fn assign(ctx: &Ctx, mm: &ModelManager, user_id: i64, role_id: i64) -> Result<()> {
+        ctx.require(Permission::RoleManage)?;
+
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::insert();
+        query
+            .into_table(UserRoleIden::Table)
+            .columns([UserRoleIden::UserId, UserRoleIden::RoleId])
+            .values([user_id.into(), role_id.into()])?
+            .on_conflict(
+                OnConflict::columns([UserRoleIden::UserId, UserRoleIden::RoleId])
+                    .do_nothing()
+                    .to_owned(),
+            );
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        sqlx::query_with(&sql, values).execute(db).await?;
+
+        Ok(())
+    }
+
+    pub async fn unassign(ctx: &Ctx, mm: &ModelManager, user_id: i64, role_id: i64) -> Result<()> {
+        ctx.require(Permission::RoleManage)?;
+
+        let db = mm.db();
+
+        // 创建 query
+        let mut query = Query::delete();
+        query
+            .from_table(UserRoleIden::Table)
+            .and_where(Expr::col(UserRoleIden::UserId).eq(user_id))
+            .and_where(Expr::col(UserRoleIden::RoleId).eq(role_id));
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let count = sqlx::query_with(&sql, values)
+            .execute(db)
+            .await?
+            .rows_affected();
+
+        // 检查
+        if count == 0 {
+            Err(Error::EntityNotFound {
+                entity: "user_role",
+                id: role_id,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
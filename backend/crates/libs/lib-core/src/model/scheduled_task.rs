@@ -0,0 +1,116 @@
+//! 定时任务的 leader 选举
+//!
+//! - 每个 tick 开始时获取该任务的 session advisory lock，拿到锁的实例执行这个 tick，其他实例跳过。
+//! - `scheduled_task` 表记录最近执行的 tick，拿到锁后立即提交，锁释放后其他实例也不会重复执行同一个 tick。
+//! - 执行期间不持有事务，不会推迟 outbox 事件可见的时间（见 `OutboxBmc::list_after`），
+//!   但锁会占用连接池中的一个连接直到 `finish`。
+//! - 执行过程中进程退出时，这个 tick 不会被重新执行。
+//!
+
+use crate::ctx::Ctx;
+use crate::model::Result;
+use time::OffsetDateTime;
+
+use super::{base::DbBmc, AdvisoryLock, ModelManager};
+
+/// 执行一个 tick 期间持有的锁，`finish` 后释放
+pub struct TickLease {
+    lock: AdvisoryLock,
+}
+
+impl TickLease {
+    /// 释放锁，tick 记录在 `try_lead` 时已经提交
+    pub async fn finish(self) -> Result<()> {
+        self.lock.unlock().await
+    }
+}
+
+pub struct ScheduledTaskBmc {}
+
+impl DbBmc for ScheduledTaskBmc {
+    const TABLE: &'static str = "scheduled_task";
+}
+
+impl ScheduledTaskBmc {
+    /// 尝试执行任务 name 的 tick，其他实例正在执行或已经执行过该 tick 时返回 None
+    pub async fn try_lead(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        name: &str,
+        tick: OffsetDateTime,
+    ) -> Result<Option<TickLease>> {
+        ctx.require_system()?;
+
+        let Some(lock) = AdvisoryLock::try_lock(mm, "scheduled_task", name).await? else {
+            return Ok(None);
+        };
+
+        // 只有 tick 比记录的更新时才会写入
+        let count = sqlx::query(
+            "INSERT INTO scheduled_task (name, last_tick) VALUES ($1, $2)
+             ON CONFLICT (name) DO UPDATE SET last_tick = EXCLUDED.last_tick, mtime = now()
+             WHERE scheduled_task.last_tick < EXCLUDED.last_tick",
+        )
+        .bind(name)
+        .bind(tick)
+        .execute(mm.db())
+        .await?
+        .rows_affected();
+        if count == 0 {
+            lock.unlock().await?;
+            return Ok(None);
+        }
+
+        Ok(Some(TickLease { lock }))
+    }
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use anyhow::Result;
+    use lib_utils::time::{now_utc, now_utc_plus_sec};
+
+    #[tokio::test]
+    async fn test_scheduled_task_try_lead() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let fx_name = "test_task";
+        let fx_tick = now_utc().replace_nanosecond(0)?;
+        let fx_next_tick = now_utc_plus_sec(60.0).replace_nanosecond(0)?;
+
+        // -- Exec
+        let lease = ScheduledTaskBmc::try_lead(&root_ctx, mm, fx_name, fx_tick).await?;
+        // 执行中时其他实例拿不到锁
+        let while_running = ScheduledTaskBmc::try_lead(&root_ctx, mm, fx_name, fx_tick).await?;
+        let other_task = ScheduledTaskBmc::try_lead(&root_ctx, mm, "test_other", fx_tick).await?;
+        // 执行中时 tick 已经提交
+        let (recorded_tick,): (OffsetDateTime,) =
+            sqlx::query_as("SELECT last_tick FROM scheduled_task WHERE name = $1")
+                .bind(fx_name)
+                .fetch_one(mm.db())
+                .await?;
+        lease.expect("Should lead the first tick").finish().await?;
+
+        // 已经执行过的 tick 不会再次执行
+        let same_tick = ScheduledTaskBmc::try_lead(&root_ctx, mm, fx_name, fx_tick).await?;
+        let next_tick = ScheduledTaskBmc::try_lead(&root_ctx, mm, fx_name, fx_next_tick).await?;
+
+        let user_res = ScheduledTaskBmc::try_lead(&Ctx::new(1000)?, mm, fx_name, fx_tick).await;
+
+        // -- Check
+        assert!(while_running.is_none());
+        assert_eq!(recorded_tick, fx_tick);
+        assert!(other_task.is_some());
+        assert!(same_tick.is_none());
+        assert!(next_tick.is_some());
+        assert!(user_res.is_err());
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -1,8 +1,11 @@
 // region:    --- Modules
 mod error;
-use std::time::Duration;
+use std::{str::FromStr, time::Duration};
 
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    Pool, Postgres,
+};
 
 pub use self::error::{Error, Result};
 
@@ -13,10 +16,24 @@ use crate::config::core_config;
 pub type Db = Pool<Postgres>;
 
 pub async fn new_db_pool() -> Result<Db> {
+    new_db_pool_with(db_connect_options()?).await
+}
+
+// 连接同一个 postgres 上的另一个数据库，用于测试
+pub async fn new_db_pool_for_database(database: &str) -> Result<Db> {
+    new_db_pool_with(db_connect_options()?.database(database)).await
+}
+
+fn db_connect_options() -> Result<PgConnectOptions> {
+    PgConnectOptions::from_str(&core_config().DB_URL)
+        .map_err(|ex| Error::FailToCreatePool(ex.to_string()))
+}
+
+async fn new_db_pool_with(options: PgConnectOptions) -> Result<Db> {
     PgPoolOptions::new()
         .max_connections(5)
         .acquire_timeout(Duration::from_millis(500))
-        .connect(&core_config().DB_URL)
+        .connect_with(options)
         .await
         .map_err(|ex| Error::FailToCreatePool(ex.to_string()))
 }
@@ -1,14 +1,29 @@
-use crate::{ctx::Ctx, model::Result};
+use crate::config::core_config;
+use crate::ctx::{Ctx, Permission, SCOPE_WRITE};
+use crate::model::{Error, Result};
 use lib_auth::pwd::{self, ContentToHash};
+use lib_utils::time::now_utc_plus_sec;
 use modql::field::{Fields, HasFields};
-use sea_query::{Expr, Iden, PostgresQueryBuilder, Query, SimpleExpr};
+use modql::filter::{FilterNodes, ListOptions, OpValsInt64, OpValsString};
+use modql::SIden;
+use sea_query::{Expr, Iden, Order, PostgresQueryBuilder, Query, SimpleExpr};
 use sea_query_binder::SqlxBinder;
-use serde::Serialize;
-use sqlx::{postgres::PgRow, FromRow};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, FromRow, PgConnection};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use super::{
-    base::{self, DbBmc},
+    api_key::{revoke_all_query, ApiKeyBmc},
+    base::{self, DbBmc, ErasePolicy, UpsertAction, UpsertResult, UserData},
+    impersonation::ImpersonationBmc,
+    invitation::InvitationBmc,
+    organization::OrganizationBmc,
+    outbox::{DomainEvent, OutboxBmc},
+    project::ProjectBmc,
+    role::RoleBmc,
+    webhook::WebhookBmc,
     ModelManager,
 };
 
@@ -17,14 +32,49 @@ use super::{
 pub struct User {
     pub id: i64,
     pub username: String,
+
+    // 用户资料
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub locale: Option<String>,
+    pub time_zone: Option<String>,
 }
 
-#[derive(serde::Deserialize, Clone, FromRow, Fields, Debug)]
+#[derive(Deserialize, Clone, FromRow, Fields, Debug)]
 pub struct UserForCreate {
     pub username: String,
     pub pwd: String,
 }
 
+/// 更新用户资料，None 的字段不会被修改
+/// username 用于签发 token，不能通过 update 修改
+#[derive(Clone, Fields, Debug, Default, Deserialize)]
+pub struct UserForUpdate {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub locale: Option<String>,
+    pub time_zone: Option<String>,
+}
+
+/// 管理后台查询用户，username 支持 `$contains`、`$startsWith` 等操作
+#[derive(FilterNodes, Deserialize, Default, Debug)]
+pub struct UserFilter {
+    pub id: Option<OpValsInt64>,
+    pub username: Option<OpValsString>,
+}
+
+/// 从外部系统同步用户，pwd 为 None 时不修改已有用户的密码
+#[derive(Clone, Debug)]
+pub struct UserForUpsert {
+    pub username: String,
+    pub pwd: Option<String>,
+}
+
+#[derive(Fields)]
+struct UserForUpsertInsert {
+    username: String,
+}
+
 #[derive(Clone, FromRow, Fields, Debug)]
 pub struct UserForLogin {
     pub id: i64,
@@ -57,14 +107,52 @@ enum UserIden {
     Id,
     Username,
     Pwd,
+    PwdSalt,
+    TokenSalt,
+    DeletionDueAt,
+    ErasedAt,
 }
 
+/// 导出的个人数据，按表名分组，每行数据为一个 json 对象
+pub type UserDataExport = BTreeMap<&'static str, Vec<serde_json::Value>>;
+
 // endregion: --- User Types
 
 pub struct UserBmc {}
 
 impl DbBmc for UserBmc {
     const TABLE: &'static str = "user";
+    // username 需要保持唯一，在 erase 中单独处理
+    const USER_DATA: &'static [UserData] = &[UserData::anonymize(
+        "user",
+        "id",
+        &[
+            "pwd",
+            "pwd_salt",
+            "token_salt",
+            "display_name",
+            "avatar_url",
+            "locale",
+            "time_zone",
+        ],
+    )
+    .secret(&["pwd", "pwd_salt", "token_salt"])];
+}
+
+/// 所有 Bmc 声明的用户数据，新增的 Bmc 如果有用户数据需要加到这里
+fn all_user_data() -> impl Iterator<Item = &'static UserData> {
+    [
+        UserBmc::USER_DATA,
+        ApiKeyBmc::USER_DATA,
+        RoleBmc::USER_DATA,
+        OrganizationBmc::USER_DATA,
+        InvitationBmc::USER_DATA,
+        ProjectBmc::USER_DATA,
+        ImpersonationBmc::USER_DATA,
+        WebhookBmc::USER_DATA,
+    ]
+    .into_iter()
+    .flatten()
 }
 
 impl UserBmc {
@@ -75,18 +163,131 @@ impl UserBmc {
         base::get::<Self, E>(ctx, mm, id).await
     }
 
+    /// 需要 user:read 权限
+    pub async fn list(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        filter: Option<UserFilter>,
+        list_options: Option<ListOptions>,
+    ) -> Result<Vec<User>> {
+        ctx.require(Permission::UserRead)?;
+
+        base::list::<Self, _, _>(ctx, mm, filter, list_options).await
+    }
+
+    /// 用户可以修改自己的资料，修改其他用户需要 user:write 权限
+    pub async fn update(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        id: i64,
+        user_u: UserForUpdate,
+    ) -> Result<()> {
+        if ctx.user_id() != id {
+            ctx.require(Permission::UserWrite)?;
+        }
+
+        base::update::<Self, _>(ctx, mm, id, user_u).await
+    }
+
+    /// 需要 user:write 权限，用户的 api key、角色和组织成员关系会一起删除
+    /// 用户仍是组织的所有者时返回 UserOwnsOrganization，需要保留组织时使用 schedule_deletion
+    pub async fn delete(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+        ctx.require(Permission::UserWrite)?;
+
+        base::delete::<Self>(ctx, mm, id)
+            .await
+            .map_err(|err| match err {
+                Error::ForeignKeyViolation { table, .. } if table == OrganizationBmc::TABLE => {
+                    Error::UserOwnsOrganization { user_id: id }
+                }
+                err => err,
+            })
+    }
+
     pub async fn create<E>(ctx: &Ctx, mm: &ModelManager, user_c: UserForCreate) -> Result<i64>
     where
         E: UserBy,
     {
-        let id = base::create::<Self, _>(ctx, mm, user_c.clone()).await?;
+        ctx.require_not_impersonated()?;
+        ctx.require_scope(SCOPE_WRITE)?;
+
+        let mut tx = mm.db().begin().await?;
+
+        // 创建 query，密码在拿到 pwd_salt 之后再写入
+        let mut query = Query::insert();
+        query
+            .into_table(Self::table_ref())
+            .columns([UserIden::Username])
+            .values([user_c.username.clone().into()])?
+            .returning(Query::returning().columns([UserIden::Id, UserIden::PwdSalt]));
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let (id, pwd_salt) = sqlx::query_as_with::<_, (i64, Uuid), _>(&sql, values)
+            .fetch_one(&mut *tx)
+            .await?;
 
         // 给新增的账号进行密码加密
-        Self::update_pwd(ctx, mm, id, &user_c.pwd).await?;
+        set_pwd(&mut tx, id, pwd_salt, &user_c.pwd).await?;
+
+        let event = DomainEvent::UserCreated {
+            user_id: id,
+            username: user_c.username,
+        };
+        OutboxBmc::emit(&mut tx, &event).await?;
+
+        tx.commit().await?;
 
         Ok(id)
     }
 
+    /// 按 username 插入或更新用户，用于从外部系统同步，需要 user:write 权限
+    pub async fn upsert_by_username(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        user_u: UserForUpsert,
+    ) -> Result<UpsertResult> {
+        ctx.require_not_impersonated()?;
+        ctx.require(Permission::UserWrite)?;
+
+        let mut tx = mm.db().begin().await?;
+
+        let user_i = UserForUpsertInsert {
+            username: user_u.username.clone(),
+        };
+        let res = base::upsert_in::<Self, _>(ctx, &mut tx, &["username"], user_i).await?;
+
+        // 和 create 一样，新插入的用户写入 UserCreated
+        if res.action == UpsertAction::Inserted {
+            let event = DomainEvent::UserCreated {
+                user_id: res.id,
+                username: user_u.username,
+            };
+            OutboxBmc::emit(&mut tx, &event).await?;
+        }
+
+        // 密码和用户在同一个事务中写入
+        if let Some(pwd) = user_u.pwd {
+            let mut query = Query::select();
+            query
+                .from(Self::table_ref())
+                .column(UserIden::PwdSalt)
+                .and_where(Expr::col(UserIden::Id).eq(res.id));
+            let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+            let pwd_salt: Uuid = sqlx::query_scalar_with(&sql, values)
+                .fetch_one(&mut *tx)
+                .await?;
+
+            set_pwd(&mut tx, res.id, pwd_salt, &pwd).await?;
+            let event = DomainEvent::PasswordChanged { user_id: res.id };
+            OutboxBmc::emit(&mut tx, &event).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(res)
+    }
+
     pub async fn first_by_username<E>(
         _ctx: &Ctx,
         mm: &ModelManager,
@@ -114,30 +315,524 @@ impl UserBmc {
         Ok(user)
     }
 
+    /// 用户可以修改自己的密码，修改其他用户需要 user:write 权限
     pub async fn update_pwd(ctx: &Ctx, mm: &ModelManager, id: i64, pwd_clear: &str) -> Result<()> {
-        let db = mm.db();
+        ctx.require_not_impersonated()?;
+        ctx.require_scope(SCOPE_WRITE)?;
+        if ctx.user_id() != id {
+            ctx.require(Permission::UserWrite)?;
+        }
 
         // 之前的 password
         let user: UserForLogin = Self::get(ctx, mm, id).await?;
-        let pwd = pwd::hash_pwd(&ContentToHash {
-            content: pwd_clear.to_string(),
-            salt: user.pwd_salt,
-        })?;
 
-        // 创建 query
+        let mut tx = mm.db().begin().await?;
+        set_pwd(&mut tx, id, user.pwd_salt, pwd_clear).await?;
+        OutboxBmc::emit(&mut tx, &DomainEvent::PasswordChanged { user_id: id }).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// 用 pwd_salt 加密并写入密码
+async fn set_pwd(conn: &mut PgConnection, id: i64, pwd_salt: Uuid, pwd_clear: &str) -> Result<()> {
+    let pwd = pwd::hash_pwd(&ContentToHash {
+        content: pwd_clear.to_string(),
+        salt: pwd_salt,
+    })?;
+
+    // 创建 query
+    let mut query = Query::update();
+    query
+        .table(UserBmc::table_ref())
+        .value(UserIden::Pwd, SimpleExpr::from(pwd))
+        .and_where(Expr::col(UserIden::Id).eq(id));
+
+    // 执行 query
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    sqlx::query_with(&sql, values).execute(conn).await?;
+
+    Ok(())
+}
+
+// region:    --- Account Deletion
+impl UserBmc {
+    /// 导出用户的所有数据，不包含密码、密钥等列
+    pub async fn export(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<UserDataExport> {
+        if ctx.user_id() != id {
+            ctx.require(Permission::UserRead)?;
+        }
+
+        let mut export = UserDataExport::new();
+        for user_data in all_user_data() {
+            let sql = format!(
+                r#"SELECT (to_jsonb(t) - $2::text[])::text FROM "{}" t WHERE {}"#,
+                user_data.table,
+                user_data.user_cond_sql()
+            );
+            let rows: Vec<String> = sqlx::query_scalar(&sql)
+                .bind(id)
+                .bind(user_data.secret_columns)
+                .fetch_all(mm.db())
+                .await?;
+
+            let entries = export.entry(user_data.table).or_default();
+            for row in rows {
+                entries.push(serde_json::from_str(&row)?);
+            }
+        }
+
+        Ok(export)
+    }
+
+    /// 申请删除账号，返回宽限期结束的时间
+    /// 重置 token_salt 并吊销所有 api key，之前签发的凭证都会失效
+    pub async fn schedule_deletion(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        id: i64,
+    ) -> Result<OffsetDateTime> {
+        ctx.require_not_impersonated()?;
+        ctx.require_scope(SCOPE_WRITE)?;
+        if ctx.user_id() != id {
+            ctx.require(Permission::UserWrite)?;
+        }
+
+        let due_at = now_utc_plus_sec(core_config().ACCOUNT_DELETION_GRACE_SEC);
+
         let mut query = Query::update();
         query
             .table(Self::table_ref())
-            .value(UserIden::Pwd, SimpleExpr::from(pwd))
-            .and_where(Expr::col(UserIden::Id).eq(id));
+            .value(UserIden::DeletionDueAt, due_at)
+            .value(UserIden::TokenSalt, Expr::cust("DEFAULT"))
+            .and_where(Expr::col(UserIden::Id).eq(id))
+            .and_where(Expr::col(UserIden::ErasedAt).is_null());
+
+        let mut tx = mm.db().begin().await?;
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let count = sqlx::query_with(&sql, values)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if count == 0 {
+            return Err(Error::EntityNotFound {
+                entity: Self::TABLE,
+                id,
+            });
+        }
+
+        let (sql, values) = revoke_all_query(id).build_sqlx(PostgresQueryBuilder);
+        sqlx::query_with(&sql, values).execute(&mut *tx).await?;
+
+        let event = DomainEvent::AccountDeletionScheduled {
+            user_id: id,
+            due_at,
+        };
+        OutboxBmc::emit(&mut tx, &event).await?;
+        tx.commit().await?;
+
+        Ok(due_at)
+    }
+
+    /// 宽限期内取消删除，返回是否有待删除的申请
+    pub async fn cancel_deletion(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<bool> {
+        ctx.require_scope(SCOPE_WRITE)?;
+        if ctx.user_id() != id {
+            ctx.require(Permission::UserWrite)?;
+        }
+
+        let mut query = Query::update();
+        query
+            .table(Self::table_ref())
+            .value(UserIden::DeletionDueAt, Expr::cust("NULL"))
+            .and_where(Expr::col(UserIden::Id).eq(id))
+            .and_where(Expr::col(UserIden::DeletionDueAt).is_not_null())
+            .and_where(Expr::col(UserIden::ErasedAt).is_null());
 
-        // 执行 query
         let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-        let _count = sqlx::query_with(&sql, values)
-            .execute(db)
+        let count = sqlx::query_with(&sql, values)
+            .execute(mm.db())
             .await?
             .rows_affected();
 
+        Ok(count > 0)
+    }
+
+    /// 删除宽限期已结束的账号，返回处理的用户 id，由定时任务调用
+    pub async fn erase_due(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<i64>> {
+        ctx.require(Permission::UserWrite)?;
+
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .column(UserIden::Id)
+            .and_where(Expr::col(UserIden::DeletionDueAt).lte(Expr::current_timestamp()))
+            .and_where(Expr::col(UserIden::ErasedAt).is_null())
+            .order_by(UserIden::Id, Order::Asc);
+
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let ids: Vec<i64> = sqlx::query_scalar_with(&sql, values)
+            .fetch_all(mm.db())
+            .await?;
+
+        for id in &ids {
+            Self::erase(mm, *id).await?;
+        }
+
+        Ok(ids)
+    }
+
+    /// 按各表声明的 ErasePolicy 处理用户数据，user 行本身保留，username 改为 `deleted_<id>`
+    async fn erase(mm: &ModelManager, id: i64) -> Result<()> {
+        let mut tx = mm.db().begin().await?;
+
+        for user_data in all_user_data() {
+            let table = SIden(user_data.table);
+            let user_cond = Expr::cust_with_values(user_data.user_cond_sql(), [id]);
+
+            let (sql, values) = match user_data.on_erase {
+                ErasePolicy::Delete => Query::delete()
+                    .from_table(table)
+                    .and_where(user_cond)
+                    .build_sqlx(PostgresQueryBuilder),
+                ErasePolicy::Anonymize(columns) => Query::update()
+                    .table(table)
+                    .values(
+                        columns
+                            .iter()
+                            .map(|column| (SIden(column), Expr::cust("DEFAULT"))),
+                    )
+                    .and_where(user_cond)
+                    .build_sqlx(PostgresQueryBuilder),
+                ErasePolicy::Keep => continue,
+            };
+            sqlx::query_with(&sql, values).execute(&mut *tx).await?;
+        }
+
+        let mut query = Query::update();
+        query
+            .table(Self::table_ref())
+            .value(UserIden::Username, Expr::cust("'deleted_' || id"))
+            .value(UserIden::DeletionDueAt, Expr::cust("NULL"))
+            .value(UserIden::ErasedAt, Expr::current_timestamp())
+            .and_where(Expr::col(UserIden::Id).eq(id));
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        sqlx::query_with(&sql, values).execute(&mut *tx).await?;
+
+        OutboxBmc::emit(&mut tx, &DomainEvent::AccountErased { user_id: id }).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+// endregion: --- Account Deletion
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use crate::ctx;
+    use crate::model::api_key::ApiKeyForCreate;
+    use crate::model::organization::OrganizationForCreate;
+    use crate::model::project::ProjectForUpdate;
+    use crate::model::webhook::WebhookForCreate;
+    use anyhow::{Context, Result};
+    use std::collections::BTreeSet;
+
+    #[tokio::test]
+    async fn test_user_upsert_by_username() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let ctx = Ctx::root_ctx();
+        let fx_user = |username: &str, pwd: Option<&str>| UserForUpsert {
+            username: username.to_string(),
+            pwd: pwd.map(String::from),
+        };
+
+        // -- Exec
+        let inserted =
+            UserBmc::upsert_by_username(&ctx, mm, fx_user("demo_hr_sync", Some("welcome"))).await?;
+        let updated = UserBmc::upsert_by_username(&ctx, mm, fx_user("demo_hr_sync", None)).await?;
+        // 只有 username 时不修改已有数据，xmin 保持不变
+        let row_xmin = || {
+            sqlx::query_as::<_, (String,)>(
+                "SELECT xmin::text FROM \"user\" WHERE username = 'demo1'",
+            )
+            .fetch_one(mm.db())
+        };
+        let (xmin_before,) = row_xmin().await?;
+        let existing = UserBmc::upsert_by_username(&ctx, mm, fx_user("demo1", None)).await?;
+        let (xmin_after,) = row_xmin().await?;
+
+        // 普通用户不能通过 upsert 或 update_pwd 修改其他用户的密码
+        let user_ctx = Ctx::new(inserted.id)?;
+        let user_upsert_res =
+            UserBmc::upsert_by_username(&user_ctx, mm, fx_user("demo1", Some("hacked"))).await;
+        let user_pwd_res = UserBmc::update_pwd(&user_ctx, mm, existing.id, "hacked").await;
+
+        // -- Check
+        assert_eq!(inserted.action, UpsertAction::Inserted);
+        assert_eq!(updated.action, UpsertAction::Unchanged);
+        assert_eq!(updated.id, inserted.id);
+        assert_eq!(existing.action, UpsertAction::Unchanged);
+        assert_eq!(xmin_after, xmin_before);
+        for res in [user_upsert_res.map(|_| ()), user_pwd_res] {
+            assert!(matches!(
+                res,
+                Err(Error::Ctx(ctx::Error::PermissionDenied { .. }))
+            ));
+        }
+
+        // 只有插入时写入 UserCreated
+        let (created_events,): (i64,) = sqlx::query_as(
+            "SELECT count(*) FROM outbox
+             WHERE event_type = 'UserCreated' AND payload -> 'data' ->> 'username' = $1",
+        )
+        .bind("demo_hr_sync")
+        .fetch_one(mm.db())
+        .await?;
+        assert_eq!(created_events, 1);
+
+        // 不带 pwd 的同步不会修改已有的密码
+        let user: UserForLogin = UserBmc::first_by_username(&ctx, mm, "demo_hr_sync")
+            .await?
+            .context("Should have user 'demo_hr_sync'")?;
+        let pwd_hash = pwd::hash_pwd(&ContentToHash {
+            content: "welcome".to_string(),
+            salt: user.pwd_salt,
+        })?;
+        assert_eq!(user.pwd, Some(pwd_hash));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_user_list_update_delete() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let mut fx_ids = Vec::new();
+        for username in ["demo_crud_01", "demo_crud_02", "other_crud_03"] {
+            let user_c = UserForCreate {
+                username: username.to_string(),
+                pwd: "welcome".to_string(),
+            };
+            fx_ids.push(UserBmc::create::<User>(&root_ctx, mm, user_c).await?);
+        }
+        let user_ctx = Ctx::new(fx_ids[0])?;
+
+        // -- Exec
+        let filter: UserFilter = serde_json::from_value(serde_json::json!({
+            "username": { "$startsWith": "demo_crud" }
+        }))?;
+        let users = UserBmc::list(&root_ctx, mm, Some(filter), None).await?;
+        let list_denied = UserBmc::list(&user_ctx, mm, None, None).await;
+
+        // 自己的资料可以修改，其他用户需要 user:write
+        let fx_update = |display_name: &str| UserForUpdate {
+            display_name: Some(display_name.to_string()),
+            ..Default::default()
+        };
+        UserBmc::update(&user_ctx, mm, fx_ids[0], fx_update("Demo Crud")).await?;
+        let update_denied = UserBmc::update(&user_ctx, mm, fx_ids[1], fx_update("x")).await;
+        let delete_denied = UserBmc::delete(&user_ctx, mm, fx_ids[1]).await;
+        UserBmc::delete(&root_ctx, mm, fx_ids[1]).await?;
+
+        // 组织的所有者不能直接删除
+        let org_c = OrganizationForCreate {
+            name: "demo_crud_org".to_string(),
+        };
+        OrganizationBmc::create(&Ctx::new(fx_ids[2])?, mm, org_c).await?;
+        let owner_delete_res = UserBmc::delete(&root_ctx, mm, fx_ids[2]).await;
+
+        // -- Check
+        assert_eq!(
+            users.iter().map(|u| u.id).collect::<Vec<_>>(),
+            fx_ids[..2].to_vec()
+        );
+        for res in [list_denied.map(|_| ()), update_denied, delete_denied] {
+            assert!(matches!(
+                res,
+                Err(Error::Ctx(ctx::Error::PermissionDenied { .. }))
+            ));
+        }
+        let updated: User = UserBmc::get(&root_ctx, mm, fx_ids[0]).await?;
+        assert_eq!(updated.display_name.as_deref(), Some("Demo Crud"));
+        assert!(matches!(
+            UserBmc::get::<User>(&root_ctx, mm, fx_ids[1]).await,
+            Err(Error::EntityNotFound { .. })
+        ));
+        assert!(matches!(
+            owner_delete_res,
+            Err(Error::UserOwnsOrganization { user_id }) if user_id == fx_ids[2]
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_user_export_and_erase() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let fx_ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let member_id = fx_ids.users["demo_fx_member"];
+        let member_ctx = Ctx::new(member_id)?;
+        let api_key_c = ApiKeyForCreate {
+            name: "export".to_string(),
+            scopes: vec!["read".to_string()],
+            expires_in_sec: None,
+        };
+        ApiKeyBmc::create(&member_ctx, mm, api_key_c).await?;
+        // 修改项目后 entity_history 中有一条修改前的快照
+        ProjectBmc::update(
+            &member_ctx.clone().with_org(fx_ids.orgs["Demo Org"]),
+            mm,
+            fx_ids.projects["Demo Project"],
+            ProjectForUpdate {
+                name: Some("renamed".to_string()),
+            },
+        )
+        .await?;
+        let webhook_c = WebhookForCreate {
+            url: "https://example.com/hook".to_string(),
+            event_types: Vec::new(),
+        };
+        WebhookBmc::create(&member_ctx, mm, webhook_c).await?;
+        let before: UserForLogin = UserBmc::get(&root_ctx, mm, member_id).await?;
+
+        // -- Exec
+        let export = UserBmc::export(&member_ctx, mm, member_id).await?;
+        let export_denied =
+            UserBmc::export(&Ctx::new(fx_ids.users["demo_fx_admin"])?, mm, member_id).await;
+
+        UserBmc::schedule_deletion(&member_ctx, mm, member_id).await?;
+        let scheduled: UserForLogin = UserBmc::get(&root_ctx, mm, member_id).await?;
+        let api_keys = ApiKeyBmc::list(&member_ctx, mm).await?;
+        // 宽限期内不会被删除
+        let erased_early = UserBmc::erase_due(&root_ctx, mm).await?;
+
+        // 宽限期结束
+        sqlx::query(
+            r#"UPDATE "user" SET deletion_due_at = now() - interval '1 second' WHERE id = $1"#,
+        )
+        .bind(member_id)
+        .execute(mm.db())
+        .await?;
+        let erased = UserBmc::erase_due(&root_ctx, mm).await?;
+        let export_after = UserBmc::export(&root_ctx, mm, member_id).await?;
+
+        // -- Check
+        // 导出的数据不包含密码和密钥
+        assert_eq!(export["user"].len(), 1);
+        assert_eq!(export["user"][0]["username"], "demo_fx_member");
+        assert!(export["user"][0].get("pwd").is_none());
+        assert!(export["api_key"][0].get("key_hash").is_none());
+        assert!(export["webhook"][0].get("secret").is_none());
+        assert_eq!(export["project"].len(), 1);
+        assert_eq!(export["entity_history"].len(), 1);
+        assert_eq!(export["entity_history"][0]["data"]["name"], "Demo Project");
+        assert_eq!(export["membership"].len(), 1);
+        assert!(matches!(
+            export_denied,
+            Err(Error::Ctx(ctx::Error::PermissionDenied { .. }))
+        ));
+
+        // 申请删除后之前的凭证都失效
+        assert_ne!(scheduled.token_salt, before.token_salt);
+        assert!(api_keys.iter().all(|api_key| api_key.revoked_at.is_some()));
+        assert!(erased_early.is_empty());
+
+        // user 行保留但被匿名化，其他数据按 ErasePolicy 处理
+        assert_eq!(erased, vec![member_id]);
+        let user: UserForLogin = UserBmc::get(&root_ctx, mm, member_id).await?;
+        assert_eq!(user.username, format!("deleted_{member_id}"));
+        assert!(user.pwd.is_none());
+        assert_eq!(
+            export_after["user"][0]["display_name"],
+            serde_json::Value::Null
+        );
+        assert!(export_after["project"].is_empty());
+        assert!(export_after["entity_history"].is_empty());
+        assert!(export_after["membership"].is_empty());
+        assert!(export_after["api_key"].is_empty());
+        assert!(export_after["webhook"].is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_user_data_covers_user_references() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+
+        // -- Exec
+        // 所有引用 user 的外键列
+        let references: Vec<(String, String)> = sqlx::query_as(
+            r#"SELECT t.relname::text, a.attname::text
+               FROM pg_constraint c
+               JOIN pg_class t ON t.oid = c.conrelid
+               JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = ANY(c.conkey)
+               WHERE c.contype = 'f' AND c.confrelid = '"user"'::regclass"#,
+        )
+        .fetch_all(mm.db())
+        .await?;
+
+        // -- Check
+        // 新增引用 user 的表时需要声明 USER_DATA 并加到 all_user_data
+        let registered: BTreeSet<(String, String)> = all_user_data()
+            .map(|d| (d.table.to_string(), d.user_column.to_string()))
+            .collect();
+        let missing: Vec<_> = references
+            .iter()
+            .filter(|r| !registered.contains(*r))
+            .collect();
+        assert!(references.contains(&("webhook".to_string(), "user_id".to_string())));
+        assert!(missing.is_empty(), "Not in all_user_data: {missing:?}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_user_cancel_deletion() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let user_c = UserForCreate {
+            username: "demo_cancel_deletion".to_string(),
+            pwd: "welcome".to_string(),
+        };
+        let user_id = UserBmc::create::<User>(&root_ctx, mm, user_c).await?;
+        let user_ctx = Ctx::new(user_id)?;
+
+        // -- Exec
+        let not_scheduled = UserBmc::cancel_deletion(&user_ctx, mm, user_id).await?;
+        UserBmc::schedule_deletion(&user_ctx, mm, user_id).await?;
+        let cancelled = UserBmc::cancel_deletion(&user_ctx, mm, user_id).await?;
+        let imp_res =
+            UserBmc::schedule_deletion(&user_ctx.clone().with_impersonator(1000), mm, user_id)
+                .await;
+
+        // -- Check
+        assert!(!not_scheduled);
+        assert!(cancelled);
+        assert!(matches!(
+            imp_res,
+            Err(Error::Ctx(ctx::Error::ImpersonationForbidden))
+        ));
+
         Ok(())
     }
 }
+// endregion: --- Tests
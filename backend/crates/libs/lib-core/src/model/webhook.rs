@@ -0,0 +1,696 @@
+//! 出站 webhook
+//!
+//! - 没有选择组织时订阅当前用户自己的事件，选择组织时由组织管理员订阅组织的事件。
+//! - 组织的 webhook 在创建者离开组织时删除，删除账号时保留。
+//! - 事件由 outbox 的消费者通过 `worker::webhook::enqueue_deliveries` 转为投递任务，
+//!   失败时按任务队列的规则重试，每次投递都记录在 `webhook_delivery` 中。
+//! - 请求体使用 `lib_auth::webhook::sign` 签名，secret 只在创建时返回一次。
+//! - 只允许指向公网的 https 地址，创建时和每次投递前都会解析 host 并检查，
+//!   避免通过 webhook 访问内网服务（SSRF）。本地开发可以通过 `WEBHOOK_ALLOW_INSECURE` 关闭检查。
+//!
+
+use crate::config::core_config;
+use crate::ctx::Ctx;
+use crate::model::organization::OrganizationBmc;
+use crate::model::outbox::DomainEvent;
+use crate::model::{Error, Result};
+use lib_auth::webhook::generate_secret;
+use lib_utils::time::Rfc3339;
+use modql::field::{Fields, HasFields};
+use sea_query::{Cond, DeleteStatement, Expr, Iden, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::Serialize;
+use serde_with::serde_as;
+use sqlx::FromRow;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use time::OffsetDateTime;
+
+use super::{
+    base::{self, DbBmc, UserData},
+    ModelManager,
+};
+
+// 每次最多返回的投递记录数
+const DELIVERY_LIST_LIMIT: u64 = 100;
+
+// region:    --- Webhook Types
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub user_id: i64,
+    pub org_id: Option<i64>,
+    pub url: String,
+
+    // 以空格分隔的事件类型，为空时订阅所有事件
+    pub event_types: String,
+
+    #[serde_as(as = "Rfc3339")]
+    pub ctime: OffsetDateTime,
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhookForCreate {
+    pub url: String,
+    pub event_types: Vec<String>,
+}
+
+/// 创建成功后返回的 secret，仅此一次
+#[derive(Debug, Serialize)]
+pub struct WebhookCreated {
+    pub id: i64,
+    pub secret: String,
+}
+
+#[derive(Fields)]
+struct WebhookForInsert {
+    user_id: i64,
+    org_id: Option<i64>,
+    url: String,
+    secret: String,
+    event_types: String,
+}
+
+/// 投递时需要的数据，包含 secret
+#[derive(Clone, FromRow, Fields, Debug)]
+pub struct WebhookForDelivery {
+    pub id: i64,
+    pub url: String,
+    pub secret: String,
+    pub event_types: String,
+}
+
+impl Webhook {
+    pub fn event_type_list(&self) -> Vec<String> {
+        split_event_types(&self.event_types)
+    }
+}
+
+impl WebhookForDelivery {
+    /// 是否订阅了 event_type
+    pub fn accepts(&self, event_type: &str) -> bool {
+        let event_types = split_event_types(&self.event_types);
+        event_types.is_empty() || event_types.iter().any(|t| t == event_type)
+    }
+}
+
+fn split_event_types(event_types: &str) -> Vec<String> {
+    event_types.split_whitespace().map(String::from).collect()
+}
+
+/// 一次投递的记录
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub event_id: i64,
+    pub event_type: String,
+
+    // 同一个事件的第几次投递，从 1 开始
+    pub attempt: i32,
+    // 没有收到响应时为 None
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+
+    #[serde_as(as = "Rfc3339")]
+    pub ctime: OffsetDateTime,
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhookDeliveryForCreate {
+    pub webhook_id: i64,
+    pub event_id: i64,
+    pub event_type: String,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+}
+
+#[derive(Iden)]
+enum WebhookIden {
+    Id,
+    UserId,
+    OrgId,
+}
+
+#[derive(Iden)]
+enum WebhookDeliveryIden {
+    #[iden = "webhook_delivery"]
+    Table,
+    Id,
+    WebhookId,
+}
+
+// endregion: --- Webhook Types
+
+pub struct WebhookBmc {}
+
+impl DbBmc for WebhookBmc {
+    const TABLE: &'static str = "webhook";
+    // 投递记录随 webhook 级联删除
+    // 组织的 webhook 属于组织，创建者离开组织时删除（见 `OrganizationBmc::remove_member`）
+    const USER_DATA: &'static [UserData] = &[UserData::delete("webhook", "user_id")
+        .secret(&["secret"])
+        .filter(r#""org_id" IS NULL"#)];
+}
+
+impl WebhookBmc {
+    /// 创建 webhook，ctx 选择了组织时订阅组织的事件，需要组织管理员身份
+    pub async fn create(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        webhook_c: WebhookForCreate,
+    ) -> Result<WebhookCreated> {
+        ctx.require_not_impersonated()?;
+
+        let WebhookForCreate { url, event_types } = webhook_c;
+        check_url(&url).await?;
+        if let Some(event_type) = event_types
+            .iter()
+            .find(|t| !DomainEvent::EVENT_TYPES.contains(&t.as_str()))
+        {
+            return Err(Error::WebhookEventTypeUnknown {
+                event_type: event_type.clone(),
+            });
+        }
+
+        if let Some(org_id) = ctx.org_id() {
+            OrganizationBmc::require_admin(ctx, mm, org_id).await?;
+        }
+
+        let secret = generate_secret();
+        let webhook_i = WebhookForInsert {
+            user_id: ctx.user_id(),
+            org_id: ctx.org_id(),
+            url,
+            secret: secret.clone(),
+            event_types: event_types.join(" "),
+        };
+
+        let id = base::create::<Self, _>(ctx, mm, webhook_i).await?;
+
+        Ok(WebhookCreated { id, secret })
+    }
+
+    /// 列出 ctx 当前范围（用户或组织）内的 webhook
+    pub async fn list(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Webhook>> {
+        let db = mm.db();
+
+        if let Some(org_id) = ctx.org_id() {
+            OrganizationBmc::require_admin(ctx, mm, org_id).await?;
+        }
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .columns(Webhook::field_idens())
+            .cond_where(scope_cond(ctx))
+            .order_by(WebhookIden::Id, sea_query::Order::Asc);
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let webhooks = sqlx::query_as_with::<_, Webhook, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        Ok(webhooks)
+    }
+
+    /// 获取 ctx 当前范围内的 webhook，其他范围的视为不存在
+    pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<Webhook> {
+        let webhook: Webhook = base::get::<Self, _>(ctx, mm, id).await?;
+
+        let in_scope = match ctx.org_id() {
+            Some(org_id) => webhook.org_id == Some(org_id),
+            None => webhook.org_id.is_none() && webhook.user_id == ctx.user_id(),
+        };
+        if !in_scope {
+            return Err(Error::EntityNotFound {
+                entity: Self::TABLE,
+                id,
+            });
+        }
+
+        if let Some(org_id) = webhook.org_id {
+            OrganizationBmc::require_admin(ctx, mm, org_id).await?;
+        }
+
+        Ok(webhook)
+    }
+
+    pub async fn delete(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+        ctx.require_not_impersonated()?;
+        Self::get(ctx, mm, id).await?;
+
+        base::delete::<Self>(ctx, mm, id).await
+    }
+
+    /// 最近的投递记录，新的在前
+    pub async fn list_deliveries(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        id: i64,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let db = mm.db();
+        Self::get(ctx, mm, id).await?;
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(WebhookDeliveryIden::Table)
+            .columns(WebhookDelivery::field_idens())
+            .and_where(Expr::col(WebhookDeliveryIden::WebhookId).eq(id))
+            .order_by(WebhookDeliveryIden::Id, sea_query::Order::Desc)
+            .limit(DELIVERY_LIST_LIMIT);
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let deliveries = sqlx::query_as_with::<_, WebhookDelivery, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        Ok(deliveries)
+    }
+
+    /// 订阅了 event 的 webhook：事件用户自己的以及事件所属组织的
+    pub async fn list_for_event(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        event: &DomainEvent,
+    ) -> Result<Vec<WebhookForDelivery>> {
+        ctx.require_system()?;
+        let db = mm.db();
+
+        let mut cond = Cond::any();
+        if let Some(user_id) = event.user_id() {
+            cond = cond.add(
+                Cond::all()
+                    .add(Expr::col(WebhookIden::OrgId).is_null())
+                    .add(Expr::col(WebhookIden::UserId).eq(user_id)),
+            );
+        }
+        if let Some(org_id) = event.org_id() {
+            cond = cond.add(Expr::col(WebhookIden::OrgId).eq(org_id));
+        }
+        if cond.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // 创建 query
+        let mut query = Query::select();
+        query
+            .from(Self::table_ref())
+            .columns(WebhookForDelivery::field_idens())
+            .cond_where(cond)
+            .order_by(WebhookIden::Id, sea_query::Order::Asc);
+
+        // 执行 query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let webhooks = sqlx::query_as_with::<_, WebhookForDelivery, _>(&sql, values)
+            .fetch_all(db)
+            .await?;
+
+        let event_type = event.event_type();
+        Ok(webhooks
+            .into_iter()
+            .filter(|webhook| webhook.accepts(event_type))
+            .collect())
+    }
+
+    /// 投递时重新读取 webhook，已删除时返回 None
+    pub async fn first_for_delivery(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        id: i64,
+    ) -> Result<Option<WebhookForDelivery>> {
+        ctx.require_system()?;
+
+        match base::get::<Self, _>(ctx, mm, id).await {
+            Ok(webhook) => Ok(Some(webhook)),
+            Err(Error::EntityNotFound { .. }) => Ok(None),
+            Err(ex) => Err(ex),
+        }
+    }
+
+    /// 记录一次投递，返回这是同一个事件的第几次投递
+    pub async fn record_delivery(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        delivery_c: WebhookDeliveryForCreate,
+    ) -> Result<i32> {
+        ctx.require_system()?;
+        let db = mm.db();
+
+        // 同一个任务的投递不会并发执行，attempt 按已有的记录数计算
+        let (attempt,) = sqlx::query_as::<_, (i32,)>(
+            "INSERT INTO webhook_delivery
+               (webhook_id, event_id, event_type, attempt, status_code, error, duration_ms)
+             SELECT $1, $2, $3, count(*)::int + 1, $4, $5, $6
+               FROM webhook_delivery WHERE webhook_id = $1 AND event_id = $2
+             RETURNING attempt",
+        )
+        .bind(delivery_c.webhook_id)
+        .bind(delivery_c.event_id)
+        .bind(delivery_c.event_type)
+        .bind(delivery_c.status_code)
+        .bind(delivery_c.error)
+        .bind(delivery_c.duration_ms)
+        .fetch_one(db)
+        .await?;
+
+        Ok(attempt)
+    }
+}
+
+/// 删除 user_id 在组织 org_id 中创建的 webhook，用于成员离开组织
+pub(in crate::model) fn delete_for_member_query(org_id: i64, user_id: i64) -> DeleteStatement {
+    let mut query = Query::delete();
+    query
+        .from_table(WebhookBmc::table_ref())
+        .and_where(Expr::col(WebhookIden::OrgId).eq(org_id))
+        .and_where(Expr::col(WebhookIden::UserId).eq(user_id));
+    query
+}
+
+/// ctx 当前范围的条件：选择了组织时为组织的 webhook，否则为用户自己的
+fn scope_cond(ctx: &Ctx) -> Cond {
+    match ctx.org_id() {
+        Some(org_id) => Cond::all().add(Expr::col(WebhookIden::OrgId).eq(org_id)),
+        None => Cond::all()
+            .add(Expr::col(WebhookIden::OrgId).is_null())
+            .add(Expr::col(WebhookIden::UserId).eq(ctx.user_id())),
+    }
+}
+
+// region:    --- Url Check
+
+/// 检查 webhook 的 url：必须是 https，host 解析出的所有地址都必须是公网地址
+/// 配置 `WEBHOOK_ALLOW_INSECURE` 时允许 http 并且不解析 host
+pub async fn check_url(url: &str) -> Result<()> {
+    check_url_with(url, core_config().WEBHOOK_ALLOW_INSECURE).await
+}
+
+async fn check_url_with(url: &str, allow_insecure: bool) -> Result<()> {
+    let invalid = || Error::WebhookUrlInvalid {
+        url: url.to_string(),
+    };
+
+    let uri: hyper::Uri = url.parse().map_err(|_| invalid())?;
+    let host = uri.host().ok_or_else(invalid)?;
+    let default_port = match uri.scheme_str() {
+        Some("https") => 443,
+        Some("http") if allow_insecure => 80,
+        _ => return Err(invalid()),
+    };
+    if allow_insecure {
+        return Ok(());
+    }
+
+    resolve_public(host, uri.port_u16().unwrap_or(default_port), allow_insecure).await?;
+
+    Ok(())
+}
+
+/// 解析 host，任一地址不是公网地址时返回错误
+/// allow_insecure 时不检查地址
+pub async fn resolve_public(
+    host: &str,
+    port: u16,
+    allow_insecure: bool,
+) -> Result<Vec<SocketAddr>> {
+    let forbidden = || Error::WebhookAddressForbidden {
+        host: host.to_string(),
+    };
+
+    // uri 中的 ipv6 地址带有方括号
+    let name = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name, port))
+        .await
+        .map_err(|_| forbidden())?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(forbidden());
+    }
+    if !allow_insecure && !addrs.iter().all(|addr| is_public_ip(addr.ip())) {
+        return Err(forbidden());
+    }
+
+    Ok(addrs)
+}
+
+/// 是否为公网地址，排除本机、内网、链路本地（包括云服务的 metadata 地址）等
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        // 169.254.0.0/16，包括 169.254.169.254
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8
+        || a == 0
+        // 100.64.0.0/10，运营商 NAT，部分云服务的 metadata 地址在这里
+        || (a == 100 && (b & 0xc0) == 64)
+        // 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // 240.0.0.0/4
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7，包括 fd00:ec2::254
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10
+        || (first & 0xffc0) == 0xfe80)
+}
+
+// endregion: --- Url Check
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use crate::model::user::UserBmc;
+    use anyhow::Result;
+
+    fn fx_webhook_c(url: &str, event_types: &[&str]) -> WebhookForCreate {
+        WebhookForCreate {
+            url: url.to_string(),
+            event_types: event_types.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhook_scope_and_event_match() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let org_id = ids.orgs["Demo Org"];
+        let member_id = ids.users["demo_fx_member"];
+        let ctx = Ctx::new(member_id)?;
+        let admin_ctx = Ctx::new(ids.users["demo_fx_admin"])?;
+
+        // -- Exec
+        let all =
+            WebhookBmc::create(&ctx, mm, fx_webhook_c("https://example.com/all", &[])).await?;
+        let pwd_only = WebhookBmc::create(
+            &ctx,
+            mm,
+            fx_webhook_c("https://example.com/pwd", &["PasswordChanged"]),
+        )
+        .await?;
+        let bad_url = WebhookBmc::create(&ctx, mm, fx_webhook_c("ftp://example.com", &[])).await;
+        let bad_type =
+            WebhookBmc::create(&ctx, mm, fx_webhook_c("https://example.com", &["Nope"])).await;
+
+        // 组织的 webhook 需要管理员创建
+        let member_org = WebhookBmc::create(
+            &ctx.clone().with_org(org_id),
+            mm,
+            fx_webhook_c("https://example.com/org", &[]),
+        )
+        .await;
+        let org = WebhookBmc::create(
+            &admin_ctx.clone().with_org(org_id),
+            mm,
+            fx_webhook_c("https://example.com/org", &["MemberJoined"]),
+        )
+        .await?;
+
+        let erased = WebhookBmc::list_for_event(
+            &root_ctx,
+            mm,
+            &DomainEvent::AccountErased { user_id: member_id },
+        )
+        .await?;
+        let pwd_changed = WebhookBmc::list_for_event(
+            &root_ctx,
+            mm,
+            &DomainEvent::PasswordChanged { user_id: member_id },
+        )
+        .await?;
+        let joined = WebhookBmc::list_for_event(
+            &root_ctx,
+            mm,
+            &DomainEvent::MemberJoined {
+                org_id,
+                user_id: member_id,
+            },
+        )
+        .await?;
+
+        // -- Check
+        assert!(all.secret.starts_with("whsec_"));
+        assert!(matches!(bad_url, Err(Error::WebhookUrlInvalid { .. })));
+        assert!(matches!(
+            bad_type,
+            Err(Error::WebhookEventTypeUnknown { .. })
+        ));
+        assert_eq!(
+            erased.iter().map(|w| w.id).collect::<Vec<_>>(),
+            vec![all.id]
+        );
+        assert_eq!(
+            pwd_changed.iter().map(|w| w.id).collect::<Vec<_>>(),
+            vec![all.id, pwd_only.id]
+        );
+        assert_eq!(pwd_changed[1].secret, pwd_only.secret);
+        assert!(matches!(member_org, Err(Error::OrgAdminRequired { .. })));
+        // 用户自己的 webhook 订阅了所有事件，组织的 webhook 只订阅了 MemberJoined
+        assert_eq!(
+            joined.iter().map(|w| w.id).collect::<Vec<_>>(),
+            vec![all.id, org.id]
+        );
+        // 其他用户和组织范围内看不到用户自己的 webhook
+        assert!(WebhookBmc::list(&admin_ctx, mm).await?.is_empty());
+        assert!(matches!(
+            WebhookBmc::get(&admin_ctx, mm, all.id).await,
+            Err(Error::EntityNotFound { .. })
+        ));
+        assert_eq!(WebhookBmc::list(&ctx, mm).await?.len(), 2);
+        assert_eq!(
+            WebhookBmc::list(&admin_ctx.with_org(org_id), mm)
+                .await?
+                .len(),
+            1
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_webhook_org_owner_leaves() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let ids = test_db
+            .load_fixture("org_demo")
+            .await
+            .map_err(|ex| anyhow::anyhow!("{ex}"))?;
+        let org_id = ids.orgs["Demo Org"];
+        let admin_id = ids.users["demo_fx_admin"];
+        let admin_ctx = Ctx::new(admin_id)?;
+        let own = WebhookBmc::create(&admin_ctx, mm, fx_webhook_c("https://example.com/own", &[]))
+            .await?;
+        let org = WebhookBmc::create(
+            &admin_ctx.clone().with_org(org_id),
+            mm,
+            fx_webhook_c("https://example.com/org", &[]),
+        )
+        .await?;
+
+        // -- Exec
+        // 组织的 webhook 不属于个人数据
+        let export = UserBmc::export(&admin_ctx, mm, admin_id).await?;
+        OrganizationBmc::remove_member(&admin_ctx, mm, org_id, admin_id).await?;
+
+        // -- Check
+        let exported: Vec<_> = export["webhook"].iter().map(|w| &w["id"]).collect();
+        assert_eq!(exported, vec![own.id]);
+        // 离开组织后组织的 webhook 被删除，个人的保留
+        assert!(WebhookBmc::first_for_delivery(&root_ctx, mm, org.id)
+            .await?
+            .is_none());
+        assert!(WebhookBmc::first_for_delivery(&root_ctx, mm, own.id)
+            .await?
+            .is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_webhook_check_url() -> Result<()> {
+        // -- Exec & Check
+        // 公网的 https 地址
+        check_url_with("https://93.184.216.34/hook", false).await?;
+        check_url_with("https://[2606:2800:220:1::1]:8443/hook", false).await?;
+
+        // 只有本地开发允许 http
+        for url in [
+            "http://93.184.216.34/hook",
+            "ftp://93.184.216.34",
+            "not a url",
+        ] {
+            assert!(
+                matches!(
+                    check_url_with(url, false).await,
+                    Err(Error::WebhookUrlInvalid { .. })
+                ),
+                "{url}"
+            );
+        }
+        check_url_with("http://127.0.0.1:8080/hook", true).await?;
+
+        // 本机、内网和 metadata 地址
+        for url in [
+            "https://127.0.0.1/hook",
+            "https://localhost/hook",
+            "https://10.0.0.1/hook",
+            "https://192.168.1.1/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://100.100.100.200/hook",
+            "https://0.0.0.0/hook",
+            "https://[::1]/hook",
+            "https://[fd00:ec2::254]/hook",
+            "https://[::ffff:10.0.0.1]/hook",
+        ] {
+            assert!(
+                matches!(
+                    check_url_with(url, false).await,
+                    Err(Error::WebhookAddressForbidden { .. })
+                ),
+                "{url}"
+            );
+        }
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
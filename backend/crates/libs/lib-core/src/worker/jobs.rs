@@ -0,0 +1,54 @@
+//! 内置的后台任务，`register` 将它们的处理函数注册到 worker 上
+
+use super::webhook::deliver_webhook;
+use super::{HandlerResult, Worker};
+use crate::ctx::Ctx;
+use crate::model::job::JobPayload;
+use crate::model::project::ProjectBmc;
+use crate::model::user::UserBmc;
+use crate::model::ModelManager;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+pub fn register(worker: Worker) -> Worker {
+    worker
+        .handle(erase_due_accounts)
+        .handle(purge_deleted_projects)
+        .handle(deliver_webhook)
+}
+
+// region:    --- Jobs
+
+/// 删除宽限期已结束的账号
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EraseDueAccounts;
+
+impl JobPayload for EraseDueAccounts {
+    const JOB_TYPE: &'static str = "erase_due_accounts";
+}
+
+async fn erase_due_accounts(mm: ModelManager, _payload: EraseDueAccounts) -> HandlerResult {
+    let ids = UserBmc::erase_due(&Ctx::root_ctx(), &mm).await?;
+    info!("{:<12} - erase_due_accounts: {ids:?}", "JOB");
+
+    Ok(())
+}
+
+/// 永久删除 older_than_days 天之前软删除的项目
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurgeDeletedProjects {
+    pub older_than_days: u32,
+}
+
+impl JobPayload for PurgeDeletedProjects {
+    const JOB_TYPE: &'static str = "purge_deleted_projects";
+}
+
+async fn purge_deleted_projects(mm: ModelManager, payload: PurgeDeletedProjects) -> HandlerResult {
+    let count = ProjectBmc::purge(&Ctx::root_ctx(), &mm, payload.older_than_days).await?;
+    info!("{:<12} - purge_deleted_projects: {count}", "JOB");
+
+    Ok(())
+}
+
+// endregion: --- Jobs
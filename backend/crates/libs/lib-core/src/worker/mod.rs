@@ -0,0 +1,266 @@
+//! 后台任务的 worker
+//!
+//! - 通过 `Worker::handle` 注册任务类型和处理函数，worker 只领取已注册的任务类型，
+//!   web-server 中的 worker 和独立的 worker 进程可以处理不同的任务。
+//! - 处理函数返回错误时按 `JobBmc::fail` 的规则推迟重试，payload 无法解析时直接进入 dead 状态。
+//! - worker 在任务完成前退出时任务会被再次执行，处理函数需要是幂等的。
+//!
+
+// region:    --- Modules
+
+pub mod jobs;
+pub mod webhook;
+
+use crate::ctx::Ctx;
+use crate::model::job::{Job, JobBmc, JobPayload};
+use crate::model::{ModelManager, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+// endregion: --- Modules
+
+// 没有到期的任务时的轮询间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// 每次领取的任务数
+const BATCH_SIZE: i64 = 10;
+
+pub type HandlerResult = core::result::Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+type HandlerFuture = Pin<Box<dyn Future<Output = HandlerResult> + Send>>;
+// payload 无法解析时返回 Err
+type HandlerFn =
+    Arc<dyn Fn(ModelManager, serde_json::Value) -> serde_json::Result<HandlerFuture> + Send + Sync>;
+
+pub struct Worker {
+    mm: ModelManager,
+    handlers: HashMap<&'static str, HandlerFn>,
+}
+
+impl Worker {
+    pub fn new(mm: ModelManager) -> Self {
+        Self {
+            mm,
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// 注册 P 类型任务的处理函数，同一个类型后注册的会覆盖之前的
+    pub fn handle<P, F, Fut>(mut self, handler: F) -> Self
+    where
+        P: JobPayload,
+        F: Fn(ModelManager, P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        let handler: HandlerFn = Arc::new(move |mm, payload| {
+            let payload: P = serde_json::from_value(payload)?;
+            Ok(Box::pin(handler(mm, payload)))
+        });
+        self.handlers.insert(P::JOB_TYPE, handler);
+        self
+    }
+
+    /// 领取并执行一批到期的任务，返回执行的任务数
+    pub async fn run_once(&self) -> Result<usize> {
+        let ctx = Ctx::root_ctx();
+        let job_types: Vec<&str> = self.handlers.keys().copied().collect();
+
+        let jobs = JobBmc::claim(&ctx, &self.mm, &job_types, BATCH_SIZE).await?;
+        let count = jobs.len();
+
+        // 单个任务状态写入失败时继续执行其他任务，未结束的任务超时后会被重新领取
+        for job in jobs {
+            let (job_type, id) = (job.job_type.clone(), job.id);
+            if let Err(ex) = self.run_job(&ctx, job).await {
+                error!("{:<12} - {job_type} {id} - {ex:?}", "WORKER");
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn run_job(&self, ctx: &Ctx, job: Job) -> Result<()> {
+        // 只会领取到已注册的类型
+        let Some(handler) = self.handlers.get(job.job_type.as_str()) else {
+            return Ok(());
+        };
+
+        let res = match handler(self.mm.clone(), job.payload.clone()) {
+            Ok(fut) => fut.await.map_err(|ex| (ex.to_string(), true)),
+            Err(ex) => Err((ex.to_string(), false)),
+        };
+
+        match res {
+            Ok(()) => JobBmc::complete(ctx, &self.mm, &job).await,
+            Err((cause, retry)) => {
+                let status = JobBmc::fail(ctx, &self.mm, &job, &cause, retry).await?;
+                error!(
+                    "{:<12} - {} {} failed ({}/{}) -> {status}: {cause}",
+                    "WORKER", job.job_type, job.id, job.attempts, job.max_attempts
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// 持续执行任务，领满一批时立即领取下一批
+    pub async fn run(self) {
+        info!(
+            "{:<12} - running jobs: {:?}",
+            "WORKER",
+            self.handlers.keys().collect::<Vec<_>>()
+        );
+
+        loop {
+            match self.run_once().await {
+                Ok(count) if count as i64 == BATCH_SIZE => continue,
+                Ok(_) => {}
+                Err(ex) => error!("{:<12} - {ex:?}", "WORKER"),
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+}
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use anyhow::Result;
+    use serde::{Deserialize, Serialize};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestFlakyJob {
+        n: i64,
+    }
+
+    impl JobPayload for TestFlakyJob {
+        const JOB_TYPE: &'static str = "test_flaky_job";
+    }
+
+    async fn fx_job_status(mm: &ModelManager, id: i64) -> Result<(String, i32)> {
+        let row = sqlx::query_as("SELECT status, attempts FROM job WHERE id = $1")
+            .bind(id)
+            .fetch_one(mm.db())
+            .await?;
+        Ok(row)
+    }
+
+    #[tokio::test]
+    async fn test_worker_run_once() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let worker = {
+            let calls = calls.clone();
+            // 第一次执行失败
+            Worker::new(mm.clone()).handle(move |_mm, payload: TestFlakyJob| {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if call == 0 {
+                        Err(format!("flaky {}", payload.n).into())
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+        };
+        let id = JobBmc::enqueue(&root_ctx, mm, &TestFlakyJob { n: 1 }).await?;
+        // payload 和类型不匹配
+        let bad_id: i64 = sqlx::query_scalar(
+            "INSERT INTO job (job_type, payload, max_attempts)
+             VALUES ('test_flaky_job', '{\"m\": 1}', 5) RETURNING id",
+        )
+        .fetch_one(mm.db())
+        .await?;
+
+        // -- Exec
+        let first_count = worker.run_once().await?;
+        let after_fail = fx_job_status(mm, id).await?;
+
+        sqlx::query("UPDATE job SET run_at = now() WHERE id = $1")
+            .bind(id)
+            .execute(mm.db())
+            .await?;
+        let second_count = worker.run_once().await?;
+
+        // -- Check
+        assert_eq!(first_count, 2);
+        assert_eq!(after_fail, (Job::STATUS_PENDING.to_string(), 1));
+        assert_eq!(second_count, 1);
+        assert_eq!(
+            fx_job_status(mm, id).await?,
+            (Job::STATUS_DONE.to_string(), 2)
+        );
+        // payload 无法解析时不会重试
+        assert_eq!(
+            fx_job_status(mm, bad_id).await?,
+            (Job::STATUS_DEAD.to_string(), 1)
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestResetJob {
+        n: i64,
+    }
+
+    impl JobPayload for TestResetJob {
+        const JOB_TYPE: &'static str = "test_reset_job";
+    }
+
+    #[tokio::test]
+    async fn test_worker_run_once_continue_on_error() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        // n 为 1 的任务在执行中被放回队列，之后写入完成状态会失败
+        let worker = Worker::new(mm.clone()).handle(|mm, payload: TestResetJob| async move {
+            if payload.n == 1 {
+                sqlx::query(
+                    "UPDATE job SET status = 'pending', locked_at = NULL
+                     WHERE job_type = $1 AND payload ->> 'n' = '1'",
+                )
+                .bind(TestResetJob::JOB_TYPE)
+                .execute(mm.db())
+                .await?;
+            }
+            Ok(())
+        });
+        let id_reset = JobBmc::enqueue(&root_ctx, mm, &TestResetJob { n: 1 }).await?;
+        let id_ok = JobBmc::enqueue(&root_ctx, mm, &TestResetJob { n: 2 }).await?;
+
+        // -- Exec
+        let count = worker.run_once().await?;
+
+        // -- Check
+        assert_eq!(count, 2);
+        assert_eq!(
+            fx_job_status(mm, id_reset).await?,
+            (Job::STATUS_PENDING.to_string(), 1)
+        );
+        assert_eq!(
+            fx_job_status(mm, id_ok).await?,
+            (Job::STATUS_DONE.to_string(), 1)
+        );
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
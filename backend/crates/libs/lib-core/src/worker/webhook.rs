@@ -0,0 +1,346 @@
+//! webhook 投递
+//!
+//! - outbox 的消费者调用 `enqueue_deliveries`，为每个订阅了事件的 webhook 添加一个投递任务。
+//! - 请求体为 `{"id": 事件 id, "type": .., "data": ..}`，签名放在 `SIGNATURE_HEADER` 中。
+//! - 非 2xx 响应、超时和连接错误都按失败处理，由任务队列按指数退避重试。
+//! - 同一个事件可能被投递多次，接收方需要按事件 id 去重。
+//! - 投递前重新检查 url，连接时只使用检查过的地址，创建后 DNS 改为指向内网的地址也不会被访问。
+//!
+
+use super::HandlerResult;
+use crate::config::core_config;
+use crate::ctx::Ctx;
+use crate::model::job::{JobBmc, JobPayload};
+use crate::model::outbox::{DomainEvent, OutboxEvent};
+use crate::model::webhook::{
+    check_url, resolve_public, WebhookBmc, WebhookDeliveryForCreate, WebhookForDelivery,
+};
+use crate::model::{ModelManager, Result};
+use hyper::client::connect::dns::Name;
+use hyper::client::HttpConnector;
+use hyper::service::Service;
+use hyper::{Body, Client, Method, Request};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use lib_auth::webhook::{sign, SIGNATURE_HEADER};
+use lib_utils::time::now_utc;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+pub const EVENT_ID_HEADER: &str = "x-webhook-event-id";
+pub const EVENT_TYPE_HEADER: &str = "x-webhook-event-type";
+
+// 单次投递的超时时间
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 投递一个事件到一个 webhook
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeliverWebhook {
+    pub webhook_id: i64,
+    pub event_id: i64,
+    pub event: DomainEvent,
+}
+
+impl JobPayload for DeliverWebhook {
+    const JOB_TYPE: &'static str = "deliver_webhook";
+    // 按任务队列的退避规则，最后一次重试在约 20 分钟后
+    const MAX_ATTEMPTS: i32 = 8;
+}
+
+#[derive(Serialize)]
+struct WebhookBody<'a> {
+    id: i64,
+    #[serde(flatten)]
+    event: &'a DomainEvent,
+}
+
+/// 为订阅了 event 的 webhook 添加投递任务，返回添加的任务数
+pub async fn enqueue_deliveries(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    event: &OutboxEvent,
+) -> Result<usize> {
+    let webhooks = WebhookBmc::list_for_event(ctx, mm, &event.event).await?;
+
+    for webhook in webhooks.iter() {
+        let payload = DeliverWebhook {
+            webhook_id: webhook.id,
+            event_id: event.id,
+            event: event.event.clone(),
+        };
+        JobBmc::enqueue(ctx, mm, &payload).await?;
+    }
+
+    Ok(webhooks.len())
+}
+
+pub(super) async fn deliver_webhook(mm: ModelManager, payload: DeliverWebhook) -> HandlerResult {
+    let ctx = Ctx::root_ctx();
+
+    // webhook 已被删除时不再投递
+    let Some(webhook) = WebhookBmc::first_for_delivery(&ctx, &mm, payload.webhook_id).await? else {
+        return Ok(());
+    };
+
+    let body = serde_json::to_vec(&WebhookBody {
+        id: payload.event_id,
+        event: &payload.event,
+    })?;
+
+    let start = Instant::now();
+    let res = send(&webhook, &payload, body).await;
+    let duration_ms = start.elapsed().as_millis() as i64;
+
+    let (status_code, error) = match &res {
+        Ok(status) if status.is_success() => (Some(status.as_u16() as i32), None),
+        Ok(status) => (
+            Some(status.as_u16() as i32),
+            Some(format!("status {status}")),
+        ),
+        Err(ex) => (None, Some(ex.to_string())),
+    };
+
+    let attempt = WebhookBmc::record_delivery(
+        &ctx,
+        &mm,
+        WebhookDeliveryForCreate {
+            webhook_id: webhook.id,
+            event_id: payload.event_id,
+            event_type: payload.event.event_type().to_string(),
+            status_code,
+            error: error.clone(),
+            duration_ms,
+        },
+    )
+    .await?;
+
+    info!(
+        "{:<12} - deliver_webhook {} event {} attempt {attempt}: {status_code:?}",
+        "JOB", webhook.id, payload.event_id
+    );
+
+    match error {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+// region:    --- Http
+
+type HttpClient = Client<HttpsConnector<HttpConnector<PublicResolver>>>;
+
+fn http_client() -> &'static HttpClient {
+    static INSTANCE: OnceLock<HttpClient> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| {
+        let mut http = HttpConnector::new_with_resolver(PublicResolver);
+        http.enforce_http(false);
+        let connector = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(http);
+        Client::builder().build(connector)
+    })
+}
+
+/// 只返回公网地址的 DNS 解析，连接时使用的地址和检查的地址相同
+#[derive(Clone)]
+struct PublicResolver;
+
+type ResolveFuture = Pin<Box<dyn Future<Output = Result<std::vec::IntoIter<SocketAddr>>> + Send>>;
+
+impl Service<Name> for PublicResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = crate::model::Error;
+    type Future = ResolveFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        Box::pin(async move {
+            // 端口由 HttpConnector 按 url 设置
+            let addrs =
+                resolve_public(name.as_str(), 0, core_config().WEBHOOK_ALLOW_INSECURE).await?;
+            Ok(addrs.into_iter())
+        })
+    }
+}
+
+async fn send(
+    webhook: &WebhookForDelivery,
+    payload: &DeliverWebhook,
+    body: Vec<u8>,
+) -> core::result::Result<hyper::StatusCode, Box<dyn std::error::Error + Send + Sync>> {
+    // ip 地址的 url 不经过 PublicResolver，在这里检查
+    check_url(&webhook.url).await?;
+
+    let signature = sign(&webhook.secret, now_utc().unix_timestamp(), &body)?;
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(&webhook.url)
+        .header(hyper::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .header(SIGNATURE_HEADER, signature)
+        .header(EVENT_ID_HEADER, payload.event_id)
+        .header(EVENT_TYPE_HEADER, payload.event.event_type())
+        .body(Body::from(body))?;
+
+    let res = tokio::time::timeout(DELIVERY_TIMEOUT, http_client().request(req))
+        .await
+        .map_err(|_| format!("timeout after {}s", DELIVERY_TIMEOUT.as_secs()))??;
+
+    Ok(res.status())
+}
+
+// endregion: --- Http
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_dev_utils::init_test;
+    use crate::model::job::Job;
+    use crate::model::user::{UserBmc, UserForCreate};
+    use crate::model::webhook::WebhookForCreate;
+    use crate::worker::{jobs, Worker};
+    use anyhow::Result;
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+    /// 本地的接收方，第一次请求返回 500，之后返回 200
+    async fn fx_receiver() -> Result<(SocketAddr, Received)> {
+        let received = Received::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State(received): State<Received>, headers: HeaderMap, body: Bytes| async move {
+                        let mut received = received.lock().unwrap();
+                        received.push((headers, body));
+                        if received.len() == 1 {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        } else {
+                            StatusCode::OK
+                        }
+                    },
+                ),
+            )
+            .with_state(received.clone());
+
+        let server = axum::Server::bind(&"127.0.0.1:0".parse()?).serve(app.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        Ok((addr, received))
+    }
+
+    fn fx_outbox_event(id: i64, event: DomainEvent) -> OutboxEvent {
+        OutboxEvent {
+            id,
+            txid: 0,
+            event,
+            ctime: now_utc(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhook_deliver_retry_and_sign() -> Result<()> {
+        // -- Fixtures
+        let test_db = init_test().await;
+        let mm = test_db.mm();
+        let root_ctx = Ctx::root_ctx();
+        let (addr, received) = fx_receiver().await?;
+        let user_id = UserBmc::create::<UserForCreate>(
+            &root_ctx,
+            mm,
+            UserForCreate {
+                username: "demo_webhook".to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+        let ctx = Ctx::new(user_id)?;
+        let created = WebhookBmc::create(
+            &ctx,
+            mm,
+            WebhookForCreate {
+                url: format!("http://{addr}/hook"),
+                event_types: vec!["PasswordChanged".to_string()],
+            },
+        )
+        .await?;
+        let fx_event = fx_outbox_event(1_000_001, DomainEvent::PasswordChanged { user_id });
+        let worker = jobs::register(Worker::new(mm.clone()));
+
+        // -- Exec
+        // 没有订阅的事件不会投递
+        let erased_count = enqueue_deliveries(
+            &root_ctx,
+            mm,
+            &fx_outbox_event(1_000_002, DomainEvent::AccountErased { user_id }),
+        )
+        .await?;
+        let count = enqueue_deliveries(&root_ctx, mm, &fx_event).await?;
+
+        // 第一次投递失败，推迟的任务改为立即到期后再次执行
+        let first_run = worker.run_once().await?;
+        sqlx::query("UPDATE job SET run_at = now() WHERE job_type = $1")
+            .bind(DeliverWebhook::JOB_TYPE)
+            .execute(mm.db())
+            .await?;
+        let second_run = worker.run_once().await?;
+
+        // -- Check
+        assert_eq!((erased_count, count), (0, 1));
+        assert_eq!((first_run, second_run), (1, 1));
+        let (status,): (String,) = sqlx::query_as("SELECT status FROM job WHERE job_type = $1")
+            .bind(DeliverWebhook::JOB_TYPE)
+            .fetch_one(mm.db())
+            .await?;
+        assert_eq!(status, Job::STATUS_DONE);
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        let (headers, body) = &received[1];
+        let signature = headers[SIGNATURE_HEADER].to_str()?;
+        lib_auth::webhook::verify(&created.secret, signature, body)?;
+        assert!(lib_auth::webhook::verify("whsec_other", signature, body).is_err());
+        assert_eq!(headers[EVENT_ID_HEADER], "1000001");
+        assert_eq!(headers[EVENT_TYPE_HEADER], "PasswordChanged");
+        let body: serde_json::Value = serde_json::from_slice(body)?;
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "id": 1_000_001,
+                "type": "PasswordChanged",
+                "data": { "user_id": user_id }
+            })
+        );
+
+        // 投递记录新的在前
+        let deliveries = WebhookBmc::list_deliveries(&ctx, mm, created.id).await?;
+        let attempts: Vec<_> = deliveries
+            .iter()
+            .map(|d| (d.attempt, d.status_code, d.error.is_some()))
+            .collect();
+        assert_eq!(attempts, vec![(2, Some(200), false), (1, Some(500), true)]);
+
+        Ok(())
+    }
+}
+// endregion: --- Tests
@@ -11,6 +11,18 @@ pub fn get_env_parse<T: FromStr>(name: &'static str) -> Result<T> {
     val.parse::<T>().map_err(|_| Error::WrongFormat(name))
 }
 
+pub fn get_env_opt(name: &'static str) -> Option<String> {
+    env::var(name).ok()
+}
+
+/// 环境变量不存在时返回默认值，存在但格式错误时仍然报错
+pub fn get_env_parse_or<T: FromStr>(name: &'static str, default: T) -> Result<T> {
+    match get_env_opt(name) {
+        Some(val) => val.parse::<T>().map_err(|_| Error::WrongFormat(name)),
+        None => Ok(default),
+    }
+}
+
 pub fn get_env_b64u_as_u8s(name: &'static str) -> Result<Vec<u8>> {
     b64u_decode(&get_env(name)?).map_err(|_| Error::WrongFormat(name))
 }
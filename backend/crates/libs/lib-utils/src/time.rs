@@ -10,9 +10,12 @@ pub fn format_time(time: OffsetDateTime) -> String {
     time.format(&Rfc3339).unwrap() // TODO: need to check if safe.
 }
 
+pub fn now_utc_plus_sec(sec: f64) -> OffsetDateTime {
+    now_utc() + Duration::seconds_f64(sec)
+}
+
 pub fn now_utc_plus_sec_str(sec: f64) -> String {
-    let new_time = now_utc() + Duration::seconds_f64(sec);
-    format_time(new_time)
+    format_time(now_utc_plus_sec(sec))
 }
 
 pub fn parse_utc(moment: &str) -> Result<OffsetDateTime> {
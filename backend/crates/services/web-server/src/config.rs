@@ -1,5 +1,7 @@
-use lib_utils::envs::get_env;
+use lib_utils::envs::{get_env, get_env_opt, get_env_parse_or};
+use std::collections::HashMap;
 use std::sync::OnceLock;
+use tower_cookies::cookie::SameSite;
 
 pub fn web_config() -> &'static WebConfig {
     static INSTANCE: OnceLock<WebConfig> = OnceLock::new();
@@ -16,6 +18,21 @@ pub struct WebConfig {
     pub WEB_FOLDER: String,
     // Web 的静态 html 文件
     pub WEB_FILE: String,
+
+    // 启动时是否删库并重新初始化开发数据，生产环境不能开启
+    pub DEV_INIT: bool,
+
+    // 是否在 web-server 中运行后台任务 worker，关闭时需要单独运行 worker 服务
+    pub WORKER_ENABLED: bool,
+
+    // 覆盖代码中定时任务的 cron 表达式，`off` 表示关闭
+    pub SCHEDULES: HashMap<String, String>,
+
+    // -- Cookie
+    // 默认值面向生产环境，本地开发在 .cargo/config.toml 中放宽
+    pub COOKIE_SECURE: bool,
+    pub COOKIE_SAME_SITE: SameSite,
+    pub COOKIE_DOMAIN: Option<String>,
 }
 
 impl WebConfig {
@@ -23,6 +40,51 @@ impl WebConfig {
         Ok(WebConfig {
             WEB_FOLDER: get_env("SERVICE_WEB_FOLDER")?,
             WEB_FILE: get_env("SERVICE_WEB_FILE")?,
+
+            DEV_INIT: get_env_parse_or("SERVICE_DEV_INIT", false)?,
+
+            WORKER_ENABLED: get_env_parse_or("SERVICE_WORKER_ENABLED", false)?,
+
+            SCHEDULES: get_env_schedules("SERVICE_SCHEDULES")?,
+
+            // -- Cookie
+            COOKIE_SECURE: get_env_parse_or("SERVICE_COOKIE_SECURE", true)?,
+            COOKIE_SAME_SITE: get_env_same_site_or("SERVICE_COOKIE_SAME_SITE", SameSite::Lax)?,
+            COOKIE_DOMAIN: get_env_opt("SERVICE_COOKIE_DOMAIN"),
         })
     }
 }
+
+/// 格式为 `name=cron;name=cron`，例如 `erase_due_accounts=0 * * * *;purge_deleted_projects=off`
+fn get_env_schedules(name: &'static str) -> lib_utils::envs::Result<HashMap<String, String>> {
+    let Some(val) = get_env_opt(name) else {
+        return Ok(HashMap::new());
+    };
+
+    val.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (task, cron) = entry
+                .split_once('=')
+                .ok_or(lib_utils::envs::Error::WrongFormat(name))?;
+            Ok((task.trim().to_string(), cron.trim().to_string()))
+        })
+        .collect()
+}
+
+fn get_env_same_site_or(
+    name: &'static str,
+    default: SameSite,
+) -> lib_utils::envs::Result<SameSite> {
+    let Some(val) = get_env_opt(name) else {
+        return Ok(default);
+    };
+
+    match val.as_str() {
+        "Strict" => Ok(SameSite::Strict),
+        "Lax" => Ok(SameSite::Lax),
+        "None" => Ok(SameSite::None),
+        _ => Err(lib_utils::envs::Error::WrongFormat(name)),
+    }
+}
@@ -0,0 +1,216 @@
+//! outbox 事件分发
+//!
+//! - 按顺序读取 outbox 中 cursor 之后的事件，依次交给所有订阅者处理，一个事件的所有订阅者都成功后才保存 cursor。
+//! - 订阅者失败时停止本轮分发，下一轮从该事件重新开始，之前已成功的订阅者会再次收到这个事件，
+//!   订阅者需要是幂等的（at-least-once）。
+//! - cursor 按 Dispatcher 的名字保存，重启后从上次的位置继续。
+//! - 多个实例使用同一个名字时，每轮分发前获取 cursor 的锁，拿不到锁的实例跳过这一轮。
+//!
+
+use crate::{Error, Result};
+use lib_core::ctx::Ctx;
+use lib_core::model::outbox::{OutboxBmc, OutboxEvent};
+use lib_core::model::ModelManager;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, error};
+
+// 没有新事件时的轮询间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// 每次读取的事件数
+const BATCH_SIZE: i64 = 100;
+
+pub type SubscriberResult = core::result::Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+type SubscriberFuture = Pin<Box<dyn Future<Output = SubscriberResult> + Send>>;
+type SubscriberFn = Arc<dyn Fn(OutboxEvent) -> SubscriberFuture + Send + Sync>;
+
+pub struct Dispatcher {
+    mm: ModelManager,
+    name: &'static str,
+    subscribers: Vec<(&'static str, SubscriberFn)>,
+}
+
+impl Dispatcher {
+    pub fn new(mm: ModelManager, name: &'static str) -> Self {
+        Self {
+            mm,
+            name,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// 添加订阅者，所有事件都会交给订阅者，由订阅者自己忽略不关心的事件
+    pub fn subscribe<F, Fut>(mut self, name: &'static str, subscriber: F) -> Self
+    where
+        F: Fn(OutboxEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = SubscriberResult> + Send + 'static,
+    {
+        let subscriber: SubscriberFn = Arc::new(move |event| Box::pin(subscriber(event)));
+        self.subscribers.push((name, subscriber));
+        self
+    }
+
+    /// 分发一批事件，返回处理完成的事件数
+    pub async fn dispatch_once(&self) -> Result<usize> {
+        let ctx = Ctx::root_ctx();
+
+        // 其他实例正在分发时跳过
+        let Some(lock) = OutboxBmc::try_lock_cursor(&ctx, &self.mm, self.name).await? else {
+            return Ok(0);
+        };
+
+        let res = self.dispatch_batch(&ctx).await;
+        lock.unlock().await?;
+
+        res
+    }
+
+    async fn dispatch_batch(&self, ctx: &Ctx) -> Result<usize> {
+        let mm = &self.mm;
+
+        let cursor = OutboxBmc::get_cursor(ctx, mm, self.name).await?;
+        let events = OutboxBmc::list_after(ctx, mm, cursor, BATCH_SIZE).await?;
+        let count = events.len();
+
+        for event in events {
+            let event_id = event.id;
+            let event_cursor = event.cursor();
+
+            for (subscriber_name, subscriber) in &self.subscribers {
+                subscriber(event.clone())
+                    .await
+                    .map_err(|ex| Error::SubscriberFailed {
+                        subscriber: subscriber_name,
+                        event_id,
+                        cause: ex.to_string(),
+                    })?;
+            }
+
+            // 每个事件处理完就保存，失败时不会重复分发之前的事件
+            OutboxBmc::save_cursor(ctx, mm, self.name, event_cursor).await?;
+        }
+
+        if count > 0 {
+            debug!("{:<12} - {} - dispatched {count}", "DISPATCHER", self.name);
+        }
+
+        Ok(count)
+    }
+
+    /// 在后台持续分发，读满一批时立即读取下一批
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match self.dispatch_once().await {
+                    Ok(count) if count as i64 == BATCH_SIZE => continue,
+                    Ok(_) => {}
+                    Err(ex) => error!("{:<12} - {} - {ex:?}", "DISPATCHER", self.name),
+                }
+
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        })
+    }
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use lib_core::_dev_utils::init_test;
+    use lib_core::model::outbox::DomainEvent;
+    use lib_core::model::user::{UserBmc, UserForCreate};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_dispatcher_redeliver() -> Result<()> {
+        let test_db = init_test().await;
+        let mm = test_db.mm().clone();
+        let root_ctx = Ctx::root_ctx();
+        let user_id = UserBmc::create::<UserForCreate>(
+            &root_ctx,
+            &mm,
+            UserForCreate {
+                username: "demo_dispatch".to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+
+        // record 记录收到的事件，flaky 第一次处理时失败
+        let received = Arc::new(Mutex::new(Vec::<(i64, DomainEvent)>::new()));
+        let failed = Arc::new(AtomicBool::new(false));
+        let dispatcher = {
+            let received = received.clone();
+            let failed = failed.clone();
+            Dispatcher::new(mm.clone(), "test_dispatch")
+                .subscribe("record", move |event| {
+                    received.lock().unwrap().push((event.id, event.event));
+                    async { Ok(()) }
+                })
+                .subscribe("flaky", move |_event| {
+                    let first = !failed.swap(true, Ordering::SeqCst);
+                    async move {
+                        if first {
+                            Err("flaky subscriber".into())
+                        } else {
+                            Ok(())
+                        }
+                    }
+                })
+        };
+        let fx_event = DomainEvent::UserCreated {
+            user_id,
+            username: "demo_dispatch".to_string(),
+        };
+        let has_fx_event = || received.lock().unwrap().iter().any(|(_, e)| *e == fx_event);
+
+        // 执行
+        // 其他实例持有 cursor 的锁时跳过这一轮
+        let lock = OutboxBmc::try_lock_cursor(&root_ctx, &mm, "test_dispatch")
+            .await?
+            .expect("Should lock the cursor");
+        let while_locked = dispatcher.dispatch_once().await?;
+        let received_while_locked = received.lock().unwrap().len();
+        lock.unlock().await?;
+
+        // 其他测试中进行中的事务会推迟事件可见的时间，最多等待 5 秒
+        let mut results = Vec::new();
+        for _ in 0..50 {
+            results.push(dispatcher.dispatch_once().await);
+            if has_fx_event() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        let cursor = OutboxBmc::get_cursor(&root_ctx, &mm, "test_dispatch").await?;
+        let again = dispatcher.dispatch_once().await?;
+
+        // 检查
+        let received = received.lock().unwrap().clone();
+        assert_eq!((while_locked, received_while_locked), (0, 0));
+        assert!(has_fx_event());
+        assert!(matches!(
+            results.iter().find(|res| res.is_err()),
+            Some(Err(Error::SubscriberFailed {
+                subscriber: "flaky",
+                ..
+            }))
+        ));
+        // 失败的事件会被重新分发给所有订阅者
+        assert_eq!(received[0].0, received[1].0);
+        assert_eq!(cursor.event_id, received.last().unwrap().0);
+        assert_eq!(again, 0);
+
+        Ok(())
+    }
+}
+
+// endregion: --- Tests
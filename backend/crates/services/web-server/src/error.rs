@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
-use lib_core::model;
+use crate::scheduler;
+use lib_core::{migration, model};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -10,8 +11,18 @@ pub enum Error {
     ConfigMissEnv(&'static str),
     ConfigWrongFormat(&'static str),
 
+    // -- Dispatcher
+    // 订阅者处理事件失败，下一轮从该事件重新分发
+    SubscriberFailed {
+        subscriber: &'static str,
+        event_id: i64,
+        cause: String,
+    },
+
     // -- Modules
     Model(model::Error),
+    Migration(migration::Error),
+    Scheduler(scheduler::Error),
 }
 
 // region:    --- Froms
@@ -21,6 +32,18 @@ impl From<model::Error> for Error {
     }
 }
 
+impl From<migration::Error> for Error {
+    fn from(value: migration::Error) -> Self {
+        Self::Migration(value)
+    }
+}
+
+impl From<scheduler::Error> for Error {
+    fn from(value: scheduler::Error) -> Self {
+        Self::Scheduler(value)
+    }
+}
+
 // endregion: --- Froms
 
 impl Display for Error {
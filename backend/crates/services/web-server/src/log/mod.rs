@@ -34,7 +34,12 @@ pub async fn log_request(
         http_path: uri.to_string(),
         http_method: req_method.to_string(),
 
-        user_id: ctx.map(|c| c.user_id()),
+        user_id: ctx.as_ref().map(|c| c.user_id()),
+        real_user_id: ctx
+            .as_ref()
+            .filter(|c| c.is_impersonated())
+            .map(|c| c.real_user_id()),
+        impersonated: ctx.as_ref().is_some_and(|c| c.is_impersonated()),
 
         client_error_type: client_error.map(|e| e.as_ref().to_string()),
 
@@ -57,6 +62,9 @@ struct RequestLogLine {
 
     // -- User and context attributes.
     user_id: Option<i64>,
+    // 模拟登录时为实际发起请求的管理员
+    real_user_id: Option<i64>,
+    impersonated: bool,
 
     // -- http request attributes.
     http_path: String,
@@ -2,19 +2,32 @@
 // region:    --- Modules
 
 mod config;
+mod dispatcher;
 mod error;
 mod log;
+mod scheduler;
 mod web;
 
 pub use self::error::{Error, Result};
 use config::web_config;
+use dispatcher::Dispatcher;
+use scheduler::Scheduler;
 
 use crate::web::mw_auth::{mw_ctx_require, mw_ctx_resolve};
+use crate::web::mw_csrf::mw_csrf_check;
 use web::mw_res_map::mw_response_map;
 
-use crate::web::{routes_login, routes_static};
+use crate::web::{
+    routes_api_key, routes_impersonation, routes_login, routes_org, routes_role, routes_static,
+    routes_user, routes_webhook,
+};
 use lib_core::_dev_utils;
+use lib_core::ctx::Ctx;
+use lib_core::migration;
+use lib_core::model::job::JobBmc;
 use lib_core::model::ModelManager;
+use lib_core::worker::jobs::{self, EraseDueAccounts, PurgeDeletedProjects};
+use lib_core::worker::{webhook, Worker};
 use std::net::SocketAddr;
 use tower_cookies::CookieManagerLayer;
 use tower_http::cors::{Any, CorsLayer};
@@ -33,11 +46,54 @@ async fn main() -> Result<()> {
         .with_env_filter(EnvFilter::from_default_env())
         .init();
 
-    _dev_utils::init_dev().await;
+    if web_config().DEV_INIT {
+        _dev_utils::init_dev().await;
+    }
 
     // Initialize ModelManager.
     let mm = ModelManager::new().await?;
 
+    // 执行未执行的 migration，已执行的文件被修改时拒绝启动
+    migration::migrate(&mm).await?;
+
+    // 将 outbox 中的领域事件分发给进程内的订阅者
+    Dispatcher::new(mm.clone(), "web-server")
+        .subscribe("log", |event| async move {
+            info!("{:<12} - {}: {:?}", "EVENT", event.id, event.event);
+            Ok(())
+        })
+        // 为订阅了事件的 webhook 添加投递任务
+        .subscribe("webhooks", {
+            let mm = mm.clone();
+            move |event| {
+                let mm = mm.clone();
+                async move {
+                    webhook::enqueue_deliveries(&Ctx::root_ctx(), &mm, &event).await?;
+                    Ok(())
+                }
+            }
+        })
+        .spawn();
+
+    if web_config().WORKER_ENABLED {
+        jobs::register(Worker::new(mm.clone())).spawn();
+    }
+
+    // 定时任务只添加后台任务，由 worker 执行
+    Scheduler::new(mm.clone(), web_config().SCHEDULES.clone())
+        .task("erase_due_accounts", "*/15 * * * *", |mm| async move {
+            JobBmc::enqueue(&Ctx::root_ctx(), &mm, &EraseDueAccounts).await?;
+            Ok(())
+        })?
+        .task("purge_deleted_projects", "0 4 * * *", |mm| async move {
+            let payload = PurgeDeletedProjects {
+                older_than_days: 30,
+            };
+            JobBmc::enqueue(&Ctx::root_ctx(), &mm, &payload).await?;
+            Ok(())
+        })?
+        .spawn();
+
     let routes_hello = Router::new()
         .route("/hello", get(|| async { Html("Hello World") }))
         .route_layer(middleware::from_fn(mw_ctx_require));
@@ -45,7 +101,15 @@ async fn main() -> Result<()> {
     // 洋葱模型，写在后面的函数越早执行
     let routes_all = Router::new()
         .merge(routes_login::routes(mm.clone()))
+        .merge(routes_api_key::routes(mm.clone()))
+        .merge(routes_role::routes(mm.clone()))
+        .merge(routes_org::routes(mm.clone()))
+        .merge(routes_impersonation::routes(mm.clone()))
+        .merge(routes_user::routes(mm.clone()))
+        .merge(routes_webhook::routes(mm.clone()))
         .merge(routes_hello)
+        // 需要在 mw_response_map 内层，CSRF 校验失败的错误才能被映射
+        .layer(middleware::from_fn(mw_csrf_check))
         // 这个中间件主要是用于处理返回到客户端中的 res body
         .layer(middleware::map_response(mw_response_map))
         .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
@@ -56,7 +120,12 @@ async fn main() -> Result<()> {
         .layer(
             // 解决跨域问题
             CorsLayer::new()
-                .allow_methods([axum::http::Method::GET, axum::http::Method::POST])
+                .allow_methods([
+                    axum::http::Method::GET,
+                    axum::http::Method::POST,
+                    axum::http::Method::PATCH,
+                    axum::http::Method::DELETE,
+                ])
                 .allow_origin(Any)
                 .allow_headers(Any),
         );
@@ -89,7 +158,14 @@ mod tests {
     };
     use lib_auth::pwd::{self, ContentToHash};
     use lib_core::{
-        ctx::Ctx,
+        ctx::{self, Ctx, Permission},
+        model,
+        model::api_key::{ApiKeyBmc, ApiKeyForCreate},
+        model::impersonation::{ImpersonationBmc, ImpersonationForCreate},
+        model::invitation::InvitationBmc,
+        model::organization::{OrganizationBmc, OrganizationForCreate},
+        model::outbox::{DomainEvent, OutboxBmc},
+        model::project::{ProjectBmc, ProjectForCreate},
         model::user::{User, UserBmc, UserForCreate, UserForLogin},
     };
     use serde::Deserialize;
@@ -117,16 +193,808 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_register_api() -> Result<()> {
-        let mm = _dev_utils::init_test().await;
-        test_register_interface(mm.clone()).await;
-        test_create_user_ok_demo12(mm.clone()).await;
-        test_first_by_username_ok_demo1(mm.clone()).await;
+    async fn test_api_key_auth() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        let root_ctx = Ctx::root_ctx();
+        let fx_username = "demo_api_key";
+
+        // 先创建账号，再以该账号创建 api key
+        let user_id = UserBmc::create::<UserForCreate>(
+            &root_ctx,
+            &mm,
+            UserForCreate {
+                username: fx_username.to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+        let ctx = Ctx::new(user_id)?;
+        let created = ApiKeyBmc::create(
+            &ctx,
+            &mm,
+            ApiKeyForCreate {
+                name: "batch".to_string(),
+                scopes: vec!["read".to_string()],
+                expires_in_sec: None,
+            },
+        )
+        .await?;
+
+        let route = Router::new()
+            .merge(web::routes_api_key::routes(mm.clone()))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+
+        let list_request = |api_key: &str| {
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/api/api_keys")
+                .header(web::API_KEY_HEADER, api_key)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // 执行
+        let ok_response = route.clone().oneshot(list_request(&created.key)).await?;
+        let ok_body = hyper::body::to_bytes(ok_response.into_body()).await?;
+        let ok_body: ResponseBody<Vec<serde_json::Value>> = serde_json::from_slice(&ok_body)?;
+
+        let wrong_key = format!("{}x", created.key);
+        let err_response = route.clone().oneshot(list_request(&wrong_key)).await?;
+
+        // 吊销后再次请求
+        ApiKeyBmc::revoke(&ctx, &mm, created.id).await?;
+        let revoked_response = route.clone().oneshot(list_request(&created.key)).await?;
+
+        // 检查
+        assert_eq!(ok_body.data.len(), 1);
+        assert_eq!(ok_body.data[0]["id"], created.id);
+        assert_eq!(ok_body.data[0]["scopes"], json!(["read"]));
+        assert_eq!(
+            err_response.status(),
+            http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            revoked_response.status(),
+            http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_key_scopes() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        let root_ctx = Ctx::root_ctx();
+        let user_id = UserBmc::create::<UserForCreate>(
+            &root_ctx,
+            &mm,
+            UserForCreate {
+                username: "demo_api_key_scopes".to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+        let ctx = Ctx::new(user_id)?;
+        let fx_key = |scopes: &[&str]| ApiKeyForCreate {
+            name: "scoped".to_string(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+            expires_in_sec: None,
+        };
+        let read_key = ApiKeyBmc::create(&ctx, &mm, fx_key(&["read"])).await?;
+        let write_key = ApiKeyBmc::create(&ctx, &mm, fx_key(&["write"])).await?;
+        let fx_permissions = vec![Permission::UserRead, Permission::UserWrite];
+        let read_ctx = Ctx::new_api_key(user_id, read_key.id, vec!["read".to_string()])?
+            .with_roles(Vec::new(), fx_permissions.clone());
+        let write_ctx = Ctx::new_api_key(user_id, write_key.id, vec!["write".to_string()])?
+            .with_roles(Vec::new(), fx_permissions);
+
+        let route = Router::new()
+            .merge(web::routes_api_key::routes(mm.clone()))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+        let request = |method: http::Method, uri: String| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(web::API_KEY_HEADER, &read_key.key)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // 执行
+        let list_response = route
+            .clone()
+            .oneshot(request(http::Method::GET, "/api/api_keys".to_string()))
+            .await?;
+        let revoke_response = route
+            .clone()
+            .oneshot(request(
+                http::Method::POST,
+                format!("/api/api_keys/{}/revoke", write_key.id),
+            ))
+            .await?;
+        let create_response = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/api/api_keys")
+                    .header(web::API_KEY_HEADER, &write_key.key)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(json!({ "name": "minted" }).to_string()))
+                    .unwrap(),
+            )
+            .await?;
+        let read_pwd_res = UserBmc::update_pwd(&read_ctx, &mm, user_id, "new_pwd").await;
+        let write_pwd_res = UserBmc::update_pwd(&write_ctx, &mm, user_id, "new_pwd").await;
+
+        // 检查
+        assert_eq!(list_response.status(), http::StatusCode::OK);
+        assert_eq!(revoke_response.status(), http::StatusCode::FORBIDDEN);
+        // 即使有 write scope 也不能用 api key 创建新的 key
+        assert_eq!(create_response.status(), http::StatusCode::FORBIDDEN);
+        assert_eq!(ApiKeyBmc::list(&ctx, &mm).await?.len(), 2);
+        assert!(matches!(
+            read_pwd_res,
+            Err(model::Error::Ctx(ctx::Error::ApiKeyScopeRequired {
+                scope: "write"
+            }))
+        ));
+        write_pwd_res?;
+        // 角色中的权限同样受 scope 限制，write 包含 read
+        assert!(read_ctx.has_permission(Permission::UserRead));
+        assert!(!read_ctx.has_permission(Permission::UserWrite));
+        assert!(write_ctx.require(Permission::UserRead).is_ok());
+        assert!(write_ctx.require(Permission::UserWrite).is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_key_create_validation() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        let root_ctx = Ctx::root_ctx();
+        let fx_username = "demo_api_key_create_scopes";
+        let user_id = UserBmc::create::<UserForCreate>(
+            &root_ctx,
+            &mm,
+            UserForCreate {
+                username: fx_username.to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+        let user: UserForLogin = UserBmc::get(&root_ctx, &mm, user_id).await?;
+        let token = lib_auth::token::generate_web_token(fx_username, user.token_salt)?;
+        let bearer = format!("{}{}", web::BEARER_PREFIX, token);
+
+        let route = Router::new()
+            .merge(web::routes_api_key::routes(mm.clone()))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+        let create_request = |body: serde_json::Value| {
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/api/api_keys")
+                .header(http::header::AUTHORIZATION, &bearer)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        // 执行
+        let default_response = route
+            .clone()
+            .oneshot(create_request(json!({ "name": "default" })))
+            .await?;
+        let unknown_response = route
+            .clone()
+            .oneshot(create_request(json!({ "name": "x", "scopes": ["admin"] })))
+            .await?;
+        let empty_response = route
+            .clone()
+            .oneshot(create_request(json!({ "name": "x", "scopes": [] })))
+            .await?;
+        let fx_expires = [1e300, 0.0, -1.0];
+        let mut expires_statuses = Vec::new();
+        for expires_in_sec in fx_expires {
+            let response = route
+                .clone()
+                .oneshot(create_request(
+                    json!({ "name": "x", "expires_in_sec": expires_in_sec }),
+                ))
+                .await?;
+            expires_statuses.push(response.status());
+        }
+
+        // 检查
+        // 不传 scopes 时只有 read，不合法的 scope 和有效期返回 400
+        assert_eq!(default_response.status(), http::StatusCode::OK);
+        assert_eq!(unknown_response.status(), http::StatusCode::BAD_REQUEST);
+        assert_eq!(empty_response.status(), http::StatusCode::BAD_REQUEST);
+        assert!(expires_statuses
+            .iter()
+            .all(|status| *status == http::StatusCode::BAD_REQUEST));
+        let api_keys = ApiKeyBmc::list(&Ctx::new(user_id)?, &mm).await?;
+        assert_eq!(api_keys.len(), 1);
+        assert_eq!(api_keys[0].scope_list(), vec!["read".to_string()]);
+        // 没有 scope 的 api key 不能执行任何操作
+        let empty_ctx = Ctx::new_api_key(user_id, api_keys[0].id, Vec::new())?;
+        assert!(!empty_ctx.has_scope("read"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_webhook_routes() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        let root_ctx = Ctx::root_ctx();
+        let user_id = UserBmc::create::<UserForCreate>(
+            &root_ctx,
+            &mm,
+            UserForCreate {
+                username: "demo_webhook_routes".to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+        let created = ApiKeyBmc::create(
+            &Ctx::new(user_id)?,
+            &mm,
+            ApiKeyForCreate {
+                name: "webhooks".to_string(),
+                scopes: vec!["write".to_string()],
+                expires_in_sec: None,
+            },
+        )
+        .await?;
+
+        let route = Router::new()
+            .merge(web::routes_webhook::routes(mm.clone()))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+        let request = |method: http::Method, uri: &str, body: Option<serde_json::Value>| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(web::API_KEY_HEADER, &created.key)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap()
+        };
+        let to_json = |body: hyper::body::Bytes| serde_json::from_slice::<serde_json::Value>(&body);
+
+        // 执行
+        let bad_response = route
+            .clone()
+            .oneshot(request(
+                http::Method::POST,
+                "/api/webhooks",
+                Some(json!({ "url": "ftp://example.com" })),
+            ))
+            .await?;
+        let create_response = route
+            .clone()
+            .oneshot(request(
+                http::Method::POST,
+                "/api/webhooks",
+                Some(json!({
+                    "url": "https://example.com/hook",
+                    "event_types": ["PasswordChanged"]
+                })),
+            ))
+            .await?;
+        let create_body = to_json(hyper::body::to_bytes(create_response.into_body()).await?)?;
+        let webhook_id = create_body["data"]["id"]
+            .as_i64()
+            .context("no webhook id")?;
+
+        let list_response = route
+            .clone()
+            .oneshot(request(http::Method::GET, "/api/webhooks", None))
+            .await?;
+        let list_body = to_json(hyper::body::to_bytes(list_response.into_body()).await?)?;
+        let deliveries_response = route
+            .clone()
+            .oneshot(request(
+                http::Method::GET,
+                &format!("/api/webhooks/{webhook_id}/deliveries"),
+                None,
+            ))
+            .await?;
+        let deliveries_body =
+            to_json(hyper::body::to_bytes(deliveries_response.into_body()).await?)?;
+        let delete_response = route
+            .clone()
+            .oneshot(request(
+                http::Method::DELETE,
+                &format!("/api/webhooks/{webhook_id}"),
+                None,
+            ))
+            .await?;
+        let after_delete_response = route
+            .clone()
+            .oneshot(request(http::Method::GET, "/api/webhooks", None))
+            .await?;
+        let after_delete_body =
+            to_json(hyper::body::to_bytes(after_delete_response.into_body()).await?)?;
+
+        // 检查
+        assert_eq!(bad_response.status(), http::StatusCode::BAD_REQUEST);
+        assert!(create_body["data"]["secret"]
+            .as_str()
+            .is_some_and(|secret| secret.starts_with("whsec_")));
+        // 列表中不包含 secret
+        assert_eq!(list_body["data"][0]["id"], webhook_id);
+        assert_eq!(
+            list_body["data"][0]["event_types"],
+            json!(["PasswordChanged"])
+        );
+        assert!(list_body["data"][0].get("secret").is_none());
+        assert_eq!(deliveries_body["data"], json!([]));
+        assert_eq!(delete_response.status(), http::StatusCode::OK);
+        assert_eq!(after_delete_body["data"], json!([]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_csrf_check() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        let route = Router::new()
+            .merge(routes(mm.clone()))
+            .layer(middleware::from_fn(mw_csrf_check))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+
+        // 先登录 demo1，拿到 auth-token 和 csrf-token 两个 cookie
+        let login_response = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/api/login")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        json!({ "username": "demo1", "pwd": "welcome" }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await?;
+
+        // auth-token cookie 需要带上 web config 中的安全属性，有效期与 token 一致
+        let auth_cookie = login_response
+            .headers()
+            .get_all(http::header::SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find(|v| v.starts_with(web::AUTH_TOKEN))
+            .context("Should have auth-token cookie")?;
+        assert!(auth_cookie.contains("HttpOnly"));
+        assert!(auth_cookie.contains("SameSite=Lax"));
+        assert!(auth_cookie.contains("Max-Age=1799") || auth_cookie.contains("Max-Age=1800"));
+
+        let cookies: Vec<String> = login_response
+            .headers()
+            .get_all(http::header::SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok()?.split(';').next().map(String::from))
+            .collect();
+        let csrf_token = cookies
+            .iter()
+            .find_map(|c| c.strip_prefix(&format!("{}=", web::CSRF_TOKEN)))
+            .context("Should have csrf-token cookie")?
+            .to_string();
+        let cookie_header = cookies.join("; ");
+
+        let logoff_request = |csrf_header: Option<&str>| {
+            let mut builder = Request::builder()
+                .method(http::Method::POST)
+                .uri("/api/logoff")
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .header(http::header::COOKIE, &cookie_header);
+            if let Some(csrf_header) = csrf_header {
+                builder = builder.header(web::CSRF_HEADER, csrf_header);
+            }
+            builder
+                .body(Body::from(json!({ "logoff": true }).to_string()))
+                .unwrap()
+        };
+
+        // 执行
+        let missing_response = route.clone().oneshot(logoff_request(None)).await?;
+        let wrong_response = route
+            .clone()
+            .oneshot(logoff_request(Some("wrong-token")))
+            .await?;
+        let ok_response = route
+            .clone()
+            .oneshot(logoff_request(Some(&csrf_token)))
+            .await?;
+
+        // 检查
+        assert_eq!(missing_response.status(), http::StatusCode::FORBIDDEN);
+        assert_eq!(wrong_response.status(), http::StatusCode::FORBIDDEN);
+        assert_eq!(ok_response.status(), http::StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_role_permission() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        let root_ctx = Ctx::root_ctx();
+
+        // 初始化：demo1 在 seed 中是 admin，另外创建一个没有角色的账号
+        let admin_id = UserBmc::first_by_username::<User>(&root_ctx, &mm, "demo1")
+            .await?
+            .context("Should have user 'demo1'")?
+            .id;
+        let user_id = UserBmc::create::<UserForCreate>(
+            &root_ctx,
+            &mm,
+            UserForCreate {
+                username: "demo_no_role".to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+
+        let mut keys = Vec::new();
+        for id in [admin_id, user_id] {
+            let created = ApiKeyBmc::create(
+                &Ctx::new(id)?,
+                &mm,
+                ApiKeyForCreate {
+                    name: "role".to_string(),
+                    scopes: vec!["role:manage".to_string()],
+                    expires_in_sec: None,
+                },
+            )
+            .await?;
+            keys.push(created.key);
+        }
+
+        let route = Router::new()
+            .merge(web::routes_role::routes(mm.clone()))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+
+        let list_request = |api_key: &str| {
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/api/roles")
+                .header(web::API_KEY_HEADER, api_key)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // 执行
+        let admin_response = route.clone().oneshot(list_request(&keys[0])).await?;
+        let user_response = route.clone().oneshot(list_request(&keys[1])).await?;
+
+        // 检查
+        assert_eq!(admin_response.status(), http::StatusCode::OK);
+        assert_eq!(user_response.status(), http::StatusCode::FORBIDDEN);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_owner_scope() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        let root_ctx = Ctx::root_ctx();
+
+        // 初始化：两个普通用户，api key 属于 owner
+        let mut user_ids = Vec::new();
+        for username in ["demo_owner", "demo_other"] {
+            let user_id = UserBmc::create::<UserForCreate>(
+                &root_ctx,
+                &mm,
+                UserForCreate {
+                    username: username.to_string(),
+                    pwd: "welcome".to_string(),
+                },
+            )
+            .await?;
+            user_ids.push(user_id);
+        }
+        let owner_ctx = Ctx::new(user_ids[0])?;
+        let other_ctx = Ctx::new(user_ids[1])?;
+        let override_ctx =
+            Ctx::new(user_ids[1])?.with_roles(Vec::new(), vec![Permission::OwnerOverride]);
+        let created = ApiKeyBmc::create(
+            &owner_ctx,
+            &mm,
+            ApiKeyForCreate {
+                name: "owned".to_string(),
+                scopes: vec!["read".to_string()],
+                expires_in_sec: None,
+            },
+        )
+        .await?;
+
+        // 执行
+        let owner_res = ApiKeyBmc::get(&owner_ctx, &mm, created.id).await;
+        let other_res = ApiKeyBmc::get(&other_ctx, &mm, created.id).await;
+        let override_res = ApiKeyBmc::get(&override_ctx, &mm, created.id).await;
+        let root_res = ApiKeyBmc::get(&root_ctx, &mm, created.id).await;
+
+        // 检查：跨用户访问和不存在的数据一样返回 EntityNotFound
+        assert_eq!(owner_res?.id, created.id);
+        assert!(matches!(
+            other_res,
+            Err(model::Error::EntityNotFound { entity: "api_key", id }) if id == created.id
+        ));
+        assert_eq!(override_res?.id, created.id);
+        assert_eq!(root_res?.id, created.id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_org_tenancy() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        let root_ctx = Ctx::root_ctx();
+
+        // 初始化：admin 创建两个组织，并邀请 guest 加入其中一个
+        let mut user_ids = Vec::new();
+        for username in ["demo_org_admin", "demo_org_guest"] {
+            let user_id = UserBmc::create::<UserForCreate>(
+                &root_ctx,
+                &mm,
+                UserForCreate {
+                    username: username.to_string(),
+                    pwd: "welcome".to_string(),
+                },
+            )
+            .await?;
+            user_ids.push(user_id);
+        }
+        let admin_ctx = Ctx::new(user_ids[0])?;
+        let guest_ctx = Ctx::new(user_ids[1])?;
+
+        let org_a = OrganizationBmc::create(
+            &admin_ctx,
+            &mm,
+            OrganizationForCreate {
+                name: "org a".to_string(),
+            },
+        )
+        .await?;
+        let org_b = OrganizationBmc::create(
+            &admin_ctx,
+            &mm,
+            OrganizationForCreate {
+                name: "org b".to_string(),
+            },
+        )
+        .await?;
+
+        let project_id = ProjectBmc::create(
+            &admin_ctx.clone().with_org(org_a),
+            &mm,
+            ProjectForCreate {
+                name: "project a".to_string(),
+            },
+        )
+        .await?;
+
+        let invitation =
+            InvitationBmc::create(&admin_ctx, &mm, org_a, "demo_org_guest".to_string()).await?;
+        let accepted_org = InvitationBmc::accept(&guest_ctx, &mm, &invitation.token).await?;
+        let reused = InvitationBmc::accept(&guest_ctx, &mm, &invitation.token).await;
+
+        // 执行
+        let guest_a_ctx = guest_ctx.clone().with_org(org_a);
+        let guest_projects = ProjectBmc::list(&guest_a_ctx, &mm, None, None).await?;
+        let other_org_get =
+            ProjectBmc::get(&admin_ctx.clone().with_org(org_b), &mm, project_id).await;
+        let no_org_list = ProjectBmc::list(&admin_ctx, &mm, None, None).await;
+        let admin_orgs = OrganizationBmc::list_for_user(&admin_ctx, &mm).await?;
+
+        // 检查
+        assert_eq!(accepted_org, org_a);
+        assert!(matches!(reused, Err(model::Error::InvitationNotValid)));
+        assert_eq!(guest_projects.len(), 1);
+        assert_eq!(guest_projects[0].id, project_id);
+        assert_eq!(guest_projects[0].org_id, org_a);
+        assert!(matches!(
+            other_org_get,
+            Err(model::Error::EntityNotFound {
+                entity: "project",
+                ..
+            })
+        ));
+        assert!(matches!(
+            no_org_list,
+            Err(model::Error::TenantNotSelected { entity: "project" })
+        ));
+        assert_eq!(admin_orgs.len(), 2);
+
+        // 通过 X-Org-Id 选择组织，只能选择自己所属的组织
+        let created = ApiKeyBmc::create(
+            &guest_ctx,
+            &mm,
+            ApiKeyForCreate {
+                name: "org".to_string(),
+                scopes: vec!["read".to_string()],
+                expires_in_sec: None,
+            },
+        )
+        .await?;
+        let route = Router::new()
+            .merge(web::routes_org::routes(mm.clone()))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+        let list_request = |org_id: i64| {
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/api/orgs")
+                .header(web::API_KEY_HEADER, &created.key)
+                .header(web::ORG_HEADER, org_id.to_string())
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let member_response = route.clone().oneshot(list_request(org_a)).await?;
+        let not_member_response = route.clone().oneshot(list_request(org_b)).await?;
+
+        assert_eq!(member_response.status(), http::StatusCode::OK);
+        assert_eq!(not_member_response.status(), http::StatusCode::FORBIDDEN);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_impersonation() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        let root_ctx = Ctx::root_ctx();
+
+        // 初始化：demo1 作为 admin 模拟一个普通用户
+        let admin_id = UserBmc::first_by_username::<User>(&root_ctx, &mm, "demo1")
+            .await?
+            .context("Should have user 'demo1'")?
+            .id;
+        let admin_ctx = Ctx::new(admin_id)?.with_roles(Vec::new(), Permission::ALL.to_vec());
+        let user_id = UserBmc::create::<UserForCreate>(
+            &root_ctx,
+            &mm,
+            UserForCreate {
+                username: "demo_imp_target".to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+        let user_ctx = Ctx::new(user_id)?;
+
+        let created = ImpersonationBmc::create(
+            &admin_ctx,
+            &mm,
+            ImpersonationForCreate {
+                user_id,
+                reason: "debug".to_string(),
+                duration_sec: Some(60.0),
+            },
+        )
+        .await?;
+        let not_admin_res = ImpersonationBmc::create(
+            &user_ctx,
+            &mm,
+            ImpersonationForCreate {
+                user_id: admin_id,
+                reason: String::new(),
+                duration_sec: None,
+            },
+        )
+        .await;
+
+        let route = Router::new()
+            .merge(web::routes_api_key::routes(mm.clone()))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+        let bearer = format!("{}{}", web::BEARER_PREFIX, created.token);
+        let list_request = || {
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/api/api_keys")
+                .header(http::header::AUTHORIZATION, &bearer)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let create_request = Request::builder()
+            .method(http::Method::POST)
+            .uri("/api/api_keys")
+            .header(http::header::AUTHORIZATION, &bearer)
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(json!({ "name": "imp" }).to_string()))
+            .unwrap();
+
+        // 执行
+        let list_response = route.clone().oneshot(list_request()).await?;
+        let create_response = route.clone().oneshot(create_request).await?;
+        let imp_ctx = Ctx::new(user_id)?.with_impersonator(admin_id);
+        let update_pwd_res = UserBmc::update_pwd(&imp_ctx, &mm, user_id, "hacked").await;
+
+        ImpersonationBmc::end(&admin_ctx, &mm, created.id).await?;
+        let ended_response = route.clone().oneshot(list_request()).await?;
+
+        // 检查
+        assert!(matches!(
+            not_admin_res,
+            Err(model::Error::Ctx(ctx::Error::PermissionDenied { .. }))
+        ));
+        assert_eq!(list_response.status(), http::StatusCode::OK);
+        assert_eq!(create_response.status(), http::StatusCode::FORBIDDEN);
+        assert!(matches!(
+            update_pwd_res,
+            Err(model::Error::Ctx(ctx::Error::ImpersonationForbidden))
+        ));
+        assert_eq!(ended_response.status(), http::StatusCode::FORBIDDEN);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrations() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+        // 初始化：复制 migration 目录，并修改已执行的第一个文件
+        let migrations_dir = std::env::var("SERVICE_MIGRATIONS_DIR")?;
+        let edited_dir = std::env::temp_dir().join(format!("migrations-{}", uuid::Uuid::new_v4()));
+        let empty_dir = edited_dir.join("empty");
+        std::fs::create_dir_all(&empty_dir)?;
+        for entry in std::fs::read_dir(&migrations_dir)? {
+            let path = entry?.path();
+            if let Some(file_name) = path.file_name() {
+                std::fs::copy(&path, edited_dir.join(file_name))?;
+            }
+        }
+        let edited_file = edited_dir.join("0001_initial_schema.sql");
+        let content = std::fs::read_to_string(&edited_file)?;
+        std::fs::write(&edited_file, format!("{content}\n-- edited\n"))?;
+
+        // 执行
+        let rerun_res = migration::migrate(&mm).await;
+        let edited_res = migration::migrate_dir(&mm, &edited_dir).await;
+        let missing_res = migration::migrate_dir(&mm, &empty_dir).await;
+        std::fs::remove_dir_all(&edited_dir)?;
+
+        // 检查：已经执行过的 migration 不会重复执行
+        assert_eq!(rerun_res?, Vec::<i64>::new());
+        assert!(matches!(
+            edited_res,
+            Err(migration::Error::ChecksumMismatch { version: 1, .. })
+        ));
+        assert!(matches!(
+            missing_res,
+            Err(migration::Error::AppliedMigrationMissing { version: 1, .. })
+        ));
 
         Ok(())
     }
 
-    async fn test_first_by_username_ok_demo1(mm: ModelManager) -> Result<()> {
+    #[tokio::test]
+    async fn test_first_by_username_ok_demo1() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
         // 初始化
         let ctx = Ctx::root_ctx();
         let fx_username = "demo1";
@@ -142,7 +1010,10 @@ mod tests {
         Ok(())
     }
 
-    async fn test_create_user_ok_demo12(mm: ModelManager) -> Result<()> {
+    #[tokio::test]
+    async fn test_create_user_ok_demo12() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
         // 初始化
         let ctx = Ctx::root_ctx();
         let fx_username = "demo12";
@@ -178,7 +1049,60 @@ mod tests {
         Ok(())
     }
 
-    async fn test_register_interface(mm: ModelManager) -> Result<()> {
+    #[tokio::test]
+    async fn test_register_duplicate_username() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+
+        // 初始化：demo1 已在 dev fixture 中
+        let route = Router::new()
+            .merge(routes(mm.clone()))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(CookieManagerLayer::new());
+
+        // 执行
+        let response = route
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/api/register")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        json!({ "username": "demo1", "pwd": "welcome" }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+
+        // 直接写入也会得到带有字段的约束错误
+        let create_res = UserBmc::create::<UserForCreate>(
+            &Ctx::root_ctx(),
+            &mm,
+            UserForCreate {
+                username: "demo1".to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await;
+
+        // 检查
+        assert_eq!(status, http::StatusCode::CONFLICT);
+        assert_eq!(body["data"]["message"], "USERNAME_ALREADY_EXIST");
+        assert!(matches!(
+            create_res,
+            Err(model::Error::UniqueViolation { field: Some(field), .. }) if field == "username"
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_register_interface() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
         let ctx = Ctx::root_ctx();
         let fx_username = "demo3";
         let fx_pwd = "welcome";
@@ -249,6 +1173,203 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_me_profile() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+
+        // 初始化：新用户和它的 api key
+        let user_id = UserBmc::create::<UserForCreate>(
+            &Ctx::root_ctx(),
+            &mm,
+            UserForCreate {
+                username: "demo_me".to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+        let created = ApiKeyBmc::create(
+            &Ctx::new(user_id)?,
+            &mm,
+            ApiKeyForCreate {
+                name: "me".to_string(),
+                scopes: vec!["write".to_string()],
+                expires_in_sec: None,
+            },
+        )
+        .await?;
+        let route = Router::new()
+            .merge(web::routes_user::routes(mm.clone()))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+        let patch_request = |body: serde_json::Value| {
+            Request::builder()
+                .method(http::Method::PATCH)
+                .uri("/api/me")
+                .header(web::API_KEY_HEADER, &created.key)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        // 执行
+        let patch_response = route
+            .clone()
+            .oneshot(patch_request(json!({
+              "display_name": "Demo Me",
+              "locale": "zh-CN"
+            })))
+            .await?;
+        // username 不能通过资料接口修改，超长的字段被 check 约束拒绝
+        let username_response = route
+            .clone()
+            .oneshot(patch_request(json!({ "username": "demo_me_renamed" })))
+            .await?;
+        let too_long_response = route
+            .clone()
+            .oneshot(patch_request(json!({ "locale": "x".repeat(17) })))
+            .await?;
+        let get_response = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri("/api/me")
+                    .header(web::API_KEY_HEADER, &created.key)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await?;
+        let anonymous_response = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri("/api/me")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await?;
+
+        // 检查
+        assert_eq!(patch_response.status(), http::StatusCode::OK);
+        assert_eq!(username_response.status(), http::StatusCode::OK);
+        assert_eq!(too_long_response.status(), http::StatusCode::BAD_REQUEST);
+        assert_eq!(anonymous_response.status(), http::StatusCode::FORBIDDEN);
+
+        let body = hyper::body::to_bytes(get_response.into_body()).await?;
+        let body: ResponseBody<serde_json::Value> = serde_json::from_slice(&body)?;
+        assert_eq!(
+            body.data,
+            json!({
+              "id": user_id,
+              "username": "demo_me",
+              "display_name": "Demo Me",
+              "avatar_url": null,
+              "locale": "zh-CN",
+              "time_zone": null
+            })
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_me_export_and_delete() -> Result<()> {
+        let test_db = _dev_utils::init_test().await;
+        let mm = test_db.mm().clone();
+
+        // 初始化：新用户和它的 api key
+        let user_id = UserBmc::create::<UserForCreate>(
+            &Ctx::root_ctx(),
+            &mm,
+            UserForCreate {
+                username: "demo_me_delete".to_string(),
+                pwd: "welcome".to_string(),
+            },
+        )
+        .await?;
+        let created = ApiKeyBmc::create(
+            &Ctx::new(user_id)?,
+            &mm,
+            ApiKeyForCreate {
+                name: "me".to_string(),
+                scopes: vec!["write".to_string()],
+                expires_in_sec: None,
+            },
+        )
+        .await?;
+        let route = Router::new()
+            .merge(web::routes_user::routes(mm.clone()))
+            .merge(routes(mm.clone()))
+            .layer(middleware::map_response(mw_response_map))
+            .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+            .layer(CookieManagerLayer::new());
+        let api_key_request = |method: http::Method, uri: &str, body: Body| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(web::API_KEY_HEADER, &created.key)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(body)
+                .unwrap()
+        };
+        let delete_request = |pwd: &str| {
+            api_key_request(
+                http::Method::POST,
+                "/api/me/delete",
+                Body::from(json!({ "pwd": pwd }).to_string()),
+            )
+        };
+
+        // 执行
+        let export_response = route
+            .clone()
+            .oneshot(api_key_request(
+                http::Method::GET,
+                "/api/me/export",
+                Body::empty(),
+            ))
+            .await?;
+        let wrong_pwd_response = route.clone().oneshot(delete_request("wrong")).await?;
+        let delete_response = route.clone().oneshot(delete_request("welcome")).await?;
+        // api key 已被吊销
+        let after_delete_response = route
+            .clone()
+            .oneshot(api_key_request(http::Method::GET, "/api/me", Body::empty()))
+            .await?;
+        // 宽限期内重新登录取消删除
+        let login_response = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/api/login")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        json!({ "username": "demo_me_delete", "pwd": "welcome" }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await?;
+
+        // 检查
+        assert_eq!(export_response.status(), http::StatusCode::OK);
+        let body = hyper::body::to_bytes(export_response.into_body()).await?;
+        let body: ResponseBody<serde_json::Value> = serde_json::from_slice(&body)?;
+        assert_eq!(body.data["entities"]["user"][0]["id"], user_id);
+        assert_eq!(body.data["entities"]["api_key"][0]["name"], "me");
+
+        assert_eq!(wrong_pwd_response.status(), http::StatusCode::FORBIDDEN);
+        assert_eq!(delete_response.status(), http::StatusCode::OK);
+        assert_eq!(after_delete_response.status(), http::StatusCode::FORBIDDEN);
+        assert_eq!(login_response.status(), http::StatusCode::OK);
+        assert!(!UserBmc::cancel_deletion(&Ctx::root_ctx(), &mm, user_id).await?);
+
+        Ok(())
+    }
 }
 
 // endregion: --- Tests
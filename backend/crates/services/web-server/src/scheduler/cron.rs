@@ -0,0 +1,242 @@
+//! 5 段式 cron 表达式：`分 时 日 月 周`，按 UTC 计算
+//!
+//! - 每段支持 `*`、`5`、`1-5`、`*/15`、`1-30/5`、`1,15,30` 以及它们的组合。
+//! - 周的取值为 0-7，0 和 7 都表示周日。
+//! - 日和周都不是 `*` 时满足任意一个即可，和标准 cron 一致。
+//! - 支持 `@hourly`、`@daily`、`@weekly`、`@monthly`、`@yearly`。
+//!
+
+use super::{Error, Result};
+use time::{Date, Duration, OffsetDateTime, Time, UtcOffset};
+
+// 查找下一次执行时间时最多检查的天数，超过时表达式永远不会触发（例如 2 月 30 日）
+const MAX_SEARCH_DAYS: i64 = 366 * 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // 日或周为 `*` 时只需要满足另一个
+    days_any: bool,
+    weekdays_any: bool,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expr = expr.trim();
+        let expanded = match expr {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" => "0 0 1 1 *",
+            _ => expr,
+        };
+
+        let invalid = |reason: &'static str| Error::CronInvalid {
+            expr: expr.to_string(),
+            reason,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(invalid("expected 5 fields"));
+        };
+
+        // 7 和 0 一样表示周日
+        let mut weekdays_bits = parse_field(weekdays, 0, 7).map_err(invalid)?;
+        if weekdays_bits & (1 << 7) != 0 {
+            weekdays_bits = (weekdays_bits | 1) & !(1 << 7);
+        }
+
+        Ok(Cron {
+            minutes: parse_field(minutes, 0, 59).map_err(invalid)?,
+            hours: parse_field(hours, 0, 23).map_err(invalid)?,
+            days: parse_field(days, 1, 31).map_err(invalid)?,
+            months: parse_field(months, 1, 12).map_err(invalid)?,
+            weekdays: weekdays_bits,
+            days_any: days == "*",
+            weekdays_any: weekdays == "*",
+        })
+    }
+
+    /// after 之后（不包括 after）的第一次执行时间，永远不会触发时返回 None
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let after = after.to_offset(UtcOffset::UTC);
+        let mut date = after.date();
+        // 从下一分钟开始
+        let start =
+            after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::minutes(1);
+        let mut from = if start.date() == date {
+            start.time()
+        } else {
+            date = start.date();
+            Time::MIDNIGHT
+        };
+
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_date(date) {
+                if let Some(time) = self.first_time_from(from) {
+                    return Some(date.with_time(time).assume_utc());
+                }
+            }
+            date = date.next_day()?;
+            from = Time::MIDNIGHT;
+        }
+
+        None
+    }
+
+    fn matches_date(&self, date: Date) -> bool {
+        if !has_bit(self.months, u8::from(date.month()) as u32) {
+            return false;
+        }
+
+        let day_match = has_bit(self.days, date.day() as u32);
+        let weekday_match = has_bit(
+            self.weekdays,
+            date.weekday().number_days_from_sunday() as u32,
+        );
+
+        match (self.days_any, self.weekdays_any) {
+            (true, true) => true,
+            (true, false) => weekday_match,
+            (false, true) => day_match,
+            (false, false) => day_match || weekday_match,
+        }
+    }
+
+    /// 当天 from 及之后第一个匹配的时间
+    fn first_time_from(&self, from: Time) -> Option<Time> {
+        (from.hour()..24)
+            .filter(|hour| has_bit(self.hours, *hour as u32))
+            .find_map(|hour| {
+                let first_minute = if hour == from.hour() {
+                    from.minute()
+                } else {
+                    0
+                };
+                (first_minute..60)
+                    .find(|minute| has_bit(self.minutes, *minute as u32))
+                    .and_then(|minute| Time::from_hms(hour, minute, 0).ok())
+            })
+    }
+}
+
+fn has_bit(bits: u64, n: u32) -> bool {
+    bits & (1 << n) != 0
+}
+
+/// 解析一段，返回取值的位集合
+fn parse_field(field: &str, min: u32, max: u32) -> core::result::Result<u64, &'static str> {
+    let mut bits = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| "invalid step")?;
+                if step == 0 {
+                    return Err("step must be greater than 0");
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start)?, parse_value(end)?)
+        } else {
+            let value = parse_value(range)?;
+            // `5/10` 表示从 5 开始每 10 个
+            if part.contains('/') {
+                (value, max)
+            } else {
+                (value, value)
+            }
+        };
+
+        if start < min || end > max || start > end {
+            return Err("value out of range");
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn parse_value(value: &str) -> core::result::Result<u32, &'static str> {
+    value.parse().map_err(|_| "invalid value")
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use lib_utils::time::{format_time, parse_utc};
+
+    #[test]
+    fn test_cron_next_after() -> Result<()> {
+        let next = |expr: &str, after: &str| -> Result<Option<String>> {
+            let next = Cron::parse(expr)?.next_after(parse_utc(after)?);
+            Ok(next.map(format_time))
+        };
+
+        // 执行 & 检查
+        assert_eq!(
+            next("*/15 * * * *", "2024-01-31T23:59:30Z")?.as_deref(),
+            Some("2024-02-01T00:00:00Z")
+        );
+        // 不包括 after 本身
+        assert_eq!(
+            next("0 4 * * *", "2024-03-10T04:00:00Z")?.as_deref(),
+            Some("2024-03-11T04:00:00Z")
+        );
+        assert_eq!(
+            next("30 9-17/4 * * 1-5", "2024-03-08T17:45:00Z")?.as_deref(),
+            Some("2024-03-11T09:30:00Z")
+        );
+        // 7 和 0 都表示周日
+        assert_eq!(
+            next("0 0 * * 7", "2024-03-08T00:00:00Z")?.as_deref(),
+            Some("2024-03-10T00:00:00Z")
+        );
+        // 日和周都设置时满足任意一个
+        assert_eq!(
+            next("0 0 13 * 5", "2024-03-09T00:00:00Z")?.as_deref(),
+            Some("2024-03-13T00:00:00Z")
+        );
+        assert_eq!(
+            next("@monthly", "2024-02-15T12:00:00Z")?.as_deref(),
+            Some("2024-03-01T00:00:00Z")
+        );
+        assert_eq!(
+            next("0 0 29 2 *", "2024-03-01T00:00:00Z")?.as_deref(),
+            Some("2028-02-29T00:00:00Z")
+        );
+        assert_eq!(next("0 0 30 2 *", "2024-03-01T00:00:00Z")?, None);
+
+        for expr in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(Cron::parse(expr).is_err(), "{expr} should be invalid");
+        }
+
+        Ok(())
+    }
+}
+
+// endregion: --- Tests
@@ -0,0 +1,32 @@
+use lib_core::model;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    // cron 表达式无法解析
+    CronInvalid { expr: String, reason: &'static str },
+    // 表达式永远不会触发，例如 2 月 30 日
+    CronNeverFires { task: &'static str },
+
+    // -- Modules
+    Model(model::Error),
+}
+
+// region:    --- Froms
+impl From<model::Error> for Error {
+    fn from(value: model::Error) -> Self {
+        Self::Model(value)
+    }
+}
+// endregion: --- Froms
+
+// region:    --- Error Boilerplate
+impl core::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for Error {}
+// endregion: --- Error Boilerplate
@@ -0,0 +1,207 @@
+//! 定时任务
+//!
+//! - 任务在代码中通过 `Scheduler::task` 声明默认的 cron 表达式，
+//!   配置 `SERVICE_SCHEDULES` 可以修改表达式，或者设置为 `off` 关闭任务。
+//! - 每个 tick 通过 `ScheduledTaskBmc::try_lead` 选出一个实例执行，多实例部署时不会重复执行。
+//! - 进程停止期间错过的 tick 不会补执行。
+//! - 任务应尽快返回，耗时的工作放到后台任务队列中执行。
+//!
+
+// region:    --- Modules
+
+mod cron;
+mod error;
+
+pub use self::cron::Cron;
+pub use self::error::{Error, Result};
+
+use lib_core::ctx::Ctx;
+use lib_core::model::scheduled_task::ScheduledTaskBmc;
+use lib_core::model::ModelManager;
+use lib_utils::time::now_utc;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+// endregion: --- Modules
+
+// 配置中关闭任务的值
+pub const SCHEDULE_OFF: &str = "off";
+
+// 两次检查之间最长的间隔
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+pub type TaskResult = core::result::Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+type TaskFuture = Pin<Box<dyn Future<Output = TaskResult> + Send>>;
+type TaskFn = Arc<dyn Fn(ModelManager) -> TaskFuture + Send + Sync>;
+
+struct Task {
+    name: &'static str,
+    cron: Cron,
+    action: TaskFn,
+}
+
+pub struct Scheduler {
+    mm: ModelManager,
+    // 任务名到 cron 表达式，覆盖代码中的默认值
+    overrides: HashMap<String, String>,
+    tasks: Vec<Task>,
+}
+
+impl Scheduler {
+    pub fn new(mm: ModelManager, overrides: HashMap<String, String>) -> Self {
+        Self {
+            mm,
+            overrides,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// 声明任务，cron 为默认的表达式，配置中有同名任务时使用配置中的表达式
+    pub fn task<F, Fut>(mut self, name: &'static str, cron: &str, action: F) -> Result<Self>
+    where
+        F: Fn(ModelManager) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        let expr = self.overrides.get(name).map(String::as_str).unwrap_or(cron);
+        if expr == SCHEDULE_OFF {
+            info!("{:<12} - {name} disabled", "SCHEDULER");
+            return Ok(self);
+        }
+
+        let cron = Cron::parse(expr)?;
+        if cron.next_after(now_utc()).is_none() {
+            return Err(Error::CronNeverFires { task: name });
+        }
+
+        let action: TaskFn = Arc::new(move |mm| Box::pin(action(mm)));
+        self.tasks.push(Task { name, cron, action });
+
+        Ok(self)
+    }
+
+    /// 执行任务 name 在 tick 时刻的这一次，返回是否由当前实例执行
+    /// 任务本身的错误只记录日志，这个 tick 不会重新执行
+    pub async fn run_tick(&self, name: &str, tick: OffsetDateTime) -> Result<bool> {
+        let Some(task) = self.tasks.iter().find(|task| task.name == name) else {
+            return Ok(false);
+        };
+
+        let ctx = Ctx::root_ctx();
+        let Some(lease) = ScheduledTaskBmc::try_lead(&ctx, &self.mm, task.name, tick).await? else {
+            return Ok(false);
+        };
+
+        info!("{:<12} - {} - tick {tick}", "SCHEDULER", task.name);
+        if let Err(ex) = (task.action)(self.mm.clone()).await {
+            error!("{:<12} - {} - {ex}", "SCHEDULER", task.name);
+        }
+
+        lease.finish().await?;
+
+        Ok(true)
+    }
+
+    pub async fn run(self) {
+        let now = now_utc();
+        let mut next_ticks: Vec<Option<OffsetDateTime>> = self
+            .tasks
+            .iter()
+            .map(|task| task.cron.next_after(now))
+            .collect();
+
+        loop {
+            for (task, next_tick) in self.tasks.iter().zip(next_ticks.iter_mut()) {
+                let Some(tick) = *next_tick else {
+                    continue;
+                };
+                if tick > now_utc() {
+                    continue;
+                }
+
+                if let Err(ex) = self.run_tick(task.name, tick).await {
+                    error!("{:<12} - {} - {ex:?}", "SCHEDULER", task.name);
+                }
+
+                // 跳过执行期间错过的 tick
+                *next_tick = task.cron.next_after(now_utc().max(tick));
+            }
+
+            // 等到最近的下一个 tick
+            let sleep = next_ticks
+                .iter()
+                .flatten()
+                .min()
+                .map(|tick| (*tick - now_utc()).try_into().unwrap_or(Duration::ZERO))
+                .unwrap_or(MAX_SLEEP)
+                .min(MAX_SLEEP);
+            tokio::time::sleep(sleep).await;
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use lib_core::_dev_utils::init_test;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_scheduler_run_tick() -> Result<()> {
+        let test_db = init_test().await;
+        let mm = test_db.mm().clone();
+        let runs = Arc::new(AtomicUsize::new(0));
+        // 两个 Scheduler 模拟两个 web-server 实例
+        let fx_scheduler = |overrides: HashMap<String, String>| {
+            let runs = runs.clone();
+            Scheduler::new(mm.clone(), overrides).task("test_task", "* * * * *", move |_mm| {
+                runs.fetch_add(1, Ordering::SeqCst);
+                async {
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    Ok(())
+                }
+            })
+        };
+        let scheduler_01 = fx_scheduler(HashMap::new())?;
+        let scheduler_02 = fx_scheduler(HashMap::new())?;
+        let fx_tick = now_utc().replace_nanosecond(0)?;
+
+        // 执行
+        let (res_01, res_02) = tokio::join!(
+            scheduler_01.run_tick("test_task", fx_tick),
+            scheduler_02.run_tick("test_task", fx_tick)
+        );
+        let again = scheduler_02.run_tick("test_task", fx_tick).await?;
+
+        let off = fx_scheduler(HashMap::from([("test_task".into(), "off".into())]))?;
+        let off_res = off
+            .run_tick("test_task", fx_tick + time::Duration::minutes(1))
+            .await?;
+        let invalid = fx_scheduler(HashMap::from([("test_task".into(), "* *".into())]));
+
+        // 检查
+        // 同一个 tick 只有一个实例执行
+        assert_eq!([res_01?, res_02?].iter().filter(|led| **led).count(), 1);
+        assert!(!again);
+        assert!(!off_res);
+        assert!(matches!(invalid, Err(Error::CronInvalid { .. })));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+}
+
+// endregion: --- Tests
@@ -2,9 +2,8 @@ use crate::web;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use lib_auth::{pwd, token};
-use lib_core::model;
+use lib_core::{ctx, model};
 use serde::Serialize;
-use std::borrow::Cow;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -23,8 +22,17 @@ pub enum Error {
     // -- Register
     RegisterFail,
 
+    // -- Account
+    // 删除账号时重新输入的密码不正确
+    AccountDeleteFailPwdNotMatching { user_id: i64 },
+
+    // -- Csrf
+    CsrfTokenMissing,
+    CsrfTokenNotMatching,
+
     // -- Modules
     Model(model::Error),
+    Ctx(ctx::Error),
 
     // -- Token
     Token(token::Error),
@@ -47,6 +55,12 @@ impl From<token::Error> for Error {
     }
 }
 
+impl From<ctx::Error> for Error {
+    fn from(value: ctx::Error) -> Self {
+        Self::Ctx(value)
+    }
+}
+
 // endregion: --- Froms
 
 // region:    --- Axum IntoResponse
@@ -85,39 +99,100 @@ impl Error {
             // -- Login/Auth
             CtxExt(_) => (StatusCode::FORBIDDEN, ClientError::NO_AUTH),
 
+            // -- Permission
+            Ctx(
+                ctx::Error::PermissionDenied { .. }
+                | ctx::Error::ImpersonationForbidden
+                | ctx::Error::ApiKeyScopeRequired { .. }
+                | ctx::Error::ApiKeyForbidden,
+            )
+            | Model(model::Error::Ctx(
+                ctx::Error::PermissionDenied { .. }
+                | ctx::Error::ImpersonationForbidden
+                | ctx::Error::ApiKeyScopeRequired { .. }
+                | ctx::Error::ApiKeyForbidden,
+            )) => (StatusCode::FORBIDDEN, ClientError::PERMISSION_DENIED),
+
+            // -- Organization
+            Model(model::Error::OrgAdminRequired { .. }) => {
+                (StatusCode::FORBIDDEN, ClientError::PERMISSION_DENIED)
+            }
+            Model(model::Error::TenantNotSelected { .. }) => {
+                (StatusCode::BAD_REQUEST, ClientError::ORG_NOT_SELECTED)
+            }
+            Model(model::Error::InvitationNotValid) => {
+                (StatusCode::BAD_REQUEST, ClientError::INVITATION_NOT_VALID)
+            }
+
+            // -- Csrf
+            CsrfTokenMissing | CsrfTokenNotMatching => {
+                (StatusCode::FORBIDDEN, ClientError::CSRF_FAIL)
+            }
+
             // -- 密码错误
             LoginFailUserHasNoPwd { user_id } => {
                 (StatusCode::INTERNAL_SERVER_ERROR, ClientError::LOGIN_FAIL)
             }
+            AccountDeleteFailPwdNotMatching { .. } => {
+                (StatusCode::FORBIDDEN, ClientError::LOGIN_FAIL)
+            }
 
-            // -- Model Error
-            Model(error) => {
-                match error {
-                    model::Error::Sqlx(sqlx_err) => {
-                        if let Some(db_err) = sqlx_err.as_database_error() {
-                            if db_err.code().take() == Some(Cow::Owned(String::from("23505"))) {
-                                return (
-                                    StatusCode::INTERNAL_SERVER_ERROR,
-                                    ClientError::USERNAME_ALREADY_EXIST,
-                                );
-                            }
-                        };
-
-                        return (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            ClientError::SERVICE_ERROR,
-                        );
-                    }
-
-                    _ => {
-                        return (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            ClientError::SERVICE_ERROR,
-                        );
-                    }
-                };
+            // -- 数据库约束
+            Model(model::Error::UniqueViolation { field, .. }) => match field.as_deref() {
+                Some("username") => (StatusCode::CONFLICT, ClientError::USERNAME_ALREADY_EXIST),
+                field => (
+                    StatusCode::CONFLICT,
+                    ClientError::ALREADY_EXIST {
+                        field: field.map(String::from),
+                    },
+                ),
+            },
+            Model(model::Error::ForeignKeyViolation { field, .. }) => (
+                StatusCode::BAD_REQUEST,
+                ClientError::INVALID_REFERENCE {
+                    field: field.clone(),
+                },
+            ),
+            Model(model::Error::NotNullViolation { .. } | model::Error::CheckViolation { .. }) => {
+                (StatusCode::BAD_REQUEST, ClientError::INVALID_PARAMS)
             }
 
+            // -- ApiKey
+            Model(
+                model::Error::ApiKeyScopesEmpty
+                | model::Error::ApiKeyScopeUnknown { .. }
+                | model::Error::ApiKeyExpiresInvalid { .. },
+            ) => (StatusCode::BAD_REQUEST, ClientError::INVALID_PARAMS),
+
+            // -- Webhook
+            Model(
+                model::Error::WebhookUrlInvalid { .. }
+                | model::Error::WebhookAddressForbidden { .. }
+                | model::Error::WebhookEventTypeUnknown { .. },
+            ) => (StatusCode::BAD_REQUEST, ClientError::INVALID_PARAMS),
+
+            // -- 乐观锁
+            Model(model::Error::VersionConflict {
+                entity,
+                id,
+                expected,
+                actual,
+            }) => (
+                StatusCode::CONFLICT,
+                ClientError::VERSION_CONFLICT {
+                    entity,
+                    id: *id,
+                    expected: *expected,
+                    actual: *actual,
+                },
+            ),
+
+            // -- Model Error
+            Model(model::Error::EntityNotFound { entity, id }) => (
+                StatusCode::BAD_REQUEST,
+                ClientError::ENTITY_NOT_FOUND { entity, id: *id },
+            ),
+
             // -- Fallback
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -134,12 +209,40 @@ pub enum ClientError {
     LOGIN_FAIL,
     // 票据验证失败
     NO_AUTH,
-    ENTITY_NOT_FOUND { entity: &'static str, id: i64 },
+    // 缺少访问权限
+    PERMISSION_DENIED,
+    // CSRF token 缺失或不匹配
+    CSRF_FAIL,
+    ENTITY_NOT_FOUND {
+        entity: &'static str,
+        id: i64,
+    },
+    // 数据已被其他请求修改，需要重新读取后再提交
+    VERSION_CONFLICT {
+        entity: &'static str,
+        id: i64,
+        expected: i64,
+        actual: i64,
+    },
+    // 访问按组织隔离的数据前需要通过 X-Org-Id 选择组织
+    ORG_NOT_SELECTED,
+    // 邀请不存在、已被使用或已过期
+    INVITATION_NOT_VALID,
     // 服务端位置错误
     SERVICE_ERROR,
 
     // 用户名已存在
     USERNAME_ALREADY_EXIST,
+    // 其他唯一字段已存在，field 为对应的字段
+    ALREADY_EXIST {
+        field: Option<String>,
+    },
+    // 引用的数据不存在
+    INVALID_REFERENCE {
+        field: Option<String>,
+    },
+    // 缺少必填字段或字段值不合法
+    INVALID_PARAMS,
 }
 
 // endregion: --- Client Error
@@ -1,14 +1,23 @@
 // region:    --- Modules
 mod error;
 pub mod mw_auth;
+pub mod mw_csrf;
 pub mod mw_res_map;
+pub mod routes_api_key;
+pub mod routes_impersonation;
 pub mod routes_login;
+pub mod routes_org;
+pub mod routes_role;
 pub mod routes_static;
+pub mod routes_user;
+pub mod routes_webhook;
 
 use tower_cookies::{Cookie, Cookies};
 use uuid::Uuid;
 
-use lib_auth::token::generate_web_token;
+use crate::config::web_config;
+use lib_auth::token::{self, generate_web_token};
+use lib_utils::time::{now_utc, parse_utc};
 
 pub use self::error::ClientError;
 pub use self::error::{Error, Result};
@@ -16,13 +25,40 @@ pub use self::error::{Error, Result};
 // endregion: --- Modules
 
 pub const AUTH_TOKEN: &str = "auth-token";
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const BEARER_PREFIX: &str = "Bearer ";
+
+// 多租户请求通过该请求头选择当前组织
+pub const ORG_HEADER: &str = "x-org-id";
+
+// CSRF token 采用 double-submit 的方式，cookie 需要能被前端读取
+pub const CSRF_TOKEN: &str = "csrf-token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
 
 fn set_token_cookie(cookies: &Cookies, user: &str, salt: Uuid) -> Result<()> {
     let token = generate_web_token(user, salt)?;
-    let mut cookie = Cookie::new(AUTH_TOKEN, token.to_string());
 
+    // cookie 的有效期和 token 的过期时间保持一致
+    let exp = parse_utc(&token.exp).map_err(|_| token::Error::ExpNotIso)?;
+    let max_age = exp - now_utc();
+
+    let mut cookie = Cookie::new(AUTH_TOKEN, token.to_string());
+    set_cookie_attrs(&mut cookie);
     cookie.set_http_only(true);
-    cookie.set_path("/");
+    cookie.set_max_age(max_age);
+
+    cookies.add(cookie);
+
+    // 同时续期 csrf cookie，没有则生成一个新的
+    let csrf_token = cookies
+        .get(CSRF_TOKEN)
+        .map(|c| c.value().to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let mut cookie = Cookie::new(CSRF_TOKEN, csrf_token);
+    set_cookie_attrs(&mut cookie);
+    cookie.set_http_only(false);
+    cookie.set_max_age(max_age);
 
     cookies.add(cookie);
 
@@ -31,9 +67,31 @@ fn set_token_cookie(cookies: &Cookies, user: &str, salt: Uuid) -> Result<()> {
 
 fn remove_token_cookie(cookies: &Cookies) -> Result<()> {
     let mut cookie = Cookie::named(AUTH_TOKEN);
-    cookie.set_path("/");
+    set_cookie_attrs(&mut cookie);
 
     cookies.remove(cookie);
 
     Ok(())
 }
+
+fn remove_csrf_cookie(cookies: &Cookies) -> Result<()> {
+    let mut cookie = Cookie::named(CSRF_TOKEN);
+    set_cookie_attrs(&mut cookie);
+
+    cookies.remove(cookie);
+
+    Ok(())
+}
+
+// 删除 cookie 时浏览器要求属性一致，所以设置和删除都走这里
+fn set_cookie_attrs(cookie: &mut Cookie<'static>) {
+    let config = web_config();
+
+    cookie.set_path("/");
+    cookie.set_secure(config.COOKIE_SECURE);
+    cookie.set_same_site(config.COOKIE_SAME_SITE);
+
+    if let Some(domain) = &config.COOKIE_DOMAIN {
+        cookie.set_domain(domain.clone());
+    }
+}
@@ -1,17 +1,26 @@
-use crate::web::{set_token_cookie, AUTH_TOKEN};
+use crate::web::{
+    remove_token_cookie, set_token_cookie, API_KEY_HEADER, AUTH_TOKEN, BEARER_PREFIX, ORG_HEADER,
+};
 use crate::web::{Error, Result};
 use async_trait::async_trait;
 use axum::extract::{FromRequestParts, State};
 use axum::http::request::Parts;
-use axum::http::Request;
+use axum::http::{header, Request};
 use axum::middleware::Next;
 use axum::response::Response;
+use lib_auth::api_key::ApiKey;
+use lib_auth::pwd::{self, ContentToHash};
 use lib_auth::token::{validate_web_token, Token};
-use lib_core::ctx::Ctx;
+use lib_core::ctx::{Ctx, Permission};
+use lib_core::model::api_key::{ApiKeyBmc, ApiKeyForAuth};
+use lib_core::model::impersonation::{ImpersonationBmc, ImpersonationForAuth};
+use lib_core::model::organization::OrganizationBmc;
+use lib_core::model::role::RoleBmc;
 use lib_core::model::user::{UserBmc, UserForAuth};
 use lib_core::model::ModelManager;
+use lib_utils::time::now_utc;
 use serde::Serialize;
-use tower_cookies::{Cookie, Cookies};
+use tower_cookies::Cookies;
 use tracing::debug;
 
 #[allow(dead_code)] // For now, until we have the rpc.
@@ -27,6 +36,23 @@ pub async fn mw_ctx_require<B>(
     Ok(next.run(req).await)
 }
 
+/// 配合 from_fn_with_state 使用，state 为路由要求的权限
+pub async fn mw_ctx_require_permission<B>(
+    State(permission): State<Permission>,
+    ctx: Result<CtxW>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Result<Response> {
+    debug!(
+        "{:<12} - mw_ctx_require_permission - {permission}",
+        "MIDDLEWARE"
+    );
+
+    ctx?.0.require(permission)?;
+
+    Ok(next.run(req).await)
+}
+
 pub async fn mw_ctx_resolve<B>(
     mm: State<ModelManager>,
     cookies: Cookies,
@@ -35,11 +61,44 @@ pub async fn mw_ctx_resolve<B>(
 ) -> Result<Response> {
     println!("->> {:<12} - mw_ctx_resolve", "MIDDLEWARE");
 
-    let ctx_ext_result = _ctx_resolve(mm, &cookies).await;
+    // 带有 api key 或 Bearer token 的请求不读写 cookie
+    let ctx_ext_result = if let Some(api_key) = req.headers().get(API_KEY_HEADER) {
+        match api_key.to_str() {
+            Ok(api_key) => _ctx_resolve_api_key(&mm, api_key).await,
+            Err(_) => Err(CtxExtError::ApiKeyWrongFormat),
+        }
+    } else if let Some(authorization) = req.headers().get(header::AUTHORIZATION) {
+        let token = authorization
+            .to_str()
+            .ok()
+            .and_then(|v| v.strip_prefix(BEARER_PREFIX));
 
-    if ctx_ext_result.is_err() && !matches!(ctx_ext_result, Err(CtxExtError::TokenNotInCookie)) {
-        cookies.remove(Cookie::named(AUTH_TOKEN))
-    }
+        match token {
+            Some(token) => _ctx_resolve_bearer(&mm, token).await,
+            None => Err(CtxExtError::TokenWrongFormat),
+        }
+    } else {
+        let ctx_ext_result = _ctx_resolve(&mm, &cookies).await;
+
+        if ctx_ext_result.is_err() && !matches!(ctx_ext_result, Err(CtxExtError::TokenNotInCookie))
+        {
+            let _ = remove_token_cookie(&cookies);
+        }
+
+        ctx_ext_result
+    };
+
+    // 加载用户的角色和权限
+    let ctx_ext_result = match ctx_ext_result {
+        Ok(CtxW(ctx)) => _ctx_load_roles(&mm, ctx).await,
+        Err(ex) => Err(ex),
+    };
+
+    // 根据请求头选择组织
+    let ctx_ext_result = match (ctx_ext_result, req.headers().get(ORG_HEADER)) {
+        (Ok(CtxW(ctx)), Some(org_id)) => _ctx_select_org(&mm, ctx, org_id.to_str().ok()).await,
+        (ctx_ext_result, _) => ctx_ext_result,
+    };
 
     // Store the ctx_ext_result in the request extension
     // (for Ctx extractor)
@@ -48,34 +107,148 @@ pub async fn mw_ctx_resolve<B>(
     Ok(next.run(req).await)
 }
 
-async fn _ctx_resolve(mm: State<ModelManager>, cookies: &Cookies) -> CtxExtResult {
+async fn _ctx_resolve(mm: &ModelManager, cookies: &Cookies) -> CtxExtResult {
     // 获取 token
     let token = cookies
         .get(AUTH_TOKEN)
         .map(|c| c.value().to_string())
         .ok_or(CtxExtError::TokenNotInCookie)?;
 
-    // 解析 token
+    let token: Token = token.parse().map_err(|_| CtxExtError::TokenWrongFormat)?;
+    let user = _validate_token_user(mm, &token).await?;
+
+    // 更新 token
+    set_token_cookie(cookies, &user.username, user.token_salt);
+
+    // 创建 CtxExtResult
+    Ctx::new(user.id)
+        .map(CtxW)
+        .map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
+}
+
+async fn _ctx_resolve_bearer(mm: &ModelManager, token: &str) -> CtxExtResult {
     let token: Token = token.parse().map_err(|_| CtxExtError::TokenWrongFormat)?;
 
+    // 模拟登录的 token 只能通过 Bearer 使用，不会写入 cookie
+    if let Some(impersonation_id) = token.impersonation_id() {
+        return _ctx_resolve_impersonation(mm, &token, impersonation_id).await;
+    }
+
+    let user = _validate_token_user(mm, &token).await?;
+
+    // 创建 CtxExtResult
+    Ctx::new_bearer(user.id)
+        .map(CtxW)
+        .map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
+}
+
+async fn _validate_token_user(
+    mm: &ModelManager,
+    token: &Token,
+) -> core::result::Result<UserForAuth, CtxExtError> {
     // 获取用户的校验信息
-    let user: UserForAuth = UserBmc::first_by_username(&Ctx::root_ctx(), &mm, &token.ident)
+    let user: UserForAuth = UserBmc::first_by_username(&Ctx::root_ctx(), mm, &token.ident)
         .await
         .map_err(|ex| CtxExtError::ModelAccessError(ex.to_string()))?
         .ok_or(CtxExtError::UserNotFound)?;
 
     // 校验 token
-    validate_web_token(&token, user.token_salt).map_err(|_| CtxExtError::FailValidate)?;
+    validate_web_token(token, user.token_salt).map_err(|_| CtxExtError::FailValidate)?;
 
-    // 更新 token
-    set_token_cookie(cookies, &user.username, user.token_salt);
+    Ok(user)
+}
 
-    // 创建 CtxExtResult
-    Ctx::new(user.id)
-        .map(CtxW)
+async fn _ctx_resolve_impersonation(
+    mm: &ModelManager,
+    token: &Token,
+    impersonation_id: i64,
+) -> CtxExtResult {
+    // 获取模拟登录记录
+    let imp: ImpersonationForAuth =
+        ImpersonationBmc::first_for_auth(&Ctx::root_ctx(), mm, impersonation_id)
+            .await
+            .map_err(|ex| CtxExtError::ModelAccessError(ex.to_string()))?
+            .ok_or(CtxExtError::ImpersonationNotFound)?;
+
+    // 校验 token
+    validate_web_token(token, imp.token_salt).map_err(|_| CtxExtError::FailValidate)?;
+
+    if imp.ended_at.is_some() || imp.expires_at < now_utc() {
+        return Err(CtxExtError::ImpersonationEnded);
+    }
+
+    // 创建 CtxExtResult，ctx 以被模拟用户的身份执行
+    Ctx::new_bearer(imp.user_id)
+        .map(|ctx| CtxW(ctx.with_impersonator(imp.admin_id)))
         .map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
 }
 
+async fn _ctx_resolve_api_key(mm: &ModelManager, api_key: &str) -> CtxExtResult {
+    // 解析 api key
+    let api_key: ApiKey = api_key
+        .parse()
+        .map_err(|_| CtxExtError::ApiKeyWrongFormat)?;
+
+    // 获取 api key 的校验信息
+    let api_key_auth: ApiKeyForAuth =
+        ApiKeyBmc::first_by_prefix(&Ctx::root_ctx(), mm, &api_key.prefix)
+            .await
+            .map_err(|ex| CtxExtError::ModelAccessError(ex.to_string()))?
+            .ok_or(CtxExtError::ApiKeyNotFound)?;
+
+    // 校验 api key
+    pwd::validate_pwd(
+        &ContentToHash {
+            content: api_key.secret,
+            salt: api_key_auth.key_salt,
+        },
+        &api_key_auth.key_hash,
+    )
+    .map_err(|_| CtxExtError::ApiKeyFailValidate)?;
+
+    if api_key_auth.revoked_at.is_some() {
+        return Err(CtxExtError::ApiKeyRevoked);
+    }
+
+    if matches!(api_key_auth.expires_at, Some(expires_at) if expires_at < now_utc()) {
+        return Err(CtxExtError::ApiKeyExpired);
+    }
+
+    // 创建 CtxExtResult
+    Ctx::new_api_key(
+        api_key_auth.user_id,
+        api_key_auth.id,
+        api_key_auth.scope_list(),
+    )
+    .map(CtxW)
+    .map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
+}
+
+async fn _ctx_load_roles(mm: &ModelManager, ctx: Ctx) -> CtxExtResult {
+    let roles = RoleBmc::list_for_user(&Ctx::root_ctx(), mm, ctx.user_id())
+        .await
+        .map_err(|ex| CtxExtError::ModelAccessError(ex.to_string()))?;
+
+    let permissions = roles.iter().flat_map(|r| r.permission_list()).collect();
+    let roles = roles.into_iter().map(|r| r.name).collect();
+
+    Ok(CtxW(ctx.with_roles(roles, permissions)))
+}
+
+/// 只能选择当前用户所属的组织
+async fn _ctx_select_org(mm: &ModelManager, ctx: Ctx, org_id: Option<&str>) -> CtxExtResult {
+    let org_id: i64 = org_id
+        .and_then(|v| v.parse().ok())
+        .ok_or(CtxExtError::OrgIdWrongFormat)?;
+
+    OrganizationBmc::first_membership(&Ctx::root_ctx(), mm, org_id, ctx.user_id())
+        .await
+        .map_err(|ex| CtxExtError::ModelAccessError(ex.to_string()))?
+        .ok_or(CtxExtError::OrgNotMember)?;
+
+    Ok(CtxW(ctx.with_org(org_id)))
+}
+
 // region:    --- Ctx Extractor
 #[derive(Debug, Clone)]
 pub struct CtxW(pub Ctx);
@@ -110,6 +283,18 @@ pub enum CtxExtError {
     FailValidate,
     CannotSetTokenCookie,
 
+    ApiKeyWrongFormat,
+    ApiKeyNotFound,
+    ApiKeyFailValidate,
+    ApiKeyRevoked,
+    ApiKeyExpired,
+
+    ImpersonationNotFound,
+    ImpersonationEnded,
+
+    OrgIdWrongFormat,
+    OrgNotMember,
+
     CtxNotInRequestExt,
     CtxCreateFail(String),
 }
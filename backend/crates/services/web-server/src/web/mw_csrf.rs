@@ -0,0 +1,51 @@
+use crate::web::mw_auth::CtxW;
+use crate::web::{Error, Result, CSRF_HEADER, CSRF_TOKEN};
+use axum::http::{Method, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use lib_core::ctx::CtxAuth;
+use tower_cookies::Cookies;
+use tracing::debug;
+
+/// 对 cookie 认证的非安全请求校验 CSRF token（double-submit）。
+/// Bearer 和 api key 认证的请求不会被浏览器自动携带，无需校验。
+pub async fn mw_csrf_check<B>(
+    ctx: Option<CtxW>,
+    cookies: Cookies,
+    req: Request<B>,
+    next: Next<B>,
+) -> Result<Response> {
+    debug!("{:<12} - mw_csrf_check", "MIDDLEWARE");
+
+    let is_cookie_auth = matches!(&ctx, Some(CtxW(ctx)) if ctx.auth() == &CtxAuth::Cookie);
+
+    if is_cookie_auth && !is_safe_method(req.method()) {
+        let cookie_token = cookies
+            .get(CSRF_TOKEN)
+            .map(|c| c.value().to_string())
+            .ok_or(Error::CsrfTokenMissing)?;
+
+        let header_token = req
+            .headers()
+            .get(CSRF_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .ok_or(Error::CsrfTokenMissing)?;
+
+        if !constant_time_eq(cookie_token.as_bytes(), header_token.as_bytes()) {
+            return Err(Error::CsrfTokenNotMatching);
+        }
+    }
+
+    Ok(next.run(req).await)
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    )
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,154 @@
+use crate::web::mw_auth::CtxW;
+use crate::web::Result;
+use axum::extract::{Path, State};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use lib_core::ctx::SCOPE_READ;
+use lib_core::model::api_key::{ApiKey, ApiKeyBmc, ApiKeyForCreate};
+use lib_core::model::ModelManager;
+use lib_utils::time::format_time;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+use ts_rs::TS;
+
+pub fn routes(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/api_keys",
+            get(api_list_api_keys_handler).post(api_create_api_key_handler),
+        )
+        .route("/api/api_keys/:id/revoke", post(api_revoke_api_key_handler))
+        .with_state(mm)
+}
+
+// region:    --- Create
+async fn api_create_api_key_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Json(payload): Json<ApiKeyCreateReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_create_api_key_handler", "HANDLER");
+
+    let ApiKeyCreateReq {
+        name,
+        scopes,
+        expires_in_sec,
+    } = payload;
+
+    let api_key_c = ApiKeyForCreate {
+        name,
+        scopes: scopes.unwrap_or_else(|| vec![SCOPE_READ.to_string()]),
+        expires_in_sec,
+    };
+
+    // key 明文仅在此处返回一次
+    let created = ApiKeyBmc::create(&ctx.0, &mm, api_key_c).await?;
+
+    let body = Json(json!({
+      "data": ApiKeyCreateResp {
+        id: created.id,
+        key: created.key,
+      }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, export_to = "api_key/")]
+struct ApiKeyCreateReq {
+    name: String,
+    // 不传时只有 read scope
+    scopes: Option<Vec<String>>,
+    expires_in_sec: Option<f64>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "api_key/")]
+struct ApiKeyCreateResp {
+    #[ts(type = "number")]
+    id: i64,
+    key: String,
+}
+
+// endregion: --- Create
+
+// region:    --- List
+async fn api_list_api_keys_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_list_api_keys_handler", "HANDLER");
+
+    let api_keys: Vec<ApiKeyResp> = ApiKeyBmc::list(&ctx.0, &mm)
+        .await?
+        .into_iter()
+        .map(ApiKeyResp::from)
+        .collect();
+
+    let body = Json(json!({
+      "data": api_keys
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "api_key/")]
+struct ApiKeyResp {
+    #[ts(type = "number")]
+    id: i64,
+    name: String,
+    prefix: String,
+    scopes: Vec<String>,
+    expires_at: Option<String>,
+    revoked_at: Option<String>,
+    ctime: String,
+}
+
+impl From<ApiKey> for ApiKeyResp {
+    fn from(api_key: ApiKey) -> Self {
+        Self {
+            scopes: api_key.scope_list(),
+            id: api_key.id,
+            name: api_key.name,
+            prefix: api_key.prefix,
+            expires_at: api_key.expires_at.map(format_time),
+            revoked_at: api_key.revoked_at.map(format_time),
+            ctime: format_time(api_key.ctime),
+        }
+    }
+}
+
+// endregion: --- List
+
+// region:    --- Revoke
+async fn api_revoke_api_key_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_revoke_api_key_handler", "HANDLER");
+
+    ApiKeyBmc::revoke(&ctx.0, &mm, id).await?;
+
+    let body = Json(json!({
+      "data": ApiKeyRevokeResp {
+        id,
+        revoked: true,
+      }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "api_key/")]
+struct ApiKeyRevokeResp {
+    #[ts(type = "number")]
+    id: i64,
+    revoked: bool,
+}
+
+// endregion: --- Revoke
@@ -0,0 +1,166 @@
+use crate::web::mw_auth::{mw_ctx_require_permission, CtxW};
+use crate::web::Result;
+use axum::extract::{Path, State};
+use axum::middleware;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use lib_core::ctx::Permission;
+use lib_core::model::impersonation::{Impersonation, ImpersonationBmc, ImpersonationForCreate};
+use lib_core::model::ModelManager;
+use lib_utils::time::format_time;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+use ts_rs::TS;
+
+pub fn routes(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/admin/impersonations",
+            get(api_list_impersonations_handler).post(api_create_impersonation_handler),
+        )
+        .route(
+            "/api/admin/impersonations/:id/end",
+            post(api_end_impersonation_handler),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            Permission::Impersonate,
+            mw_ctx_require_permission,
+        ))
+        .with_state(mm)
+}
+
+// region:    --- Create
+async fn api_create_impersonation_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Json(payload): Json<ImpersonationCreateReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_create_impersonation_handler", "HANDLER");
+
+    let ImpersonationCreateReq {
+        user_id,
+        reason,
+        duration_sec,
+    } = payload;
+
+    let imp_c = ImpersonationForCreate {
+        user_id,
+        reason: reason.unwrap_or_default(),
+        duration_sec,
+    };
+
+    // token 仅在此处返回一次，需要通过 Authorization: Bearer 使用
+    let created = ImpersonationBmc::create(&ctx.0, &mm, imp_c).await?;
+
+    let body = Json(json!({
+      "data": ImpersonationCreateResp {
+        id: created.id,
+        token: created.token,
+        expires_at: format_time(created.expires_at),
+      }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, export_to = "impersonation/")]
+struct ImpersonationCreateReq {
+    #[ts(type = "number")]
+    user_id: i64,
+    reason: Option<String>,
+    duration_sec: Option<f64>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "impersonation/")]
+struct ImpersonationCreateResp {
+    #[ts(type = "number")]
+    id: i64,
+    token: String,
+    expires_at: String,
+}
+
+// endregion: --- Create
+
+// region:    --- List
+async fn api_list_impersonations_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_list_impersonations_handler", "HANDLER");
+
+    let imps: Vec<ImpersonationResp> = ImpersonationBmc::list(&ctx.0, &mm)
+        .await?
+        .into_iter()
+        .map(ImpersonationResp::from)
+        .collect();
+
+    let body = Json(json!({
+      "data": imps
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "impersonation/")]
+struct ImpersonationResp {
+    #[ts(type = "number")]
+    id: i64,
+    #[ts(type = "number")]
+    admin_id: i64,
+    #[ts(type = "number")]
+    user_id: i64,
+    reason: String,
+    expires_at: String,
+    ended_at: Option<String>,
+    ctime: String,
+}
+
+impl From<Impersonation> for ImpersonationResp {
+    fn from(imp: Impersonation) -> Self {
+        Self {
+            id: imp.id,
+            admin_id: imp.admin_id,
+            user_id: imp.user_id,
+            reason: imp.reason,
+            expires_at: format_time(imp.expires_at),
+            ended_at: imp.ended_at.map(format_time),
+            ctime: format_time(imp.ctime),
+        }
+    }
+}
+
+// endregion: --- List
+
+// region:    --- End
+async fn api_end_impersonation_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_end_impersonation_handler", "HANDLER");
+
+    ImpersonationBmc::end(&ctx.0, &mm, id).await?;
+
+    let body = Json(json!({
+      "data": ImpersonationEndResp {
+        id,
+        ended: true,
+      }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "impersonation/")]
+struct ImpersonationEndResp {
+    #[ts(type = "number")]
+    id: i64,
+    ended: bool,
+}
+
+// endregion: --- End
@@ -1,4 +1,4 @@
-use crate::web::{self, remove_token_cookie, Error, Result};
+use crate::web::{self, remove_csrf_cookie, remove_token_cookie, Error, Result};
 use axum::extract::State;
 use axum::routing::post;
 use axum::{Json, Router};
@@ -56,8 +56,15 @@ async fn api_login_handler(
     )
     .map_err(|_| Error::LoginFailPwdNotMatching { user_id })?;
 
+    // 申请删除账号后，宽限期内重新登录会取消删除
+    if UserBmc::cancel_deletion(&root_ctx, &mm, user_id).await? {
+        info!("{:<12} - account deletion cancelled: {user_id}", "LOGIN");
+    }
+
     // 设置 web token
     // 此处的 token_salt 是在建表时添加的 uuid
+    // 登录时先移除旧的 csrf cookie，set_token_cookie 会生成新的 csrf token
+    remove_csrf_cookie(&cookies)?;
     web::set_token_cookie(&cookies, &user.username, user.token_salt)?;
 
     let body = Json(json!({
@@ -139,6 +146,7 @@ async fn api_logoff_handler(
 
     if should_logoff {
         remove_token_cookie(&cookies)?;
+        remove_csrf_cookie(&cookies)?;
     }
 
     // Create the success body.
@@ -0,0 +1,279 @@
+use crate::web::mw_auth::CtxW;
+use crate::web::Result;
+use axum::extract::{Path, State};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use lib_core::model::invitation::{Invitation, InvitationBmc};
+use lib_core::model::organization::{
+    Membership, Organization, OrganizationBmc, OrganizationForCreate,
+};
+use lib_core::model::ModelManager;
+use lib_utils::time::format_time;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+use ts_rs::TS;
+
+pub fn routes(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/orgs",
+            get(api_list_orgs_handler).post(api_create_org_handler),
+        )
+        .route("/api/orgs/:id/members", get(api_list_members_handler))
+        .route(
+            "/api/orgs/:id/members/:user_id/remove",
+            post(api_remove_member_handler),
+        )
+        .route(
+            "/api/orgs/:id/invitations",
+            get(api_list_invitations_handler).post(api_create_invitation_handler),
+        )
+        .route(
+            "/api/invitations/accept",
+            post(api_accept_invitation_handler),
+        )
+        .with_state(mm)
+}
+
+// region:    --- Organization
+async fn api_create_org_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Json(payload): Json<OrgCreateReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_create_org_handler", "HANDLER");
+
+    let org_c = OrganizationForCreate { name: payload.name };
+    let id = OrganizationBmc::create(&ctx.0, &mm, org_c).await?;
+
+    let body = Json(json!({
+      "data": OrgCreateResp { id }
+    }));
+
+    Ok(body)
+}
+
+async fn api_list_orgs_handler(State(mm): State<ModelManager>, ctx: CtxW) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_list_orgs_handler", "HANDLER");
+
+    let orgs: Vec<OrgResp> = OrganizationBmc::list_for_user(&ctx.0, &mm)
+        .await?
+        .into_iter()
+        .map(OrgResp::from)
+        .collect();
+
+    let body = Json(json!({
+      "data": orgs
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, export_to = "org/")]
+struct OrgCreateReq {
+    name: String,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "org/")]
+struct OrgCreateResp {
+    #[ts(type = "number")]
+    id: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "org/")]
+struct OrgResp {
+    #[ts(type = "number")]
+    id: i64,
+    name: String,
+    #[ts(type = "number")]
+    owner_id: i64,
+    ctime: String,
+}
+
+impl From<Organization> for OrgResp {
+    fn from(org: Organization) -> Self {
+        Self {
+            id: org.id,
+            name: org.name,
+            owner_id: org.owner_id,
+            ctime: format_time(org.ctime),
+        }
+    }
+}
+
+// endregion: --- Organization
+
+// region:    --- Membership
+async fn api_list_members_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Path(org_id): Path<i64>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_list_members_handler", "HANDLER");
+
+    let members: Vec<MemberResp> = OrganizationBmc::list_members(&ctx.0, &mm, org_id)
+        .await?
+        .into_iter()
+        .map(MemberResp::from)
+        .collect();
+
+    let body = Json(json!({
+      "data": members
+    }));
+
+    Ok(body)
+}
+
+async fn api_remove_member_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Path((org_id, user_id)): Path<(i64, i64)>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_remove_member_handler", "HANDLER");
+
+    OrganizationBmc::remove_member(&ctx.0, &mm, org_id, user_id).await?;
+
+    let body = Json(json!({
+      "data": MemberRemoveResp {
+        org_id,
+        user_id,
+        removed: true,
+      }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "org/")]
+struct MemberResp {
+    #[ts(type = "number")]
+    user_id: i64,
+    role: String,
+    ctime: String,
+}
+
+impl From<Membership> for MemberResp {
+    fn from(membership: Membership) -> Self {
+        Self {
+            user_id: membership.user_id,
+            role: membership.role,
+            ctime: format_time(membership.ctime),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "org/")]
+struct MemberRemoveResp {
+    #[ts(type = "number")]
+    org_id: i64,
+    #[ts(type = "number")]
+    user_id: i64,
+    removed: bool,
+}
+
+// endregion: --- Membership
+
+// region:    --- Invitation
+async fn api_create_invitation_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Path(org_id): Path<i64>,
+    Json(payload): Json<InvitationCreateReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_create_invitation_handler", "HANDLER");
+
+    let invitation = InvitationBmc::create(&ctx.0, &mm, org_id, payload.username).await?;
+
+    let body = Json(json!({
+      "data": InvitationResp::from(invitation)
+    }));
+
+    Ok(body)
+}
+
+async fn api_list_invitations_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Path(org_id): Path<i64>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_list_invitations_handler", "HANDLER");
+
+    let invitations: Vec<InvitationResp> = InvitationBmc::list_pending(&ctx.0, &mm, org_id)
+        .await?
+        .into_iter()
+        .map(InvitationResp::from)
+        .collect();
+
+    let body = Json(json!({
+      "data": invitations
+    }));
+
+    Ok(body)
+}
+
+async fn api_accept_invitation_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Json(payload): Json<InvitationAcceptReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_accept_invitation_handler", "HANDLER");
+
+    let org_id = InvitationBmc::accept(&ctx.0, &mm, &payload.token).await?;
+
+    let body = Json(json!({
+      "data": InvitationAcceptResp { org_id }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, export_to = "org/")]
+struct InvitationCreateReq {
+    username: String,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "org/")]
+struct InvitationResp {
+    #[ts(type = "number")]
+    id: i64,
+    #[ts(type = "number")]
+    org_id: i64,
+    username: String,
+    token: String,
+    expires_at: String,
+}
+
+impl From<Invitation> for InvitationResp {
+    fn from(invitation: Invitation) -> Self {
+        Self {
+            id: invitation.id,
+            org_id: invitation.org_id,
+            username: invitation.username,
+            token: invitation.token,
+            expires_at: format_time(invitation.expires_at),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, export_to = "org/")]
+struct InvitationAcceptReq {
+    token: String,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "org/")]
+struct InvitationAcceptResp {
+    #[ts(type = "number")]
+    org_id: i64,
+}
+
+// endregion: --- Invitation
@@ -0,0 +1,113 @@
+use crate::web::mw_auth::{mw_ctx_require_permission, CtxW};
+use crate::web::Result;
+use axum::extract::State;
+use axum::middleware;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use lib_core::ctx::Permission;
+use lib_core::model::role::{Role, RoleBmc};
+use lib_core::model::ModelManager;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+use ts_rs::TS;
+
+pub fn routes(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/api/roles", get(api_list_roles_handler))
+        .route("/api/roles/assign", post(api_assign_role_handler))
+        .route("/api/roles/unassign", post(api_unassign_role_handler))
+        .route_layer(middleware::from_fn_with_state(
+            Permission::RoleManage,
+            mw_ctx_require_permission,
+        ))
+        .with_state(mm)
+}
+
+// region:    --- List
+async fn api_list_roles_handler(State(mm): State<ModelManager>, ctx: CtxW) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_list_roles_handler", "HANDLER");
+
+    let roles: Vec<RoleResp> = RoleBmc::list(&ctx.0, &mm)
+        .await?
+        .into_iter()
+        .map(RoleResp::from)
+        .collect();
+
+    let body = Json(json!({
+      "data": roles
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "role/")]
+struct RoleResp {
+    #[ts(type = "number")]
+    id: i64,
+    name: String,
+    permissions: Vec<String>,
+}
+
+impl From<Role> for RoleResp {
+    fn from(role: Role) -> Self {
+        Self {
+            permissions: role
+                .permissions
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            id: role.id,
+            name: role.name,
+        }
+    }
+}
+
+// endregion: --- List
+
+// region:    --- Assign / Unassign
+async fn api_assign_role_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Json(payload): Json<UserRoleReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_assign_role_handler", "HANDLER");
+
+    let UserRoleReq { user_id, role_id } = payload;
+    RoleBmc::assign(&ctx.0, &mm, user_id, role_id).await?;
+
+    let body = Json(json!({
+      "data": UserRoleReq { user_id, role_id }
+    }));
+
+    Ok(body)
+}
+
+async fn api_unassign_role_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Json(payload): Json<UserRoleReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_unassign_role_handler", "HANDLER");
+
+    let UserRoleReq { user_id, role_id } = payload;
+    RoleBmc::unassign(&ctx.0, &mm, user_id, role_id).await?;
+
+    let body = Json(json!({
+      "data": UserRoleReq { user_id, role_id }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export, export_to = "role/")]
+struct UserRoleReq {
+    #[ts(type = "number")]
+    user_id: i64,
+    #[ts(type = "number")]
+    role_id: i64,
+}
+
+// endregion: --- Assign / Unassign
@@ -0,0 +1,201 @@
+use crate::web::mw_auth::CtxW;
+use crate::web::{remove_csrf_cookie, remove_token_cookie, Error, Result};
+use axum::extract::State;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use lib_auth::pwd::{self, ContentToHash};
+use lib_core::model::user::{User, UserBmc, UserDataExport, UserForLogin, UserForUpdate};
+use lib_core::model::ModelManager;
+use lib_utils::time::{format_time, now_utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tower_cookies::Cookies;
+use tracing::info;
+use ts_rs::TS;
+
+pub fn routes(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/me",
+            get(api_get_me_handler).patch(api_update_me_handler),
+        )
+        .route("/api/me/export", get(api_export_me_handler))
+        .route("/api/me/delete", post(api_delete_me_handler))
+        .with_state(mm)
+}
+
+// region:    --- Get
+async fn api_get_me_handler(State(mm): State<ModelManager>, ctx: CtxW) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_get_me_handler", "HANDLER");
+
+    let ctx = ctx.0;
+    let user: User = UserBmc::get(&ctx, &mm, ctx.user_id()).await?;
+
+    let body = Json(json!({
+      "data": MeResp::from(user)
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "user/")]
+struct MeResp {
+    #[ts(type = "number")]
+    id: i64,
+    username: String,
+    display_name: Option<String>,
+    avatar_url: Option<String>,
+    locale: Option<String>,
+    time_zone: Option<String>,
+}
+
+impl From<User> for MeResp {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            display_name: user.display_name,
+            avatar_url: user.avatar_url,
+            locale: user.locale,
+            time_zone: user.time_zone,
+        }
+    }
+}
+
+// endregion: --- Get
+
+// region:    --- Update
+async fn api_update_me_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Json(payload): Json<MeUpdateReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_update_me_handler", "HANDLER");
+
+    let ctx = ctx.0;
+    let MeUpdateReq {
+        display_name,
+        avatar_url,
+        locale,
+        time_zone,
+    } = payload;
+
+    // 没有需要修改的字段时直接返回当前资料
+    let has_changes = [&display_name, &avatar_url, &locale, &time_zone]
+        .iter()
+        .any(|field| field.is_some());
+
+    if has_changes {
+        let user_u = UserForUpdate {
+            display_name,
+            avatar_url,
+            locale,
+            time_zone,
+        };
+        UserBmc::update(&ctx, &mm, ctx.user_id(), user_u).await?;
+    }
+
+    let user: User = UserBmc::get(&ctx, &mm, ctx.user_id()).await?;
+
+    let body = Json(json!({
+      "data": MeResp::from(user)
+    }));
+
+    Ok(body)
+}
+
+// 未传的字段不会被修改
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, export_to = "user/")]
+struct MeUpdateReq {
+    display_name: Option<String>,
+    avatar_url: Option<String>,
+    locale: Option<String>,
+    time_zone: Option<String>,
+}
+
+// endregion: --- Update
+
+// region:    --- Export
+async fn api_export_me_handler(State(mm): State<ModelManager>, ctx: CtxW) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_export_me_handler", "HANDLER");
+
+    let ctx = ctx.0;
+    let entities = UserBmc::export(&ctx, &mm, ctx.user_id()).await?;
+
+    let body = Json(json!({
+      "data": MeExportResp {
+        exported_at: format_time(now_utc()),
+        entities,
+      }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "user/")]
+struct MeExportResp {
+    exported_at: String,
+    // 按表名分组的数据
+    #[ts(type = "Record<string, Array<Record<string, unknown>>>")]
+    entities: UserDataExport,
+}
+
+// endregion: --- Export
+
+// region:    --- Delete
+async fn api_delete_me_handler(
+    State(mm): State<ModelManager>,
+    cookies: Cookies,
+    ctx: CtxW,
+    Json(payload): Json<MeDeleteReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_delete_me_handler", "HANDLER");
+
+    let ctx = ctx.0;
+    let user_id = ctx.user_id();
+
+    // 删除账号前需要重新输入密码
+    let user: UserForLogin = UserBmc::get(&ctx, &mm, user_id).await?;
+    let Some(pwd) = user.pwd else {
+        return Err(Error::AccountDeleteFailPwdNotMatching { user_id });
+    };
+    pwd::validate_pwd(
+        &ContentToHash {
+            salt: user.pwd_salt,
+            content: payload.pwd,
+        },
+        &pwd,
+    )
+    .map_err(|_| Error::AccountDeleteFailPwdNotMatching { user_id })?;
+
+    // 宽限期内重新登录会取消删除
+    let due_at = UserBmc::schedule_deletion(&ctx, &mm, user_id).await?;
+
+    remove_token_cookie(&cookies)?;
+    remove_csrf_cookie(&cookies)?;
+
+    let body = Json(json!({
+      "data": MeDeleteResp {
+        deletion_due_at: format_time(due_at),
+      }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, export_to = "user/")]
+struct MeDeleteReq {
+    pwd: String,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "user/")]
+struct MeDeleteResp {
+    deletion_due_at: String,
+}
+
+// endregion: --- Delete
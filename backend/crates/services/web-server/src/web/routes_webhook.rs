@@ -0,0 +1,205 @@
+use crate::web::mw_auth::CtxW;
+use crate::web::Result;
+use axum::extract::{Path, State};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use lib_core::model::webhook::{Webhook, WebhookBmc, WebhookDelivery, WebhookForCreate};
+use lib_core::model::ModelManager;
+use lib_utils::time::format_time;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+use ts_rs::TS;
+
+pub fn routes(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/webhooks",
+            get(api_list_webhooks_handler).post(api_create_webhook_handler),
+        )
+        .route("/api/webhooks/:id", delete(api_delete_webhook_handler))
+        .route(
+            "/api/webhooks/:id/deliveries",
+            get(api_list_webhook_deliveries_handler),
+        )
+        .with_state(mm)
+}
+
+// region:    --- Create
+async fn api_create_webhook_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Json(payload): Json<WebhookCreateReq>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_create_webhook_handler", "HANDLER");
+
+    let WebhookCreateReq { url, event_types } = payload;
+
+    let webhook_c = WebhookForCreate {
+        url,
+        event_types: event_types.unwrap_or_default(),
+    };
+
+    // secret 仅在此处返回一次，接收方用它校验签名
+    let created = WebhookBmc::create(&ctx.0, &mm, webhook_c).await?;
+
+    let body = Json(json!({
+      "data": WebhookCreateResp {
+        id: created.id,
+        secret: created.secret,
+      }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, export_to = "webhook/")]
+struct WebhookCreateReq {
+    url: String,
+    // 为空时订阅所有事件
+    event_types: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "webhook/")]
+struct WebhookCreateResp {
+    #[ts(type = "number")]
+    id: i64,
+    secret: String,
+}
+
+// endregion: --- Create
+
+// region:    --- List
+async fn api_list_webhooks_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_list_webhooks_handler", "HANDLER");
+
+    let webhooks: Vec<WebhookResp> = WebhookBmc::list(&ctx.0, &mm)
+        .await?
+        .into_iter()
+        .map(WebhookResp::from)
+        .collect();
+
+    let body = Json(json!({
+      "data": webhooks
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "webhook/")]
+struct WebhookResp {
+    #[ts(type = "number")]
+    id: i64,
+    #[ts(type = "number | null")]
+    org_id: Option<i64>,
+    url: String,
+    event_types: Vec<String>,
+    ctime: String,
+}
+
+impl From<Webhook> for WebhookResp {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            event_types: webhook.event_type_list(),
+            id: webhook.id,
+            org_id: webhook.org_id,
+            url: webhook.url,
+            ctime: format_time(webhook.ctime),
+        }
+    }
+}
+
+// endregion: --- List
+
+// region:    --- Delete
+async fn api_delete_webhook_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>> {
+    info!("->> {:<12} - api_delete_webhook_handler", "HANDLER");
+
+    WebhookBmc::delete(&ctx.0, &mm, id).await?;
+
+    let body = Json(json!({
+      "data": WebhookDeleteResp {
+        id,
+        deleted: true,
+      }
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "webhook/")]
+struct WebhookDeleteResp {
+    #[ts(type = "number")]
+    id: i64,
+    deleted: bool,
+}
+
+// endregion: --- Delete
+
+// region:    --- Deliveries
+async fn api_list_webhook_deliveries_handler(
+    State(mm): State<ModelManager>,
+    ctx: CtxW,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>> {
+    info!(
+        "->> {:<12} - api_list_webhook_deliveries_handler",
+        "HANDLER"
+    );
+
+    let deliveries: Vec<WebhookDeliveryResp> = WebhookBmc::list_deliveries(&ctx.0, &mm, id)
+        .await?
+        .into_iter()
+        .map(WebhookDeliveryResp::from)
+        .collect();
+
+    let body = Json(json!({
+      "data": deliveries
+    }));
+
+    Ok(body)
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, export_to = "webhook/")]
+struct WebhookDeliveryResp {
+    #[ts(type = "number")]
+    id: i64,
+    #[ts(type = "number")]
+    event_id: i64,
+    event_type: String,
+    attempt: i32,
+    status_code: Option<i32>,
+    error: Option<String>,
+    #[ts(type = "number")]
+    duration_ms: i64,
+    ctime: String,
+}
+
+impl From<WebhookDelivery> for WebhookDeliveryResp {
+    fn from(delivery: WebhookDelivery) -> Self {
+        Self {
+            id: delivery.id,
+            event_id: delivery.event_id,
+            event_type: delivery.event_type,
+            attempt: delivery.attempt,
+            status_code: delivery.status_code,
+            error: delivery.error,
+            duration_ms: delivery.duration_ms,
+            ctime: format_time(delivery.ctime),
+        }
+    }
+}
+
+// endregion: --- Deliveries
@@ -0,0 +1,25 @@
+//! 独立运行的后台任务 worker，和 web-server 中的 worker 共用 job 表
+//!
+//! 需要先由 web-server 执行 migration。
+//!
+
+use lib_core::model::ModelManager;
+use lib_core::worker::{jobs, Worker};
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .without_time()
+        .with_target(false)
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let mm = ModelManager::new().await?;
+
+    info!("{:<12} - worker started", "WORKER");
+    jobs::register(Worker::new(mm)).run().await;
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+-- 创建 user 表
+CREATE TABLE  "user" (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  username varchar(128) NOT NULL UNIQUE,
+
+  -- 验证相关字段
+  pwd varchar(256),
+  -- 下面两个字段交给数据库自动生成 uuid，它将作为加密算法的秘钥进行验证数据一致性
+  pwd_salt uuid NOT NULL DEFAULT gen_random_uuid(),
+  token_salt uuid NOT NULL DEFAULT gen_random_uuid()
+);
+
+-- 创建 api_key 表，用于服务间调用时代替账号密码
+CREATE TABLE api_key (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  user_id BIGINT NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+  name varchar(128) NOT NULL,
+
+  -- prefix 明文保存，用于查找 key，secret 仅保存加密后的结果
+  prefix varchar(32) NOT NULL UNIQUE,
+  key_hash varchar(256) NOT NULL,
+  key_salt uuid NOT NULL DEFAULT gen_random_uuid(),
+
+  -- 以空格分隔的 scope 列表
+  scopes varchar(1024) NOT NULL DEFAULT '',
+
+  expires_at timestamp with time zone,
+  revoked_at timestamp with time zone,
+  ctime timestamp with time zone NOT NULL DEFAULT now()
+);
+
+-- 创建 role 表
+CREATE TABLE role (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  name varchar(64) NOT NULL UNIQUE,
+
+  -- 以空格分隔的权限列表，"*" 表示所有权限
+  permissions varchar(1024) NOT NULL DEFAULT ''
+);
+
+-- 创建 user_role 表，记录用户拥有的角色
+CREATE TABLE user_role (
+  user_id BIGINT NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+  role_id BIGINT NOT NULL REFERENCES role(id) ON DELETE CASCADE,
+
+  PRIMARY KEY (user_id, role_id)
+);
+
+-- 创建 organization 表，每个客户团队对应一个组织
+CREATE TABLE organization (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  name varchar(128) NOT NULL,
+  owner_id BIGINT NOT NULL REFERENCES "user"(id),
+
+  ctime timestamp with time zone NOT NULL DEFAULT now()
+);
+
+-- 创建 membership 表，一个用户可以属于多个组织
+CREATE TABLE membership (
+  org_id BIGINT NOT NULL REFERENCES organization(id) ON DELETE CASCADE,
+  user_id BIGINT NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+
+  -- 组织内的角色：admin 或 member
+  role varchar(32) NOT NULL DEFAULT 'member',
+
+  ctime timestamp with time zone NOT NULL DEFAULT now(),
+
+  PRIMARY KEY (org_id, user_id)
+);
+
+-- 创建 invitation 表，被邀请的用户通过 token 加入组织
+CREATE TABLE invitation (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  org_id BIGINT NOT NULL REFERENCES organization(id) ON DELETE CASCADE,
+  username varchar(128) NOT NULL,
+  token varchar(64) NOT NULL UNIQUE,
+  invited_by BIGINT NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+
+  expires_at timestamp with time zone NOT NULL,
+  accepted_at timestamp with time zone,
+  ctime timestamp with time zone NOT NULL DEFAULT now()
+);
+
+-- 创建 project 表，数据按组织隔离
+CREATE TABLE project (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  org_id BIGINT NOT NULL REFERENCES organization(id) ON DELETE CASCADE,
+  owner_id BIGINT NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+  name varchar(256) NOT NULL,
+
+  ctime timestamp with time zone NOT NULL DEFAULT now()
+);
+
+-- 创建 impersonation 表，记录管理员模拟其他用户登录的审计信息
+CREATE TABLE impersonation (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- admin_id 为发起模拟的管理员，user_id 为被模拟的用户
+  admin_id BIGINT NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+  user_id BIGINT NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+  reason varchar(512) NOT NULL DEFAULT '',
+
+  -- 用于签发和校验模拟登录 token
+  token_salt uuid NOT NULL DEFAULT gen_random_uuid(),
+
+  expires_at timestamp with time zone NOT NULL,
+  ended_at timestamp with time zone,
+  ctime timestamp with time zone NOT NULL DEFAULT now()
+)
@@ -0,0 +1,5 @@
+-- project 支持软删除，deleted_at 不为空表示已删除
+ALTER TABLE project ADD COLUMN deleted_at timestamp with time zone;
+
+-- purge 时按删除时间查找
+CREATE INDEX project_deleted_at_idx ON project (deleted_at) WHERE deleted_at IS NOT NULL;
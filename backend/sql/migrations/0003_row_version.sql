@@ -0,0 +1,2 @@
+-- project 支持乐观锁，每次更新 version 加 1
+ALTER TABLE project ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
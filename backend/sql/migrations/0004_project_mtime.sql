@@ -0,0 +1,2 @@
+-- project 记录最后修改时间，由 base 的 update 函数写入
+ALTER TABLE project ADD COLUMN mtime timestamp with time zone NOT NULL DEFAULT now();
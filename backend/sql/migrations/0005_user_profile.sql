@@ -0,0 +1,6 @@
+-- 用户资料，都可以为空，长度通过 check 约束限制，超出时返回 INVALID_PARAMS
+ALTER TABLE "user"
+  ADD COLUMN display_name text CONSTRAINT user_display_name_len CHECK (char_length(display_name) <= 64),
+  ADD COLUMN avatar_url text CONSTRAINT user_avatar_url_len CHECK (char_length(avatar_url) <= 1024),
+  ADD COLUMN locale text CONSTRAINT user_locale_len CHECK (char_length(locale) <= 16),
+  ADD COLUMN time_zone text CONSTRAINT user_time_zone_len CHECK (char_length(time_zone) <= 64);
@@ -0,0 +1,7 @@
+-- 用户申请删除账号后，宽限期结束时间之后数据会被删除或匿名化
+ALTER TABLE "user"
+  ADD COLUMN deletion_due_at timestamp with time zone,
+  ADD COLUMN erased_at timestamp with time zone;
+
+-- 定时任务按到期时间查找需要删除的账号
+CREATE INDEX user_deletion_due_at_idx ON "user" (deletion_due_at) WHERE deletion_due_at IS NOT NULL;
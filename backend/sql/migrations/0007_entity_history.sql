@@ -0,0 +1,17 @@
+-- 开启 HISTORY 的实体在 update/delete 时把修改之前的数据写入该表
+CREATE TABLE entity_history (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- 实体的表名和 id
+  entity varchar(64) NOT NULL,
+  entity_id BIGINT NOT NULL,
+  -- update 或 delete
+  op varchar(16) NOT NULL,
+  data jsonb NOT NULL,
+
+  -- 执行修改的用户，root ctx 为 0，所以不设置外键
+  changed_by BIGINT NOT NULL,
+  ctime timestamp with time zone NOT NULL DEFAULT now()
+);
+
+CREATE INDEX entity_history_entity_idx ON entity_history (entity, entity_id);
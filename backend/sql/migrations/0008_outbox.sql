@@ -0,0 +1,24 @@
+-- 领域事件，和产生事件的修改在同一个事务中写入
+CREATE TABLE outbox (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- 写入事件的事务 id，消费者只读取更早的事务都已结束的事件
+  txid BIGINT NOT NULL DEFAULT (pg_current_xact_id()::text::bigint),
+
+  event_type varchar(64) NOT NULL,
+  payload jsonb NOT NULL,
+
+  ctime timestamp with time zone NOT NULL DEFAULT now()
+);
+
+CREATE INDEX outbox_txid_id_idx ON outbox (txid, id);
+
+-- 每个消费者已处理到的位置
+CREATE TABLE outbox_cursor (
+  name varchar(64) PRIMARY KEY,
+
+  txid BIGINT NOT NULL,
+  event_id BIGINT NOT NULL,
+
+  mtime timestamp with time zone NOT NULL DEFAULT now()
+);
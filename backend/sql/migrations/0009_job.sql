@@ -0,0 +1,26 @@
+-- 后台任务队列，worker 通过 FOR UPDATE SKIP LOCKED 领取任务
+CREATE TABLE job (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  job_type varchar(64) NOT NULL,
+  payload jsonb NOT NULL,
+
+  -- pending: 等待执行，running: 执行中，done: 已完成，dead: 超过重试次数，不再执行
+  status varchar(16) NOT NULL DEFAULT 'pending',
+  attempts INT NOT NULL DEFAULT 0,
+  max_attempts INT NOT NULL,
+  -- 下次可以执行的时间，失败后按重试次数推迟
+  run_at timestamp with time zone NOT NULL DEFAULT now(),
+  -- 被领取的时间，执行中的任务超时后可以被重新领取
+  locked_at timestamp with time zone,
+  last_error text,
+
+  ctime timestamp with time zone NOT NULL DEFAULT now(),
+  mtime timestamp with time zone NOT NULL DEFAULT now(),
+
+  CONSTRAINT job_status_check CHECK (status IN ('pending', 'running', 'done', 'dead')),
+  CONSTRAINT job_max_attempts_check CHECK (max_attempts > 0)
+);
+
+CREATE INDEX job_pending_run_at_idx ON job (run_at) WHERE status = 'pending';
+CREATE INDEX job_running_locked_at_idx ON job (locked_at) WHERE status = 'running';
@@ -0,0 +1,8 @@
+-- 定时任务最近一次执行的 tick，多个实例中只有一个会执行同一个 tick
+CREATE TABLE scheduled_task (
+  name varchar(64) PRIMARY KEY,
+
+  last_tick timestamp with time zone NOT NULL,
+
+  mtime timestamp with time zone NOT NULL DEFAULT now()
+);
@@ -0,0 +1,38 @@
+-- 出站 webhook 订阅，org_id 为空时订阅 user_id 自己的事件，否则订阅组织的事件
+CREATE TABLE webhook (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- 创建者
+  user_id BIGINT NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+  org_id BIGINT REFERENCES organization(id) ON DELETE CASCADE,
+
+  url varchar(2048) NOT NULL,
+  -- 每次投递都需要用它签名，只能明文保存
+  secret varchar(128) NOT NULL,
+  -- 以空格分隔的事件类型，为空时订阅所有事件
+  event_types varchar(1024) NOT NULL DEFAULT '',
+
+  ctime timestamp with time zone NOT NULL DEFAULT now()
+);
+
+CREATE INDEX webhook_user_id_idx ON webhook (user_id) WHERE org_id IS NULL;
+CREATE INDEX webhook_org_id_idx ON webhook (org_id) WHERE org_id IS NOT NULL;
+
+-- 每次投递的记录，重试时每次一条
+CREATE TABLE webhook_delivery (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  webhook_id BIGINT NOT NULL REFERENCES webhook(id) ON DELETE CASCADE,
+  event_id BIGINT NOT NULL,
+  event_type varchar(64) NOT NULL,
+
+  attempt INT NOT NULL,
+  -- 没有收到响应时为空
+  status_code INT,
+  error text,
+  duration_ms BIGINT NOT NULL,
+
+  ctime timestamp with time zone NOT NULL DEFAULT now()
+);
+
+CREATE INDEX webhook_delivery_webhook_id_idx ON webhook_delivery (webhook_id, id);
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ApiKeyCreateReq = { name: string, scopes: Array<string> | null, expires_in_sec: number | null, };
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ApiKeyResp = { id: number, name: string, prefix: string, scopes: Array<string>, expires_at: string | null, revoked_at: string | null, ctime: string, };
\ No newline at end of file
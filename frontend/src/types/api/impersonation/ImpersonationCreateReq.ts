@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ImpersonationCreateReq = { user_id: number, reason: string | null, duration_sec: number | null, };
\ No newline at end of file
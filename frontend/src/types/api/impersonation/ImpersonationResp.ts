@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ImpersonationResp = { id: number, admin_id: number, user_id: number, reason: string, expires_at: string, ended_at: string | null, ctime: string, };
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type MeResp = { id: number, username: string, display_name: string | null, avatar_url: string | null, locale: string | null, time_zone: string | null, };
\ No newline at end of file
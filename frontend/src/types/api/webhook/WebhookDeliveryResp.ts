@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type WebhookDeliveryResp = { id: number, event_id: number, event_type: string, attempt: number, status_code: number | null, error: string | null, duration_ms: number, ctime: string, };
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type WebhookResp = { id: number, org_id: number | null, url: string, event_types: Array<string>, ctime: string, };
\ No newline at end of file
@@ -21,6 +21,20 @@ const httpPost = async <Q = any, R = Object>(url: string, body?: Q, config?: Axi
 }
 
 
+// 服务端使用 double-submit 的方式校验 CSRF，需要把 csrf-token cookie 放到请求头中
+axiosInstance.interceptors.request.use((config) => {
+  const csrfToken = document.cookie
+    .split('; ')
+    .find((item) => item.startsWith('csrf-token='))
+    ?.split('=')[1];
+
+  if (csrfToken) {
+    config.headers['X-CSRF-Token'] = csrfToken;
+  }
+
+  return config;
+})
+
 axiosInstance.interceptors.response.use((response) => {
 
   if (response.status !== 200) {